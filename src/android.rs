@@ -0,0 +1,100 @@
+use std::{
+    ffi::CString,
+    io::{
+        self,
+        Cursor,
+        Read,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use ndk::asset::{
+    Asset,
+    AssetManager,
+};
+
+use crate::{
+    BackendMeta,
+    FileBackend,
+};
+
+/// the assets packaged into an APK, read through the `AAssetManager` of the activity, e.g.
+/// with `GemFileSystem::with_backend`. Android only lists the files of a directory, never its
+/// subdirectories, so `list` only sees files in the top level and in the directories given
+/// to `new`. assets have no modification time
+pub struct AndroidBackend {
+    assets: AssetManager,
+    dirs: Vec<PathBuf>,
+}
+
+// an AAssetManager may be used from any thread, and every asset opened from it is read and
+// closed again within one call
+unsafe impl Send for AndroidBackend {}
+
+unsafe impl Sync for AndroidBackend {}
+
+impl AndroidBackend {
+    /// `dirs` like "models" or "levels/chapter1"
+    pub fn new(assets: AssetManager, dirs: &[&str]) -> AndroidBackend {
+        AndroidBackend {
+            assets,
+            dirs: dirs.iter().map(PathBuf::from).collect(),
+        }
+    }
+    
+    fn open_asset(&self, file_path: &Path) -> io::Result<Asset> {
+        self.assets.open(&asset_path(file_path)?).ok_or_else(|| {
+            let mut err = String::from("No such asset: ");
+            err.push_str(&format!("{:#?}", file_path));
+            io::Error::new(io::ErrorKind::NotFound, err)
+        })
+    }
+}
+
+impl FileBackend for AndroidBackend {
+    fn open(&self, file_path: &Path) -> io::Result<Box<dyn Read + Send + '_>> {
+        Ok(Box::new(Cursor::new(self.read_all(file_path)?)))
+    }
+    
+    fn read_all(&self, file_path: &Path) -> io::Result<Vec<u8>> {
+        let mut asset = self.open_asset(file_path)?;
+        let mut content = Vec::with_capacity(asset.length());
+        asset.read_to_end(&mut content)?;
+        Ok(content)
+    }
+    
+    fn metadata(&self, file_path: &Path) -> io::Result<BackendMeta> {
+        Ok(BackendMeta {
+            size: self.open_asset(file_path)?.length() as u64,
+            ..BackendMeta::default()
+        })
+    }
+    
+    fn list(&self) -> io::Result<Vec<(PathBuf, u64)>> {
+        let mut files = Vec::new();
+        for dir in std::iter::once(Path::new("")).chain(self.dirs.iter().map(PathBuf::as_path)) {
+            let listed = match self.assets.open_dir(&asset_path(dir)?) {
+                Some(listed) => listed,
+                None => continue,
+            };
+            for name in listed {
+                let file_path = dir.join(&*name.to_string_lossy());
+                if let Ok(asset) = self.open_asset(&file_path) {
+                    files.push((file_path, asset.length() as u64));
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// the name the asset manager knows a file by, always with `/` separators
+fn asset_path(file_path: &Path) -> io::Result<CString> {
+    let components: Vec<_> = file_path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    CString::new(components.join("/")).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
@@ -0,0 +1,176 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{
+        self,
+        Read,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        Mutex,
+        MutexGuard,
+    },
+    time::SystemTime,
+};
+
+use crate::{
+    lock,
+    FileContent,
+    FileMeta,
+    GfsError,
+};
+
+/// a `.zip` file mounted with `GemFileSystem::mount_zip`. the archive is opened again
+/// whenever its modification time changes, so sync checks see a replaced archive
+pub(crate) struct ZipMount {
+    pub(crate) path: PathBuf,
+    opened: Mutex<Opened>,
+}
+
+struct Opened {
+    archive: zip::ZipArchive<fs::File>,
+    modified: Option<SystemTime>,
+    // CRC-32, index and uncompressed size of every file entry, keyed like the cache
+    entries: HashMap<PathBuf, (u32, usize, u64)>,
+}
+
+impl ZipMount {
+    pub(crate) fn open(path: &Path) -> Result<ZipMount, GfsError> {
+        Ok(ZipMount {
+            path: path.to_path_buf(),
+            opened: Mutex::new(Opened::open(path)?),
+        })
+    }
+    
+    pub(crate) fn contains(&self, key: &Path) -> bool {
+        self.opened().entries.contains_key(key)
+    }
+    
+    /// the CRC-32 the archive stores for an entry, None if there is no such entry
+    pub(crate) fn crc32(&self, key: &Path) -> Option<u32> {
+        self.opened().entries.get(key).map(|(crc, _, _)| *crc)
+    }
+    
+    /// every file in the archive with its uncompressed size
+    pub(crate) fn files(&self) -> Vec<(PathBuf, u64)> {
+        self.opened().entries.iter().map(|(key, (_, _, size))| (key.clone(), *size)).collect()
+    }
+    
+    /// decompress an entry, None if there is no such entry
+    pub(crate) fn read(&self, key: &Path) -> Option<io::Result<(FileContent, FileMeta)>> {
+        let mut opened = self.opened();
+        let (crc, index, _) = *opened.entries.get(key)?;
+        let modified = opened.modified;
+        let read = opened.archive.by_index(index)
+            .map_err(io::Error::from)
+            .and_then(|mut entry| {
+                let mut content = Vec::with_capacity(entry.size() as usize);
+                entry.read_to_end(&mut content)?;
+                Ok(content)
+            })
+            .map(|content| {
+                let meta = FileMeta {
+                    modified,
+                    archive_crc: Some(crc),
+                    ..FileMeta::default()
+                };
+                (FileContent::from(content), meta)
+            });
+        Some(read)
+    }
+    
+    fn opened(&self) -> MutexGuard<'_, Opened> {
+        let mut opened = lock(&self.opened);
+        if modified(&self.path) != opened.modified {
+            match Opened::open(&self.path) {
+                Ok(reopened) => *opened = reopened,
+                // keep serving what was there, the archive may be half written
+                Err(err) => warn!("failed to reopen {}: {}", self.path.display(), err),
+            }
+        }
+        opened
+    }
+}
+
+impl Opened {
+    fn open(path: &Path) -> Result<Opened, GfsError> {
+        let modified = modified(path);
+        let mut archive = zip::ZipArchive::new(fs::File::open(path)?).map_err(io::Error::from)?;
+        let mut entries = HashMap::new();
+        for index in 0..archive.len() {
+            let entry = archive.by_index(index).map_err(io::Error::from)?;
+            if entry.is_dir() {
+                continue;
+            }
+            entries.insert(PathBuf::from(entry.name()), (entry.crc32(), index, entry.size()));
+        }
+        Ok(Opened {
+            archive,
+            modified,
+            entries,
+        })
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        tests::test_root,
+        FileSyncState,
+        GemFileSystem,
+        ReadFile,
+    };
+    use std::{
+        fs,
+        io::Write,
+        path::Path,
+        thread,
+        time::Duration,
+    };
+    
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, content) in files {
+            zip.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+    
+    #[test]
+    fn reads_and_syncs_through_archives() {
+        let root = test_root("zip");
+        fs::create_dir_all(root.join("base/models")).unwrap();
+        fs::write(root.join("base/models/chest.obj"), b"loose").unwrap();
+        let pack = root.join("pack.zip");
+        write_zip(&pack, &[("models/chest.obj", b"zipped"), ("models/lid.obj", b"lid")]);
+        let mut gfs = GemFileSystem::new(root.join("base"));
+        gfs.mount_zip(&pack, 1).unwrap();
+    
+        assert_eq!(&gfs.read_file("models/chest.obj").unwrap()[..], b"zipped");
+        assert_eq!(&gfs.read_file("models/lid.obj").unwrap()[..], b"lid");
+        assert!(gfs.read_file("models/missing.obj").is_err());
+        assert_eq!(gfs.check_for_sync_file("models/chest.obj").unwrap(), FileSyncState::HashMatch);
+        let listed: Vec<_> = gfs.list_dir("models").unwrap().into_iter().map(|entry| (entry.name, entry.size)).collect();
+        assert_eq!(listed, [(String::from("chest.obj"), 6), (String::from("lid.obj"), 3)]);
+    
+        // make sure the new archive gets a different modification time
+        thread::sleep(Duration::from_millis(20));
+        write_zip(&pack, &[("models/chest.obj", b"patched"), ("models/lid.obj", b"lid")]);
+        assert_eq!(gfs.check_for_sync_file("models/chest.obj").unwrap(), FileSyncState::HashUnmatch);
+        assert_eq!(gfs.check_for_sync_file("models/lid.obj").unwrap(), FileSyncState::HashMatch);
+        assert!(gfs.reload_if_changed("models/chest.obj").unwrap());
+        assert_eq!(&gfs.read_file("models/chest.obj").unwrap()[..], b"patched");
+    
+        assert!(gfs.unmount(&pack));
+        gfs.clear();
+        assert_eq!(&gfs.read_file("models/chest.obj").unwrap()[..], b"loose");
+    }
+}
@@ -0,0 +1,571 @@
+use std::{
+    any::{
+        Any,
+        TypeId,
+    },
+    collections::{
+        BTreeSet,
+        HashMap,
+    },
+    ffi::OsStr,
+    fmt,
+    ops::Deref,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        Arc,
+        Weak,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use crate::{
+    ChangeToken,
+    GfsError,
+};
+
+/// parses the content of a file into a `T`, see `GemFileSystem::register_loader`.
+/// any `Fn(&Path, &[u8]) -> Result<T, GfsError>` is one
+pub trait AssetLoader<T>: Send + Sync {
+    fn load(&self, file_path: &Path, bytes: &[u8]) -> Result<T, GfsError>;
+    
+    /// the other files `asset` was built from, e.g. the textures of a material, as paths
+    /// `load` takes. none by default
+    fn dependencies(&self, _file_path: &Path, _asset: &T) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+impl<T, F> AssetLoader<T> for F
+    where F: Fn(&Path, &[u8]) -> Result<T, GfsError> + Send + Sync {
+    fn load(&self, file_path: &Path, bytes: &[u8]) -> Result<T, GfsError> {
+        self(file_path, bytes)
+    }
+}
+
+pub(crate) type AnyAsset = Arc<dyn Any + Send + Sync>;
+
+/// an asset of whichever type `GemFileSystem::load_any` found a loader for
+#[derive(Clone)]
+pub struct ErasedAsset {
+    pub(crate) asset: AnyAsset,
+    pub(crate) type_name: &'static str,
+}
+
+impl ErasedAsset {
+    /// the asset if it is a `T`
+    pub fn downcast<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        Arc::clone(&self.asset).downcast().ok()
+    }
+    
+    pub fn is<T: 'static>(&self) -> bool {
+        self.asset.is::<T>()
+    }
+    
+    /// of the asset, as `std::any::type_name` has it
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+impl fmt::Debug for ErasedAsset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ErasedAsset {{ type: {} }}", self.type_name)
+    }
+}
+
+/// parses an asset along with its dependencies
+pub(crate) type ErasedLoader = Arc<dyn Fn(&Path, &[u8]) -> Result<(AnyAsset, Vec<PathBuf>), GfsError> + Send + Sync>;
+
+/// names an asset handed out by `GemFileSystem::acquire`, for as long as it stays loaded.
+/// cheap to copy and store anywhere, and never mistaken for a later asset once it is unloaded
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HandleId {
+    index: u32,
+    generation: u32,
+}
+
+/// a live reference to an asset from `GemFileSystem::acquire`. while any handle to it is
+/// around, the asset and the file it was parsed from are never unloaded by `unload_unused`
+pub struct Handle<T> {
+    asset: Arc<T>,
+    live: Arc<HandleId>,
+}
+
+impl<T> Handle<T> {
+    pub fn id(&self) -> HandleId {
+        *self.live
+    }
+    
+    /// the asset itself, to hold on to without keeping it loaded
+    pub fn asset(&self) -> &Arc<T> {
+        &self.asset
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Handle<T> {
+        Handle {
+            asset: Arc::clone(&self.asset),
+            live: Arc::clone(&self.live),
+        }
+    }
+}
+
+impl<T> Deref for Handle<T> {
+    type Target = T;
+    
+    fn deref(&self) -> &T {
+        &self.asset
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle {{ id: {:?}, asset: {:?} }}", self.id(), self.asset)
+    }
+}
+
+/// what a `HandleId` stands for, reused with the next generation once it is unloaded
+#[derive(Default)]
+struct Slot {
+    generation: u32,
+    asset: Option<SlotAsset>,
+}
+
+struct SlotAsset {
+    key: PathBuf,
+    type_id: TypeId,
+    /// alive for as long as any handle is
+    live: Weak<HandleId>,
+    /// when `unload_unused` first found no handle left
+    unused_since: Option<Instant>,
+}
+
+/// the loaders registered with a file system and the assets they parsed
+#[derive(Default)]
+pub(crate) struct Assets {
+    loaders: HashMap<TypeId, ErasedLoader>,
+    /// which type `load_any` loads files with an extension as, with its name
+    extensions: HashMap<String, (TypeId, &'static str)>,
+    /// by cache key and type, along with the version of the file each was parsed from
+    loaded: HashMap<PathBuf, HashMap<TypeId, (AnyAsset, ChangeToken)>>,
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
+    /// of every asset handles were acquired for
+    slot_of: HashMap<(PathBuf, TypeId), u32>,
+    /// the files each file depends on, as its loaders and `add_dependency` declared
+    dependencies: HashMap<PathBuf, BTreeSet<PathBuf>>,
+}
+
+impl Assets {
+    pub(crate) fn register<T, L>(&mut self, loader: L)
+        where T: Send + Sync + 'static,
+              L: AssetLoader<T> + 'static {
+        let erased: ErasedLoader = Arc::new(move |file_path: &Path, bytes: &[u8]| {
+            let asset = loader.load(file_path, bytes)?;
+            let dependencies = loader.dependencies(file_path, &asset);
+            Ok((Arc::new(asset) as AnyAsset, dependencies))
+        });
+        self.loaders.insert(TypeId::of::<T>(), erased);
+        // parsed by the loader being replaced
+        for assets in self.loaded.values_mut() {
+            assets.remove(&TypeId::of::<T>());
+        }
+    }
+    
+    pub(crate) fn loader(&self, type_id: TypeId) -> Option<ErasedLoader> {
+        self.loaders.get(&type_id).map(Arc::clone)
+    }
+    
+    pub(crate) fn map_extension<T: 'static>(&mut self, extension: &str) {
+        let type_id = (TypeId::of::<T>(), std::any::type_name::<T>());
+        self.extensions.insert(String::from(extension.trim_start_matches('.')), type_id);
+    }
+    
+    /// the type files with the extension of `file_path` are loaded as by `load_any`
+    pub(crate) fn type_for(&self, file_path: &Path) -> Option<(TypeId, &'static str)> {
+        let extension = file_path.extension()?;
+        self.extensions.iter()
+            .find(|(registered, _)| OsStr::new(registered) == extension)
+            .map(|(_, type_id)| *type_id)
+    }
+    
+    /// the asset parsed from the version of the file `token` stands for, if it still is around
+    pub(crate) fn loaded(&self, key: &Path, type_id: TypeId, token: ChangeToken) -> Option<AnyAsset> {
+        match self.loaded.get(key)?.get(&type_id)? {
+            (asset, loaded_from) if *loaded_from == token => Some(Arc::clone(asset)),
+            _ => None,
+        }
+    }
+    
+    pub(crate) fn store(&mut self, key: &Path, type_id: TypeId, asset: AnyAsset, token: ChangeToken) {
+        self.loaded.entry(key.to_path_buf()).or_default().insert(type_id, (asset, token));
+    }
+    
+    /// a handle to `asset`, shared with every other handle to the same file and type
+    pub(crate) fn handle<T>(&mut self, key: &Path, type_id: TypeId, asset: Arc<T>) -> Handle<T> {
+        let index = match self.slot_of.get(&(key.to_path_buf(), type_id)) {
+            Some(index) => *index,
+            None => {
+                let index = match self.free_slots.pop() {
+                    Some(index) => index,
+                    None => {
+                        self.slots.push(Slot::default());
+                        (self.slots.len() - 1) as u32
+                    }
+                };
+                self.slot_of.insert((key.to_path_buf(), type_id), index);
+                index
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        let live = match slot.asset.as_ref().and_then(|asset| asset.live.upgrade()) {
+            Some(live) => live,
+            None => Arc::new(HandleId {
+                index,
+                generation: slot.generation,
+            }),
+        };
+        slot.asset = Some(SlotAsset {
+            key: key.to_path_buf(),
+            type_id,
+            live: Arc::downgrade(&live),
+            unused_since: None,
+        });
+        Handle {
+            asset,
+            live,
+        }
+    }
+    
+    /// the file and type `id` was handed out for, if there still is a handle to it
+    pub(crate) fn handle_of(&self, id: HandleId) -> Option<(PathBuf, Arc<HandleId>)> {
+        let slot = self.slots.get(id.index as usize)?;
+        match &slot.asset {
+            Some(asset) if slot.generation == id.generation => Some((asset.key.clone(), asset.live.upgrade()?)),
+            _ => None,
+        }
+    }
+    
+    /// files with no handle left to any asset parsed from them since `grace` before `now`,
+    /// which are forgotten along with their handle ids. without a `now` there is no grace
+    pub(crate) fn unused(&mut self, now: Option<Instant>, grace: Duration) -> Vec<PathBuf> {
+        let mut unused = Vec::new();
+        let mut in_use = Vec::new();
+        for slot in &mut self.slots {
+            let asset = match &mut slot.asset {
+                Some(asset) => asset,
+                None => continue,
+            };
+            if asset.live.strong_count() > 0 {
+                in_use.push(asset.key.clone());
+                continue;
+            }
+            let waited = match now {
+                Some(now) => now.saturating_duration_since(*asset.unused_since.get_or_insert(now)) >= grace,
+                None => true,
+            };
+            if waited {
+                unused.push(asset.key.clone());
+            }
+        }
+        // what assets in use depend on stays loaded, as does another type parsed from the same file
+        let mut index = 0;
+        while index < in_use.len() {
+            for dependency in self.dependencies(&in_use[index]) {
+                if !in_use.contains(&dependency) {
+                    in_use.push(dependency);
+                }
+            }
+            index += 1;
+        }
+        unused.retain(|key| !in_use.contains(key));
+        unused.sort();
+        unused.dedup();
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            let unloaded = match &slot.asset {
+                Some(asset) => unused.contains(&asset.key),
+                None => false,
+            };
+            if unloaded {
+                if let Some(asset) = slot.asset.take() {
+                    self.slot_of.remove(&(asset.key, asset.type_id));
+                }
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free_slots.push(index as u32);
+            }
+        }
+        unused
+    }
+    
+    /// drop every asset parsed from a file under `dir`, along with what they depend on
+    pub(crate) fn forget_prefix(&mut self, dir: &Path) {
+        self.loaded.retain(|key, _| !key.starts_with(dir));
+        self.dependencies.retain(|key, _| !key.starts_with(dir));
+    }
+    
+    /// drop the assets parsed from a file, so they are parsed again on the next load
+    pub(crate) fn forget_parsed(&mut self, key: &Path) {
+        self.loaded.remove(key);
+    }
+    
+    pub(crate) fn add_dependencies<I: IntoIterator<Item = PathBuf>>(&mut self, key: &Path, dependencies: I) {
+        self.dependencies.entry(key.to_path_buf()).or_default().extend(dependencies);
+    }
+    
+    pub(crate) fn set_dependencies(&mut self, key: &Path, dependencies: Vec<PathBuf>) {
+        self.dependencies.remove(key);
+        if !dependencies.is_empty() {
+            self.add_dependencies(key, dependencies);
+        }
+    }
+    
+    pub(crate) fn dependencies(&self, key: &Path) -> Vec<PathBuf> {
+        self.dependencies.get(key).map_or_else(Vec::new, |dependencies| dependencies.iter().cloned().collect())
+    }
+    
+    /// every file depending on `key`, directly or through other files, in path order
+    pub(crate) fn dependents(&self, key: &Path) -> Vec<PathBuf> {
+        let mut found = BTreeSet::new();
+        let mut pending = vec![key.to_path_buf()];
+        while let Some(dependency) = pending.pop() {
+            for (dependent, dependencies) in &self.dependencies {
+                if dependencies.contains(&dependency) && dependent != key && found.insert(dependent.clone()) {
+                    pending.push(dependent.clone());
+                }
+            }
+        }
+        found.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        Clock,
+        EvictDependents,
+        GemFileSystem,
+        ReadFile,
+        GfsError,
+        WriteFile,
+    };
+    use std::{
+        fs,
+        io,
+        path::Path,
+        sync::{
+            atomic::{
+                AtomicUsize,
+                Ordering,
+            },
+            Arc,
+            Mutex,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    };
+    
+    #[derive(Debug, PartialEq)]
+    struct Texture {
+        width: u32,
+        height: u32,
+    }
+    
+    #[test]
+    fn assets_are_parsed_once_per_version() {
+        let root = test_root("assets");
+        fs::create_dir_all(root.join("textures")).unwrap();
+        fs::write(root.join("textures/wood.png"), b"16x16").unwrap();
+        fs::write(root.join("textures/broken.png"), b"16").unwrap();
+        let parsed = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&parsed);
+        let mut gfs = GemFileSystem::new(&root);
+        assert!(matches!(gfs.load::<Texture>("textures/wood.png"), Err(GfsError::NoLoader { .. })));
+        gfs.register_loader(move |file_path: &Path, bytes: &[u8]| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let text = String::from_utf8_lossy(bytes);
+            let size = text.split_once('x').and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+            match size {
+                Some((width, height)) => Ok(Texture { width, height }),
+                None => Err(GfsError::Io(io::Error::new(io::ErrorKind::InvalidData, format!("{:#?}", file_path)))),
+            }
+        });
+    
+        let wood = gfs.load::<Texture>("textures/wood.png").unwrap();
+        assert_eq!(*wood, Texture { width: 16, height: 16 });
+        assert!(Arc::ptr_eq(&wood, &gfs.load::<Texture>("textures/wood.png").unwrap()));
+        assert_eq!(parsed.load(Ordering::SeqCst), 1);
+        assert!(gfs.load::<Texture>("textures/broken.png").is_err());
+    
+        gfs.write_file("textures/wood.png", b"32x8").unwrap();
+        assert_eq!(*gfs.load::<Texture>("textures/wood.png").unwrap(), Texture { width: 32, height: 8 });
+        gfs.evict("textures/wood.png");
+        gfs.load::<Texture>("textures/wood.png").unwrap();
+        assert_eq!(parsed.load(Ordering::SeqCst), 4);
+    }
+    
+    #[test]
+    fn uncached_files_load_as_assets() {
+        let root = test_root("uncached_assets");
+        let shared = test_root("uncached_assets_shared");
+        fs::write(shared.join("remote.png"), b"4x4").unwrap();
+        fs::write(root.join("huge.png"), b"1024x1024").unwrap();
+        let parsed = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&parsed);
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.add_fallback_root(&shared, false);
+        gfs.stream_threshold(Some(4))
+            .register_loader(move |_: &Path, bytes: &[u8]| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let text = String::from_utf8_lossy(bytes);
+                let (width, height) = text.split_once('x').unwrap();
+                Ok(Texture { width: width.parse().unwrap(), height: height.parse().unwrap() })
+            });
+    
+        assert_eq!(*gfs.load::<Texture>("remote.png").unwrap(), Texture { width: 4, height: 4 });
+        assert_eq!(*gfs.acquire::<Texture>("huge.png").unwrap(), Texture { width: 1024, height: 1024 });
+        assert!(gfs.cache().content_map.is_empty());
+        // nothing is cached to tell whether the file changed, so it is parsed again
+        gfs.load::<Texture>("remote.png").unwrap();
+        assert_eq!(parsed.load(Ordering::SeqCst), 3);
+    }
+    
+    #[test]
+    fn extensions_pick_the_loader() {
+        let root = test_root("any_assets");
+        fs::create_dir_all(root.join("models")).unwrap();
+        fs::write(root.join("models/chest.obj"), b"8x4").unwrap();
+        fs::write(root.join("models/chest.mtl"), b"wood").unwrap();
+        fs::write(root.join("models/chest.txt"), b"notes").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.register_loader(|_: &Path, bytes: &[u8]| Ok(Texture { width: bytes.len() as u32, height: 1 }))
+            .register_loader(|_: &Path, bytes: &[u8]| Ok(String::from_utf8_lossy(bytes).into_owned()))
+            .extension_loader::<Texture>(".obj")
+            .extension_loader::<String>("mtl");
+    
+        let chest = gfs.load_any("models/chest.obj").unwrap();
+        assert!(chest.is::<Texture>());
+        assert_eq!(*chest.downcast::<Texture>().unwrap(), Texture { width: 3, height: 1 });
+        assert!(chest.downcast::<String>().is_none());
+        // shared with typed loads of the same file
+        assert!(Arc::ptr_eq(&chest.downcast::<Texture>().unwrap(), &gfs.load::<Texture>("models/chest.obj").unwrap()));
+        assert_eq!(&*gfs.load_any("models/chest.mtl").unwrap().downcast::<String>().unwrap(), "wood");
+        assert!(matches!(gfs.load_any("models/chest.txt"), Err(GfsError::NoLoader { .. })));
+    }
+    
+    #[derive(Clone)]
+    struct ManualClock(Arc<Mutex<Instant>>);
+    
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+    
+    #[test]
+    fn assets_unload_once_unused() {
+        let root = test_root("asset_handles");
+        fs::create_dir_all(root.join("levels")).unwrap();
+        fs::write(root.join("levels/forest.lvl"), b"trees").unwrap();
+        fs::write(root.join("levels/cave.lvl"), b"rocks").unwrap();
+        let clock = ManualClock(Arc::new(Mutex::new(Instant::now())));
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.register_loader(|_: &Path, bytes: &[u8]| Ok(String::from_utf8_lossy(bytes).into_owned()))
+            .set_clock(clock.clone())
+            .unload_grace(Duration::from_secs(5));
+    
+        let forest = gfs.acquire::<String>("levels/forest.lvl").unwrap();
+        let cave = gfs.acquire::<String>("levels/cave.lvl").unwrap();
+        let cave_id = cave.id();
+        assert_eq!(&*forest, "trees");
+        assert_eq!(forest.id(), forest.clone().id());
+        assert_ne!(forest.id(), cave_id);
+        assert_eq!(&*gfs.handle::<String>(cave_id).unwrap(), "rocks");
+        drop(cave);
+        assert_eq!(gfs.unload_unused(), 0);
+        *clock.0.lock().unwrap() += Duration::from_secs(5);
+        assert_eq!(gfs.unload_unused(), 1);
+        assert!(!gfs.cache().content_map.contains_key(Path::new("levels/cave.lvl")));
+        assert!(gfs.cache().content_map.contains_key(Path::new("levels/forest.lvl")));
+        assert!(gfs.handle::<String>(cave_id).is_none());
+        // the id is not handed out again for whatever takes the slot next
+        let cave = gfs.acquire::<String>("levels/cave.lvl").unwrap();
+        assert_ne!(cave.id(), cave_id);
+        assert_eq!(&*cave, "rocks");
+    }
+    
+    #[test]
+    fn without_a_clock_there_is_no_grace() {
+        let root = test_root("asset_no_clock");
+        fs::write(root.join("forest.lvl"), b"trees").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.register_loader(|_: &Path, bytes: &[u8]| Ok(String::from_utf8_lossy(bytes).into_owned()))
+            .unload_grace(Duration::from_secs(5));
+        
+        let forest = gfs.acquire::<String>("forest.lvl").unwrap();
+        assert!(crate::lock(&gfs.assets).unused(None, Duration::from_secs(5)).is_empty());
+        drop(forest);
+        // what `unload_unused` does on the web unless `set_clock` gave it a clock
+        assert_eq!(crate::lock(&gfs.assets).unused(None, Duration::from_secs(5)), [Path::new("forest.lvl")]);
+    }
+    
+    struct Material {
+        textures: Vec<PathBuf>,
+    }
+    
+    /// a material lists the textures it uses, one per line
+    struct MaterialLoader;
+    
+    impl AssetLoader<Material> for MaterialLoader {
+        fn load(&self, _: &Path, bytes: &[u8]) -> Result<Material, GfsError> {
+            let textures = String::from_utf8_lossy(bytes).lines().map(PathBuf::from).collect();
+            Ok(Material { textures })
+        }
+    
+        fn dependencies(&self, _: &Path, material: &Material) -> Vec<PathBuf> {
+            material.textures.clone()
+        }
+    }
+    
+    #[test]
+    fn dependents_follow_their_dependencies() {
+        let root = test_root("dependencies");
+        fs::create_dir_all(root.join("textures")).unwrap();
+        fs::write(root.join("textures/wood.png"), b"brown").unwrap();
+        fs::write(root.join("textures/bark.png"), b"dark").unwrap();
+        fs::write(root.join("wood.mat"), b"textures/wood.png\ntextures/bark.png").unwrap();
+        fs::write(root.join("table.mesh"), b"legs").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.register_loader(MaterialLoader);
+    
+        let material = gfs.load::<Material>("wood.mat").unwrap();
+        assert_eq!(gfs.dependencies("wood.mat"), vec![PathBuf::from("textures/bark.png"), PathBuf::from("textures/wood.png")]);
+        gfs.add_dependency("table.mesh", "wood.mat");
+        assert_eq!(gfs.dependents("textures/wood.png"), vec![PathBuf::from("table.mesh"), PathBuf::from("wood.mat")]);
+        assert!(gfs.dependents("table.mesh").is_empty());
+    
+        // a texture changing on disk has the material parsed again
+        assert_eq!(gfs.apply_change_feed(["textures/wood.png"]), 0);
+        assert!(Arc::ptr_eq(&material, &gfs.load::<Material>("wood.mat").unwrap()));
+        gfs.read_file("textures/wood.png").unwrap();
+        assert_eq!(gfs.apply_change_feed(["textures/wood.png"]), 1);
+        assert!(!Arc::ptr_eq(&material, &gfs.load::<Material>("wood.mat").unwrap()));
+    
+        gfs.read_file("table.mesh").unwrap();
+        gfs.read_file("textures/bark.png").unwrap();
+        gfs.evict_dependents(EvictDependents::Cascade);
+        gfs.evict("textures/bark.png");
+        assert!(!gfs.cache().content_map.contains_key(Path::new("wood.mat")));
+        assert!(!gfs.cache().content_map.contains_key(Path::new("table.mesh")));
+    }
+}
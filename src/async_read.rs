@@ -0,0 +1,105 @@
+use std::{
+    future::Future,
+    path::Path,
+    time::SystemTime,
+};
+
+use crate::{
+    FileContent,
+    FileMeta,
+    GemFileSystem,
+    GfsError,
+    HashMode,
+    MAX_READ_RETRIES,
+};
+
+/// same as `ReadFile`, for use inside an async runtime. files are loaded with `tokio::fs`
+/// instead of blocking the runtime, and end up in the same cache the sync API reads from.
+/// named apart from `ReadFile::read_file` so both traits can be in scope at once
+pub trait AsyncReadFile {
+    fn read_file_async<P: AsRef<Path>>(&self, file_path: P)
+        -> impl Future<Output=Result<FileContent, GfsError>> + Send + '_;
+}
+
+impl AsyncReadFile for GemFileSystem {
+    fn read_file_async<P: AsRef<Path>>(&self, file_path: P)
+        -> impl Future<Output=Result<FileContent, GfsError>> + Send + '_ {
+        let file_path = self.cache_key(file_path);
+        async move {
+            if let Some(content) = self.cached_content(&file_path) {
+                self.cache().record_hit();
+                return Ok(content);
+            }
+            self.cache().record_miss();
+            let resolved = self.resolve(&file_path);
+            self.log_miss(&file_path, resolved.is_some());
+            let (absolute_path, cache) = match resolved {
+                Some(resolved) => resolved,
+                None => return Err(self.not_found_error(&file_path)),
+            };
+            debug!("{}", absolute_path.display());
+    
+            let (content, meta) = load_stable(&absolute_path).await?;
+            if meta.volatile {
+                warn!("{} kept changing while being read", absolute_path.display());
+            }
+            self.cache().record_disk_read(content.len() as u64);
+            let content = FileContent::from(content);
+            if cache {
+                return Ok(self.store_loaded(&file_path, content, meta, HashMode::Default, None));
+            }
+            Ok(content)
+        }
+    }
+}
+
+/// `crate::load_stable` with `tokio::fs`
+async fn load_stable(absolute_path: &Path) -> Result<(Vec<u8>, FileMeta), GfsError> {
+    let mut retries = 0;
+    loop {
+        let before = modified(absolute_path).await;
+        let content = tokio::fs::read(absolute_path).await?;
+        let after = modified(absolute_path).await;
+        if before == after || retries == MAX_READ_RETRIES {
+            let meta = FileMeta {
+                modified: after,
+                volatile: before != after,
+                ..FileMeta::default()
+            };
+            return Ok((content, meta));
+        }
+        retries += 1;
+    }
+}
+
+async fn modified(absolute_path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(absolute_path).await.and_then(|meta| meta.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        ReadFile,
+    };
+    use std::fs;
+    
+    #[test]
+    fn shares_the_cache_with_sync_reads() {
+        let root = test_root("async_read");
+        fs::write(root.join("song.ogg"), b"async").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    
+        let content = runtime.block_on(gfs.read_file_async("song.ogg")).unwrap();
+        assert_eq!(&content[..], b"async");
+        assert!(gfs.cache().content_map.contains_key(Path::new("song.ogg")));
+        // served from the cache, so the sync API sees the same content
+        let sync_content = gfs.read_file("song.ogg").unwrap();
+        assert_eq!(&sync_content[..], &content[..]);
+        assert!(matches!(runtime.block_on(gfs.read_file_async("missing.ogg")),
+                         Err(GfsError::NotFound { .. })));
+        assert_eq!(gfs.stats().misses, 2);
+    }
+}
@@ -0,0 +1,404 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{
+        self,
+        Cursor,
+        Read,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        Arc,
+        RwLock,
+        RwLockReadGuard,
+        RwLockWriteGuard,
+    },
+    time::SystemTime,
+};
+
+use crate::{
+    normalize,
+    pack,
+    FileContent,
+    GfsError,
+    ReadFile,
+    Throttle,
+    WriteFile,
+};
+
+/// what a `FileBackend` knows about one of its files
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BackendMeta {
+    /// in bytes
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// changes whenever the content does, like an HTTP ETag. sync checks compare it instead
+    /// of reading and hashing the file
+    pub etag: Option<String>,
+}
+
+/// a source of files other than a directory, pack or archive, e.g. a network share or
+/// generated data, mounted with `GemFileSystem::mount_backend`. files read from it are cached,
+/// hashed and synced like any other. paths are keys like `read_file` takes, normalized and
+/// relative to the backend, with `/` separators
+pub trait FileBackend: Send + Sync {
+    /// a reader over a file, failing with `io::ErrorKind::NotFound` if there is no such file
+    fn open(&self, file_path: &Path) -> io::Result<Box<dyn Read + Send + '_>>;
+    
+    /// the whole content of a file, read through `open` unless the backend has a faster way
+    fn read_all(&self, file_path: &Path) -> io::Result<Vec<u8>> {
+        let mut content = Vec::new();
+        self.open(file_path)?.read_to_end(&mut content)?;
+        Ok(content)
+    }
+    
+    /// failing with `io::ErrorKind::NotFound` if there is no such file. lookups ask for it to
+    /// find out whether the backend has a file at all, so keep it cheap
+    fn metadata(&self, file_path: &Path) -> io::Result<BackendMeta>;
+    
+    /// every file with its size. a backend that can't enumerate its files may list none, its
+    /// files can still be read but `list_dir`, `glob` and case-insensitive lookups miss them
+    fn list(&self) -> io::Result<Vec<(PathBuf, u64)>>;
+    
+    /// what the backend downloads through, for `GemFileSystem::stats` to count its throughput
+    fn throttle(&self) -> Option<&Throttle> {
+        None
+    }
+}
+
+/// so a backend can be mounted and still be reached, e.g. to keep filling a `FetchBackend`
+impl<B: FileBackend + ?Sized> FileBackend for Arc<B> {
+    fn open(&self, file_path: &Path) -> io::Result<Box<dyn Read + Send + '_>> {
+        (**self).open(file_path)
+    }
+    
+    fn read_all(&self, file_path: &Path) -> io::Result<Vec<u8>> {
+        (**self).read_all(file_path)
+    }
+    
+    fn metadata(&self, file_path: &Path) -> io::Result<BackendMeta> {
+        (**self).metadata(file_path)
+    }
+    
+    fn list(&self) -> io::Result<Vec<(PathBuf, u64)>> {
+        (**self).list()
+    }
+    
+    fn throttle(&self) -> Option<&Throttle> {
+        (**self).throttle()
+    }
+}
+
+/// a directory as a `FileBackend`. mounting it with `mount_backend` skips what only plain
+/// directory mounts do, like `mmap_extension`, `symlink_policy` and torn read detection
+pub struct DirBackend {
+    root: PathBuf,
+}
+
+impl DirBackend {
+    pub fn new<P: AsRef<Path>>(root: P) -> DirBackend {
+        DirBackend {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl FileBackend for DirBackend {
+    fn open(&self, file_path: &Path) -> io::Result<Box<dyn Read + Send + '_>> {
+        Ok(Box::new(fs::File::open(self.root.join(file_path))?))
+    }
+    
+    fn read_all(&self, file_path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(self.root.join(file_path))
+    }
+    
+    fn metadata(&self, file_path: &Path) -> io::Result<BackendMeta> {
+        let meta = fs::metadata(self.root.join(file_path))?;
+        if !meta.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "Not a file"));
+        }
+        Ok(BackendMeta {
+            size: meta.len(),
+            modified: meta.modified().ok(),
+            etag: None,
+        })
+    }
+    
+    fn list(&self) -> io::Result<Vec<(PathBuf, u64)>> {
+        let mut files = Vec::new();
+        pack::collect_files(&self.root, &self.root, &mut files)?;
+        Ok(files.into_iter()
+            .filter_map(|(key, absolute_path)| Some((PathBuf::from(key), fs::metadata(absolute_path).ok()?.len())))
+            .collect())
+    }
+}
+
+/// files kept in memory only, for tests of code taking a `ReadFile` or `WriteFile` that
+/// shouldn't need a directory on disk. can be mounted with `mount_backend` as well
+#[derive(Default)]
+pub struct MemoryBackend {
+    files: RwLock<HashMap<PathBuf, FileContent>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> MemoryBackend {
+        MemoryBackend::default()
+    }
+    
+    /// add a file, or replace it. the path is normalized like a cache key
+    pub fn insert<P: AsRef<Path>>(&self, file_path: P, bytes: Vec<u8>) -> Result<(), GfsError> {
+        let key = key(file_path.as_ref())?;
+        self.write().insert(key, FileContent::from(bytes));
+        Ok(())
+    }
+    
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<PathBuf, FileContent>> {
+        self.files.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<PathBuf, FileContent>> {
+        self.files.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    
+    fn get(&self, file_path: &Path) -> Result<FileContent, GfsError> {
+        let key = key(file_path)?;
+        match self.read().get(&key) {
+            Some(content) => Ok(content.clone()),
+            None => Err(GfsError::NotFound { path: key }),
+        }
+    }
+}
+
+impl From<HashMap<PathBuf, Vec<u8>>> for MemoryBackend {
+    fn from(files: HashMap<PathBuf, Vec<u8>>) -> MemoryBackend {
+        let memory = MemoryBackend::new();
+        for (file_path, bytes) in files {
+            // a path that would leave the root can't be looked up anyway
+            let _ = memory.insert(file_path, bytes);
+        }
+        memory
+    }
+}
+
+impl ReadFile for MemoryBackend {
+    fn read_file<P: AsRef<Path>>(&self, file_path: P) -> Result<FileContent, GfsError> {
+        self.get(file_path.as_ref())
+    }
+}
+
+impl WriteFile for MemoryBackend {
+    fn write_file<P: AsRef<Path>>(&self, file_path: P, bytes: &[u8]) -> Result<(), GfsError> {
+        self.insert(file_path, bytes.to_vec())
+    }
+    
+    fn remove_file<P: AsRef<Path>>(&self, file_path: P) -> Result<(), GfsError> {
+        let key = key(file_path.as_ref())?;
+        match self.write().remove(&key) {
+            Some(_) => Ok(()),
+            None => Err(GfsError::NotFound { path: key }),
+        }
+    }
+    
+    fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> Result<(), GfsError> {
+        let (from, to) = (key(from.as_ref())?, key(to.as_ref())?);
+        let mut files = self.write();
+        match files.remove(&from) {
+            Some(content) => {
+                files.insert(to, content);
+                Ok(())
+            }
+            None => Err(GfsError::NotFound { path: from }),
+        }
+    }
+}
+
+impl FileBackend for MemoryBackend {
+    fn open(&self, file_path: &Path) -> io::Result<Box<dyn Read + Send + '_>> {
+        Ok(Box::new(Cursor::new(self.get(file_path)?)))
+    }
+    
+    fn read_all(&self, file_path: &Path) -> io::Result<Vec<u8>> {
+        Ok(self.get(file_path)?.to_vec())
+    }
+    
+    fn metadata(&self, file_path: &Path) -> io::Result<BackendMeta> {
+        Ok(BackendMeta {
+            size: self.get(file_path)?.len() as u64,
+            modified: None,
+            etag: None,
+        })
+    }
+    
+    fn list(&self) -> io::Result<Vec<(PathBuf, u64)>> {
+        Ok(self.read().iter().map(|(key, content)| (key.clone(), content.len() as u64)).collect())
+    }
+}
+
+/// files compiled into the binary, so a small tool can ship as a single executable.
+/// usually made with `embed!`, and mounted with `mount_backend` or read from directly
+pub struct EmbeddedBackend {
+    files: HashMap<PathBuf, &'static [u8]>,
+}
+
+impl EmbeddedBackend {
+    /// every path is normalized like a cache key, paths that would leave the root are dropped
+    pub fn new(files: &[(&str, &'static [u8])]) -> EmbeddedBackend {
+        EmbeddedBackend {
+            files: files.iter()
+                .filter_map(|(file_path, bytes)| Some((key(Path::new(file_path)).ok()?, *bytes)))
+                .collect(),
+        }
+    }
+    
+    fn get(&self, file_path: &Path) -> Result<&'static [u8], GfsError> {
+        let key = key(file_path)?;
+        match self.files.get(&key) {
+            Some(bytes) => Ok(*bytes),
+            None => Err(GfsError::NotFound { path: key }),
+        }
+    }
+}
+
+impl ReadFile for EmbeddedBackend {
+    fn read_file<P: AsRef<Path>>(&self, file_path: P) -> Result<FileContent, GfsError> {
+        Ok(FileContent::from(self.get(file_path.as_ref())?.to_vec()))
+    }
+}
+
+impl FileBackend for EmbeddedBackend {
+    fn open(&self, file_path: &Path) -> io::Result<Box<dyn Read + Send + '_>> {
+        Ok(Box::new(self.get(file_path)?))
+    }
+    
+    fn metadata(&self, file_path: &Path) -> io::Result<BackendMeta> {
+        Ok(BackendMeta {
+            size: self.get(file_path)?.len() as u64,
+            ..BackendMeta::default()
+        })
+    }
+    
+    fn list(&self) -> io::Result<Vec<(PathBuf, u64)>> {
+        Ok(self.files.iter().map(|(key, bytes)| (key.clone(), bytes.len() as u64)).collect())
+    }
+}
+
+/// an `EmbeddedBackend` with files `include_bytes!`-ed relative to the crate root, keyed by
+/// the paths given. `embed!(in "assets": "models/chest.obj")` embeds
+/// "assets/models/chest.obj" as "models/chest.obj"
+#[macro_export]
+macro_rules! embed {
+    (in $dir:literal: $($file_path:literal),+ $(,)?) => {
+        $crate::EmbeddedBackend::new(&[
+            $(($file_path, include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $dir, "/", $file_path)))),+
+        ])
+    };
+    ($($file_path:literal),+ $(,)?) => {
+        $crate::EmbeddedBackend::new(&[
+            $(($file_path, include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $file_path)))),+
+        ])
+    };
+}
+
+/// percent-encode everything but the characters URLs allow unencoded
+#[cfg(any(feature = "http", feature = "s3"))]
+pub(crate) fn percent_encode(segment: &str) -> String {
+    segment.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn key(file_path: &Path) -> Result<PathBuf, GfsError> {
+    normalize(file_path).ok_or_else(|| GfsError::OutsideRoot { path: file_path.to_path_buf() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        FileSyncState,
+        GemFileSystem,
+    };
+    use std::env;
+    
+    /// every `level<n>.txt` for n below 3, with its name as content
+    struct Generated;
+    
+    impl FileBackend for Generated {
+        fn open(&self, file_path: &Path) -> io::Result<Box<dyn Read + Send + '_>> {
+            let content = self.metadata(file_path).map(|_| file_path.to_string_lossy().into_owned().into_bytes())?;
+            Ok(Box::new(io::Cursor::new(content)))
+        }
+    
+        fn metadata(&self, file_path: &Path) -> io::Result<BackendMeta> {
+            match self.list()?.into_iter().find(|(key, _)| key == file_path) {
+                Some((_, size)) => Ok(BackendMeta { size, ..BackendMeta::default() }),
+                None => Err(io::Error::new(io::ErrorKind::NotFound, "No such level")),
+            }
+        }
+    
+        fn list(&self) -> io::Result<Vec<(PathBuf, u64)>> {
+            Ok((0..3).map(|n| (PathBuf::from(format!("levels/level{}.txt", n)), 15)).collect())
+        }
+    }
+    
+    #[test]
+    fn backends_are_mounted_like_directories() {
+        let root = test_root("backend");
+        fs::create_dir_all(root.join("base")).unwrap();
+        fs::create_dir_all(root.join("dlc/levels")).unwrap();
+        fs::write(root.join("dlc/levels/level1.txt"), b"dlc level").unwrap();
+        let mut gfs = GemFileSystem::new(root.join("base"));
+        gfs.mount_backend("generated", Generated, 0)
+            .mount_backend("dlc", DirBackend::new(root.join("dlc")), 1);
+    
+        assert_eq!(&gfs.read_file("levels/level0.txt").unwrap()[..], b"levels/level0.txt");
+        assert_eq!(&gfs.read_file("levels/level1.txt").unwrap()[..], b"dlc level");
+        assert_eq!(gfs.check_for_sync_file("levels/level0.txt").unwrap(), FileSyncState::HashMatch);
+        assert_eq!(gfs.list_dir("levels").unwrap().len(), 3);
+        assert_eq!(gfs.glob("levels/*.txt").count(), 3);
+        assert!(gfs.read_file("levels/level3.txt").is_err());
+        assert!(gfs.unmount("generated"));
+        gfs.clear();
+        assert!(gfs.read_file("levels/level0.txt").is_err());
+    }
+    
+    #[test]
+    fn memory_backends_stand_in_for_disk() {
+        fn sizes<R: ReadFile>(files: &R, file_paths: &[&str]) -> Result<u64, GfsError> {
+            file_paths.iter().map(|file_path| Ok(files.read_file(file_path)?.len() as u64)).sum()
+        }
+    
+        let memory = MemoryBackend::from(HashMap::from([(PathBuf::from("save/slot1.dat"), b"level 3".to_vec())]));
+        memory.write_file("save/./slot2.dat", b"level 7").unwrap();
+        assert_eq!(sizes(&memory, &["save/slot1.dat", "save/slot2.dat"]).unwrap(), 14);
+        memory.rename("save/slot2.dat", "save/slot3.dat").unwrap();
+        memory.remove_file("save/slot1.dat").unwrap();
+        assert!(matches!(memory.read_file("save/slot1.dat"), Err(GfsError::NotFound { .. })));
+        assert!(matches!(memory.read_file("../slot1.dat"), Err(GfsError::OutsideRoot { .. })));
+    
+        let mut gfs = GemFileSystem::new(env::temp_dir().join("gfs_no_such_root"));
+        gfs.mount_backend("memory", memory, 0);
+        assert_eq!(&gfs.read_file("save/slot3.dat").unwrap()[..], b"level 7");
+    }
+    
+    #[test]
+    fn embedded_files_are_served() {
+        let embedded = embed!(in "src": "glob.rs", "./backend.rs");
+        assert_eq!(&embedded.read_file("glob.rs").unwrap()[..], include_bytes!("glob.rs"));
+        assert!(embedded.read_file("lib.rs").is_err());
+        let mut gfs = GemFileSystem::new(env::temp_dir().join("gfs_no_such_root"));
+        gfs.mount_backend("embedded", embedded, 0)
+            .mount_backend("manifest", embed!("Cargo.toml"), 0);
+        assert_eq!(&gfs.read_file("backend.rs").unwrap()[..], include_bytes!("backend.rs"));
+        assert!(gfs.read_file("Cargo.toml").is_ok());
+        assert_eq!(gfs.list_dir("").unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,35 @@
+//! build a gfs pack from a directory tree, to be mounted with `GemFileSystem::mount_pack`
+//!
+//! usage: gfs-pack <dir> <pack>
+use std::{
+    env,
+    fs,
+    io::BufWriter,
+    path::PathBuf,
+    process,
+};
+
+use gfs::Sha256Hasher;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (dir, pack) = match &args[..] {
+        [dir, pack] => (PathBuf::from(dir), PathBuf::from(pack)),
+        _ => {
+            eprintln!("usage: gfs-pack <dir> <pack>");
+            process::exit(2);
+        }
+    };
+    let packed = fs::File::create(&pack)
+        .map_err(gfs::GfsError::from)
+        .and_then(|file| gfs::write_pack(&dir, &mut BufWriter::new(file), &Sha256Hasher));
+    match packed {
+        Ok(count) => println!("packed {} files from {} into {}", count, dir.display(), pack.display()),
+        Err(err) => {
+            eprintln!("gfs-pack: {}", err);
+            // don't leave a truncated pack behind
+            let _ = fs::remove_file(&pack);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    path::Path,
+    time::Duration,
+};
+
+use crate::{
+    ContentHasher,
+    GemFileSystem,
+    HashTiming,
+    SymlinkPolicy,
+    SyncPolicy,
+};
+
+/// a `GemFileSystem` being set up, see `GemFileSystem::builder`. every option starts out as
+/// `GemFileSystem::new` has it, and anything without a method here is set with `configure`
+pub struct GemFileSystemBuilder {
+    gfs: GemFileSystem,
+}
+
+impl GemFileSystem {
+    /// set up a file system in one expression, e.g.
+    /// `GemFileSystem::builder(root).case_insensitive(true).max_cache_bytes(256 << 20).build()`
+    pub fn builder<P: AsRef<Path>>(root: P) -> GemFileSystemBuilder {
+        GemFileSystemBuilder {
+            gfs: GemFileSystem::new(root),
+        }
+    }
+}
+
+impl GemFileSystemBuilder {
+    /// see `GemFileSystem::case_insensitive`
+    pub fn case_insensitive(mut self, enabled: bool) -> GemFileSystemBuilder {
+        self.gfs.case_insensitive(enabled);
+        self
+    }
+    
+    /// see `GemFileSystem::lowercase_keys`
+    pub fn lowercase_keys(mut self, enabled: bool) -> GemFileSystemBuilder {
+        self.gfs.lowercase_keys(enabled);
+        self
+    }
+    
+    /// see `GemFileSystem::symlink_policy`
+    pub fn symlink_policy(mut self, policy: SymlinkPolicy) -> GemFileSystemBuilder {
+        self.gfs.symlink_policy(policy);
+        self
+    }
+    
+    /// the budget of the cache, see `Cache::set_budget`
+    pub fn max_cache_bytes(mut self, bytes: usize) -> GemFileSystemBuilder {
+        self.gfs.cache_budget(Some(bytes));
+        self
+    }
+    
+    /// see `GemFileSystem::stream_threshold`
+    pub fn max_cacheable_file_bytes(mut self, bytes: u64) -> GemFileSystemBuilder {
+        self.gfs.stream_threshold(Some(bytes));
+        self
+    }
+    
+    /// see `GemFileSystem::buffer_size`
+    pub fn buffer_size(mut self, bytes: usize) -> GemFileSystemBuilder {
+        self.gfs.buffer_size(bytes);
+        self
+    }
+    
+    /// see `GemFileSystem::hash_timing`
+    pub fn hash_timing(mut self, timing: HashTiming) -> GemFileSystemBuilder {
+        self.gfs.hash_timing(timing);
+        self
+    }
+    
+    /// see `GemFileSystem::sync_policy`
+    pub fn sync_policy(mut self, policy: SyncPolicy) -> GemFileSystemBuilder {
+        self.gfs.sync_policy(policy);
+        self
+    }
+    
+    /// see `GemFileSystem::set_hasher`
+    pub fn hasher<H: ContentHasher + 'static>(mut self, hasher: H) -> GemFileSystemBuilder {
+        self.gfs.set_hasher(hasher);
+        self
+    }
+    
+    /// see `GemFileSystem::deduplicate`
+    pub fn deduplicate(mut self, enabled: bool) -> GemFileSystemBuilder {
+        self.gfs.deduplicate(enabled);
+        self
+    }
+    
+    /// see `GemFileSystem::min_reload_interval`
+    pub fn min_reload_interval(mut self, interval: Duration) -> GemFileSystemBuilder {
+        self.gfs.min_reload_interval(interval);
+        self
+    }
+    
+    /// anything else, e.g. `.configure(|gfs| { gfs.mount("mods", 10); })`
+    pub fn configure<F: FnOnce(&mut GemFileSystem)>(mut self, configure: F) -> GemFileSystemBuilder {
+        configure(&mut self.gfs);
+        self
+    }
+    
+    pub fn build(self) -> GemFileSystem {
+        self.gfs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        ReadFile,
+    };
+    use std::fs;
+    
+    #[test]
+    fn builder_sets_every_option() {
+        let root = test_root("builder");
+        fs::create_dir_all(root.join("mods")).unwrap();
+        fs::write(root.join("Config.toml"), b"volume = 1").unwrap();
+        fs::write(root.join("mods/skin.png"), b"skin").unwrap();
+        fs::write(root.join("huge.bin"), vec![0u8; 64]).unwrap();
+        let gfs = GemFileSystem::builder(&root)
+            .case_insensitive(true)
+            .max_cache_bytes(256 << 20)
+            .max_cacheable_file_bytes(32)
+            .configure(|gfs| {
+                gfs.alias("mods", "mods");
+            })
+            .build();
+        assert_eq!(gfs.cache().budget(), Some(256 << 20));
+        assert_eq!(&gfs.read_file("config.toml").unwrap()[..], b"volume = 1");
+        assert_eq!(&gfs.read_file("mods://skin.png").unwrap()[..], b"skin");
+        gfs.read_file("huge.bin").unwrap();
+        assert!(!gfs.is_cached("huge.bin"));
+    }
+}
@@ -0,0 +1,83 @@
+use std::io;
+#[cfg(feature = "lz4")]
+use std::io::Read;
+
+/// how files with an extension set up with `GemFileSystem::decompress_extension` are stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    /// a zstd frame, as written by the `zstd` command line tool
+    #[cfg(feature = "zstd")]
+    Zstd,
+    /// an LZ4 frame, as written by the `lz4` command line tool. unrelated to
+    /// `Cache::compress_cache`, which keeps content compressed in memory
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+impl Codec {
+    pub fn decompress(self, compressed: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => zstd::stream::decode_all(compressed),
+            #[cfg(feature = "lz4")]
+            Codec::Lz4 => {
+                let mut content = Vec::new();
+                lz4_flex::frame::FrameDecoder::new(compressed).read_to_end(&mut content)?;
+                Ok(content)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        FileSyncState,
+        GemFileSystem,
+        ReadFile,
+    };
+    use std::{
+        fs,
+        path::Path,
+    };
+    
+    fn compress(codec: Codec, content: &[u8]) -> Vec<u8> {
+        match codec {
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => zstd::stream::encode_all(content, 3).unwrap(),
+            #[cfg(feature = "lz4")]
+            Codec::Lz4 => {
+                use std::io::Write;
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+                encoder.write_all(content).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+    
+    fn check_codec(codec: Codec, extension: &str) {
+        let root = test_root(&format!("codec_{}", extension));
+        let name = format!("level.json.{}", extension);
+        let level = vec![b'{'; 4096];
+        fs::write(root.join(&name), compress(codec, &level)).unwrap();
+        fs::write(root.join("broken.bin"), b"not compressed").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.decompress_extension(extension, codec).decompress_extension("bin", codec);
+    
+        assert_eq!(&gfs.read_file(&name).unwrap()[..], &level[..]);
+        // cached decompressed, and synced against the decompressed file
+        assert_eq!(gfs.cache().meta_map[Path::new(&name)].size, level.len() as u64);
+        assert_eq!(gfs.check_for_sync_file(&name).unwrap(), FileSyncState::HashMatch);
+        assert!(gfs.read_file("broken.bin").is_err());
+    }
+    
+    #[test]
+    fn compressed_files_are_cached_decompressed() {
+        #[cfg(feature = "zstd")]
+        check_codec(Codec::Zstd, "zst");
+        #[cfg(feature = "lz4")]
+        check_codec(Codec::Lz4, "lz4");
+    }
+}
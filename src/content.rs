@@ -0,0 +1,213 @@
+use std::{
+    fmt,
+    ops::Deref,
+    sync::{
+        Arc,
+        Weak,
+    },
+};
+
+#[cfg(feature = "mmap")]
+use crate::store::AnonMap;
+
+/// a handle to the content of a file, cheap to clone and shared with the cache, so content
+/// stays alive for as long as any caller holds it. usually a heap block, but files mapped
+/// because of `GemFileSystem::mmap_extension` are served from the memory map itself, and
+/// a cache using an `MmapStore` keeps content in anonymous maps
+#[derive(Clone)]
+pub struct FileContent {
+    bytes: Bytes,
+}
+
+#[derive(Clone)]
+enum Bytes {
+    Heap(Arc<[u8]>),
+    #[cfg(feature = "mmap")]
+    Mapped(Arc<memmap2::Mmap>),
+    #[cfg(feature = "mmap")]
+    Anon(Arc<AnonMap>),
+}
+
+/// a `FileContent` that doesn't keep the content alive, see `Cache::deduplicate`
+#[derive(Clone)]
+pub(crate) enum WeakContent {
+    Heap(Weak<[u8]>),
+    #[cfg(feature = "mmap")]
+    Mapped(Weak<memmap2::Mmap>),
+    #[cfg(feature = "mmap")]
+    Anon(Weak<AnonMap>),
+}
+
+impl WeakContent {
+    pub(crate) fn upgrade(&self) -> Option<FileContent> {
+        let bytes = match self {
+            WeakContent::Heap(content) => Bytes::Heap(content.upgrade()?),
+            #[cfg(feature = "mmap")]
+            WeakContent::Mapped(map) => Bytes::Mapped(map.upgrade()?),
+            #[cfg(feature = "mmap")]
+            WeakContent::Anon(map) => Bytes::Anon(map.upgrade()?),
+        };
+        Some(FileContent {
+            bytes,
+        })
+    }
+    
+    pub(crate) fn is_alive(&self) -> bool {
+        match self {
+            WeakContent::Heap(content) => content.strong_count() > 0,
+            #[cfg(feature = "mmap")]
+            WeakContent::Mapped(map) => map.strong_count() > 0,
+            #[cfg(feature = "mmap")]
+            WeakContent::Anon(map) => map.strong_count() > 0,
+        }
+    }
+}
+
+impl FileContent {
+    #[cfg(feature = "mmap")]
+    pub(crate) fn mapped(map: memmap2::Mmap) -> FileContent {
+        FileContent {
+            bytes: Bytes::Mapped(Arc::new(map)),
+        }
+    }
+    
+    #[cfg(feature = "mmap")]
+    pub(crate) fn anonymous(map: AnonMap) -> FileContent {
+        FileContent {
+            bytes: Bytes::Anon(Arc::new(map)),
+        }
+    }
+    
+    /// the content is a memory map of the file rather than a copy of it
+    pub fn is_mapped(&self) -> bool {
+        match self.bytes {
+            Bytes::Heap(_) => false,
+            #[cfg(feature = "mmap")]
+            Bytes::Mapped(_) => true,
+            #[cfg(feature = "mmap")]
+            Bytes::Anon(_) => false,
+        }
+    }
+    
+    /// the content is on the regular heap, not in a memory map of any kind
+    #[cfg_attr(not(feature = "mmap"), allow(dead_code))]
+    pub(crate) fn is_heap(&self) -> bool {
+        matches!(self.bytes, Bytes::Heap(_))
+    }
+    
+    pub(crate) fn downgrade(&self) -> WeakContent {
+        match &self.bytes {
+            Bytes::Heap(content) => WeakContent::Heap(Arc::downgrade(content)),
+            #[cfg(feature = "mmap")]
+            Bytes::Mapped(map) => WeakContent::Mapped(Arc::downgrade(map)),
+            #[cfg(feature = "mmap")]
+            Bytes::Anon(map) => WeakContent::Anon(Arc::downgrade(map)),
+        }
+    }
+    
+    /// both handles refer to the same content, not merely to equal content
+    pub fn ptr_eq(this: &FileContent, other: &FileContent) -> bool {
+        this.as_ptr() == other.as_ptr() && this.len() == other.len()
+    }
+}
+
+impl Deref for FileContent {
+    type Target = [u8];
+    
+    fn deref(&self) -> &[u8] {
+        match &self.bytes {
+            Bytes::Heap(content) => content,
+            #[cfg(feature = "mmap")]
+            Bytes::Mapped(map) => map,
+            #[cfg(feature = "mmap")]
+            Bytes::Anon(map) => map.bytes(),
+        }
+    }
+}
+
+impl AsRef<[u8]> for FileContent {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl PartialEq for FileContent {
+    fn eq(&self, other: &FileContent) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl Eq for FileContent {}
+
+impl fmt::Debug for FileContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FileContent {{ len: {}, mapped: {} }}", self.len(), self.is_mapped())
+    }
+}
+
+impl From<Arc<[u8]>> for FileContent {
+    fn from(content: Arc<[u8]>) -> FileContent {
+        FileContent {
+            bytes: Bytes::Heap(content),
+        }
+    }
+}
+
+impl From<Box<[u8]>> for FileContent {
+    fn from(content: Box<[u8]>) -> FileContent {
+        FileContent::from(Arc::from(content))
+    }
+}
+
+impl From<Vec<u8>> for FileContent {
+    fn from(content: Vec<u8>) -> FileContent {
+        FileContent::from(Arc::from(content))
+    }
+}
+
+impl From<&[u8]> for FileContent {
+    fn from(content: &[u8]) -> FileContent {
+        FileContent::from(Arc::from(content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "mmap")]
+    use crate::{
+        tests::test_root,
+        GemFileSystem,
+        ReadFile,
+    };
+    #[cfg(feature = "mmap")]
+    use std::fs;
+    
+    #[test]
+    fn handles_compare_by_content() {
+        let first = FileContent::from(vec![1, 2, 3]);
+        let copy = FileContent::from(&[1u8, 2, 3][..]);
+        assert_eq!(first, copy);
+        assert!(!FileContent::ptr_eq(&first, &copy));
+        assert!(FileContent::ptr_eq(&first, &first.clone()));
+        assert!(!first.is_mapped());
+    }
+    
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mapped_extensions_are_not_copied() {
+        let root = test_root("mmap");
+        fs::write(root.join("level.pak"), b"packed level").unwrap();
+        fs::write(root.join("level.json"), b"{}").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.mmap_extension(".pak");
+    
+        let pak = gfs.read_file("level.pak").unwrap();
+        assert!(pak.is_mapped());
+        assert_eq!(&pak[..], b"packed level");
+        assert!(FileContent::ptr_eq(&pak, &gfs.read_file("level.pak").unwrap()));
+        assert!(!gfs.read_file("level.json").unwrap().is_mapped());
+        // hashed like any other entry
+        assert!(matches!(gfs.check_for_sync_file("level.pak"), Ok(crate::FileSyncState::HashMatch)));
+    }
+}
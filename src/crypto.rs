@@ -0,0 +1,89 @@
+use std::{
+    io,
+    path::Path,
+};
+
+use chacha20poly1305::{
+    aead::{
+        Aead,
+        KeyInit,
+    },
+    ChaCha20Poly1305,
+    Key,
+    Nonce,
+};
+
+const NONCE_LEN: usize = 12;
+
+/// hands out the keys of encrypted files, see `GemFileSystem::set_key_provider`
+pub trait KeyProvider: Send + Sync {
+    /// the ChaCha20-Poly1305 key `file_path` is encrypted with, found under `mount`
+    /// (a directory, pack or archive). None for a file that isn't encrypted
+    fn key(&self, file_path: &Path, mount: &Path) -> Option<[u8; 32]>;
+}
+
+/// encrypt `content` the way encrypted files are expected to be stored: the nonce followed by
+/// the ciphertext and its tag. never reuse a nonce with the same key
+pub fn seal(key: &[u8; 32], nonce: [u8; NONCE_LEN], content: &[u8]) -> Vec<u8> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let mut sealed = nonce.to_vec();
+    // only fails for content far bigger than any file
+    match cipher.encrypt(Nonce::from_slice(&nonce), content) {
+        Ok(ciphertext) => sealed.extend(ciphertext),
+        Err(err) => panic!("{}", err),
+    }
+    sealed
+}
+
+/// the inverse of `seal`, failing for a wrong key or tampered content
+pub(crate) fn open(key: &[u8; 32], sealed: &[u8]) -> io::Result<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Encrypted file is too short"));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Failed to decrypt, wrong key or corrupted file"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        FileSyncState,
+        GemFileSystem,
+        ReadFile,
+    };
+    use std::fs;
+    
+    const LICENSED: [u8; 32] = [7; 32];
+    
+    struct LicensedDir;
+    
+    impl KeyProvider for LicensedDir {
+        fn key(&self, file_path: &Path, _mount: &Path) -> Option<[u8; 32]> {
+            match file_path.starts_with("licensed") {
+                true => Some(LICENSED),
+                false => None,
+            }
+        }
+    }
+    
+    #[test]
+    fn encrypted_files_are_cached_decrypted() {
+        let root = test_root("crypto");
+        fs::create_dir_all(root.join("licensed")).unwrap();
+        fs::write(root.join("licensed/song.ogg"), seal(&LICENSED, [1; 12], b"licensed song")).unwrap();
+        fs::write(root.join("licensed/forged.ogg"), seal(&[8; 32], [2; 12], b"forged")).unwrap();
+        fs::write(root.join("free.ogg"), b"free song").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.set_key_provider(LicensedDir);
+    
+        assert_eq!(&gfs.read_file("licensed/song.ogg").unwrap()[..], b"licensed song");
+        assert_eq!(&gfs.read_file("free.ogg").unwrap()[..], b"free song");
+        assert_eq!(gfs.check_for_sync_file("licensed/song.ogg").unwrap(), FileSyncState::HashMatch);
+        assert!(gfs.read_file("licensed/forged.ogg").is_err());
+        assert!(open(&LICENSED, b"short").is_err());
+    }
+}
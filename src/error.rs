@@ -0,0 +1,148 @@
+use std::{
+    error,
+    fmt,
+    io,
+    path::PathBuf,
+    time::Duration,
+};
+
+/// what went wrong inside gfs, returned by every fallible call.
+/// converts into an `io::Error` for code built around `io::Result`, where it can be recovered
+/// with `get_ref()` and `downcast_ref::<GfsError>()`
+#[derive(Debug)]
+pub enum GfsError {
+    /// the file is neither cached nor on disk under any root
+    NotFound {
+        path: PathBuf,
+    },
+    /// `link` exists, but points at `target` which doesn't
+    BrokenSymlink {
+        link: PathBuf,
+        target: PathBuf,
+    },
+    /// the operation needs `path` to be cached, but it isn't
+    NotCached {
+        path: PathBuf,
+    },
+    /// the content of `path` doesn't match the hash it is expected to have
+    HashMismatch {
+        path: PathBuf,
+    },
+    /// `path` would resolve to a file outside of every root
+    OutsideRoot {
+        path: PathBuf,
+    },
+    Io(io::Error),
+    /// `path` is cached on both sides of a merge with `MergePolicy::Error`
+    MergeConflict {
+        path: PathBuf,
+    },
+    /// cached hashes were computed with the `found` digest instead of the `expected` one
+    HashAlgorithmMismatch {
+        expected: String,
+        found: String,
+    },
+    /// the signature of the manifest at `path` doesn't verify with the key it was checked with
+    InvalidSignature {
+        path: PathBuf,
+    },
+    /// `path` was to be loaded as an `asset_type`, but no loader is registered for it
+    NoLoader {
+        path: PathBuf,
+        asset_type: String,
+    },
+    /// `path` was to be read as text, but isn't UTF-8 from byte `offset` on
+    InvalidUtf8 {
+        path: PathBuf,
+        offset: usize,
+    },
+    /// `path` was to be checked against its source, but `SyncPolicy::Disabled` is set
+    SyncDisabled {
+        path: PathBuf,
+    },
+    /// `path` was to be deserialized from `format`, e.g. "JSON", but isn't valid
+    Parse {
+        path: PathBuf,
+        format: &'static str,
+        message: String,
+    },
+    /// the load of `path` was given up on with its `CancellationToken`
+    Cancelled {
+        path: PathBuf,
+    },
+    /// reading `path` took longer than the `after` it was given
+    Timeout {
+        path: PathBuf,
+        after: Duration,
+    },
+}
+
+impl fmt::Display for GfsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GfsError::NotFound { path } => write!(f, "Resource not found at path: {:#?}", path),
+            GfsError::BrokenSymlink { link, target } => {
+                write!(f, "Resource is a broken symlink: {:#?} points to missing {:#?}", link, target)
+            }
+            GfsError::NotCached { path } => write!(f, "Resource not found in cache: {:#?}", path),
+            GfsError::HashMismatch { path } => write!(f, "Resource does not match its hash: {:#?}", path),
+            GfsError::OutsideRoot { path } => write!(f, "Resource path escapes the root: {:#?}", path),
+            GfsError::Io(err) => write!(f, "{}", err),
+            GfsError::MergeConflict { path } => write!(f, "Resource cached in both merged caches: {:#?}", path),
+            GfsError::HashAlgorithmMismatch { expected, found } => {
+                write!(f, "Cache was hashed with {}, expected {}", found, expected)
+            }
+            GfsError::InvalidSignature { path } => write!(f, "Manifest signature does not verify: {:#?}", path),
+            GfsError::NoLoader { path, asset_type } => {
+                write!(f, "No asset loader registered for {}: {:#?}", asset_type, path)
+            }
+            GfsError::InvalidUtf8 { path, offset } => {
+                write!(f, "Resource is not valid UTF-8 at byte {}: {:#?}", offset, path)
+            }
+            GfsError::SyncDisabled { path } => write!(f, "Resource sync is disabled: {:#?}", path),
+            GfsError::Parse { path, format, message } => {
+                write!(f, "Resource is not valid {}: {:#?}: {}", format, path, message)
+            }
+            GfsError::Cancelled { path } => write!(f, "Resource load was cancelled: {:#?}", path),
+            GfsError::Timeout { path, after } => write!(f, "Resource read timed out after {:?}: {:#?}", after, path),
+        }
+    }
+}
+
+impl error::Error for GfsError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            GfsError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<GfsError> for io::Error {
+    fn from(err: GfsError) -> io::Error {
+        let kind = match err {
+            GfsError::Io(err) => return err,
+            GfsError::NotFound { .. } => io::ErrorKind::NotFound,
+            GfsError::BrokenSymlink { .. } => io::ErrorKind::NotFound,
+            GfsError::NotCached { .. } => io::ErrorKind::NotFound,
+            GfsError::HashMismatch { .. } => io::ErrorKind::InvalidData,
+            GfsError::OutsideRoot { .. } => io::ErrorKind::PermissionDenied,
+            GfsError::MergeConflict { .. } => io::ErrorKind::AlreadyExists,
+            GfsError::HashAlgorithmMismatch { .. } => io::ErrorKind::InvalidData,
+            GfsError::InvalidSignature { .. } => io::ErrorKind::InvalidData,
+            GfsError::NoLoader { .. } => io::ErrorKind::Unsupported,
+            GfsError::InvalidUtf8 { .. } => io::ErrorKind::InvalidData,
+            GfsError::SyncDisabled { .. } => io::ErrorKind::Unsupported,
+            GfsError::Parse { .. } => io::ErrorKind::InvalidData,
+            GfsError::Cancelled { .. } => io::ErrorKind::Other,
+            GfsError::Timeout { .. } => io::ErrorKind::TimedOut,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+impl From<io::Error> for GfsError {
+    fn from(err: io::Error) -> GfsError {
+        GfsError::Io(err)
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    future::Future,
+    io::{
+        self,
+        Read,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    pin::Pin,
+};
+
+use crate::{
+    BackendMeta,
+    FileBackend,
+    GfsError,
+    MemoryBackend,
+};
+
+/// downloads files for a `FetchBackend`, e.g. with the browser's `fetch` on
+/// `wasm32-unknown-unknown`, where reads can't block on the network
+pub trait ByteProvider {
+    /// the whole content of a file
+    fn fetch(&self, file_path: &Path) -> Pin<Box<dyn Future<Output = io::Result<Vec<u8>>> + '_>>;
+}
+
+/// files downloaded ahead of time by a `ByteProvider`, for builds without `std::fs` like the
+/// web. a file has to be `fetch`ed before it can be read, and is served from memory after
+/// that. mount an `Arc` of it with `mount_backend` to keep fetching once it is mounted.
+/// files cached from it are hashed and synced as usual, so `check_for_sync_file` tells
+/// whether a cached file is still what was fetched last
+pub struct FetchBackend<P> {
+    provider: P,
+    fetched: MemoryBackend,
+}
+
+impl<P: ByteProvider> FetchBackend<P> {
+    pub fn new(provider: P) -> FetchBackend<P> {
+        FetchBackend {
+            provider,
+            fetched: MemoryBackend::new(),
+        }
+    }
+    
+    /// download a file, replacing what was fetched for it before
+    pub async fn fetch<Q: AsRef<Path>>(&self, file_path: Q) -> Result<(), GfsError> {
+        let bytes = self.provider.fetch(file_path.as_ref()).await?;
+        self.fetched.insert(file_path, bytes)
+    }
+}
+
+impl<P: ByteProvider + Send + Sync> FileBackend for FetchBackend<P> {
+    fn open(&self, file_path: &Path) -> io::Result<Box<dyn Read + Send + '_>> {
+        self.fetched.open(file_path)
+    }
+    
+    fn read_all(&self, file_path: &Path) -> io::Result<Vec<u8>> {
+        self.fetched.read_all(file_path)
+    }
+    
+    fn metadata(&self, file_path: &Path) -> io::Result<BackendMeta> {
+        self.fetched.metadata(file_path)
+    }
+    
+    fn list(&self) -> io::Result<Vec<(PathBuf, u64)>> {
+        self.fetched.list()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lock,
+        FileSyncState,
+        GemFileSystem,
+        ReadFile,
+    };
+    use std::{
+        collections::HashMap,
+        env,
+        sync::{
+            Arc,
+            Mutex,
+        },
+        task::{
+            Context,
+            Poll,
+            Wake,
+            Waker,
+        },
+    };
+    
+    struct Server(Mutex<HashMap<PathBuf, Vec<u8>>>);
+    
+    impl ByteProvider for Server {
+        fn fetch(&self, file_path: &Path) -> Pin<Box<dyn Future<Output = io::Result<Vec<u8>>> + '_>> {
+            let fetched = lock(&self.0).get(file_path).cloned()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "404"));
+            Box::pin(async move { fetched })
+        }
+    }
+    
+    struct Noop;
+    
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+    
+    /// enough of an executor for futures that are ready right away
+    fn ready<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Noop));
+        let mut future = Box::pin(future);
+        match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("the test server never keeps anyone waiting"),
+        }
+    }
+    
+    #[test]
+    fn fetched_files_are_read_and_synced() {
+        let server = Server(Mutex::new(HashMap::from([(PathBuf::from("levels/level1.txt"), b"level 1".to_vec())])));
+        let backend = Arc::new(FetchBackend::new(server));
+        let mut gfs = GemFileSystem::new(env::temp_dir().join("gfs_no_such_root"));
+        gfs.mount_backend("web", Arc::clone(&backend), 0);
+        assert!(gfs.read_file("levels/level1.txt").is_err());
+    
+        ready(backend.fetch("levels/level1.txt")).unwrap();
+        assert!(ready(backend.fetch("levels/level2.txt")).is_err());
+        assert_eq!(&gfs.read_file("levels/level1.txt").unwrap()[..], b"level 1");
+        lock(&backend.provider.0).insert(PathBuf::from("levels/level1.txt"), b"patched".to_vec());
+        ready(backend.fetch("levels/level1.txt")).unwrap();
+        assert_eq!(gfs.check_for_sync_file("levels/level1.txt").unwrap(), FileSyncState::HashUnmatch);
+        assert!(gfs.reload_if_changed("levels/level1.txt").unwrap());
+        assert_eq!(&gfs.read_file("levels/level1.txt").unwrap()[..], b"patched");
+    }
+}
@@ -0,0 +1,63 @@
+/// whether a key like "textures/wood/oak.png" matches a pattern like "textures/**/*.png".
+/// `*` matches within one path segment, `?` one character of it and a `**` segment any
+/// number of segments, including none
+pub(crate) fn matches(pattern: &str, key: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let key: Vec<&str> = key.split('/').collect();
+    match_segments(&pattern, &key)
+}
+
+/// the segments of `pattern` before its first wildcard, the only directory that can
+/// have matches in it
+pub(crate) fn literal_prefix(pattern: &str) -> Vec<&str> {
+    let mut segments: Vec<&str> = pattern.split('/').collect();
+    // the last segment names files, not a directory to walk
+    segments.pop();
+    segments.into_iter()
+        .take_while(|segment| !segment.contains(['*', '?']))
+        .collect()
+}
+
+fn match_segments(pattern: &[&str], key: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => key.is_empty(),
+        Some((&"**", rest)) => (0..=key.len()).any(|skipped| match_segments(rest, &key[skipped..])),
+        Some((segment, rest)) => match key.split_first() {
+            Some((name, key_rest)) => {
+                let segment: Vec<char> = segment.chars().collect();
+                let name: Vec<char> = name.chars().collect();
+                match_name(&segment, &name) && match_segments(rest, key_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_name(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => match_name(&pattern[1..], name) || (!name.is_empty() && match_name(pattern, &name[1..])),
+        (Some('?'), Some(_)) => match_name(&pattern[1..], &name[1..]),
+        (Some(expected), Some(found)) if expected == found => match_name(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn patterns_match_keys() {
+        assert!(matches("textures/**/*.png", "textures/wood.png"));
+        assert!(matches("textures/**/*.png", "textures/wood/oak/bark.png"));
+        assert!(!matches("textures/**/*.png", "textures/wood/oak.jpg"));
+        assert!(matches("textures/*.png", "textures/wood.png"));
+        assert!(!matches("textures/*.png", "textures/wood/oak.png"));
+        assert!(matches("levels/level?.json", "levels/level1.json"));
+        assert!(!matches("levels/level?.json", "levels/level10.json"));
+        assert!(matches("**", "anything/at/all"));
+        assert_eq!(literal_prefix("textures/wood/**/*.png"), ["textures", "wood"]);
+        assert!(literal_prefix("*.png").is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+use std::{
+    fmt,
+    io::{
+        self,
+        Read,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        mpsc,
+        Arc,
+        Condvar,
+        Mutex,
+        RwLock,
+    },
+    thread,
+};
+
+use sha2::Sha256;
+
+use crate::{
+    current_cache,
+    lock,
+    manifest::to_hex,
+    process_sha256,
+    Cache,
+    CacheSlot,
+};
+
+/// the digest behind the sync subsystem, i.e. `Cache::sha2_map` and `check_for_sync_file`.
+/// it only has to notice changed content, so a fast non-cryptographic hash is fine
+pub trait ContentHasher: Send + Sync {
+    /// recorded as `Cache::hash_algorithm`, so hashes of different digests are never compared
+    fn name(&self) -> &'static str;
+    /// fails only if `reader` does
+    fn hash(&self, reader: &mut dyn Read) -> io::Result<Vec<u8>>;
+    
+    /// `hash` of content in memory, which never fails to be read. should a hasher fail
+    /// anyway, the hash is empty and matches nothing
+    fn hash_bytes(&self, content: &[u8]) -> Vec<u8> {
+        self.hash(&mut &content[..]).unwrap_or_default()
+    }
+    
+    /// `hash`, along with the digest it was computed with
+    fn file_hash(&self, reader: &mut dyn Read) -> io::Result<FileHash> {
+        Ok(FileHash::new(self.name(), self.hash(reader)?))
+    }
+}
+
+/// the hash of a file as a `ContentHasher` computed it, e.g. by `GemFileSystem::file_hash`.
+/// hashes of different digests are never equal, whatever their bytes. displayed as
+/// `<digest>:<hash in hex>`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileHash {
+    algorithm: String,
+    digest: Box<[u8]>,
+}
+
+impl FileHash {
+    /// `algorithm` like `ContentHasher::name`
+    pub fn new<D: Into<Box<[u8]>>>(algorithm: &str, digest: D) -> FileHash {
+        FileHash {
+            algorithm: String::from(algorithm),
+            digest: digest.into(),
+        }
+    }
+    
+    pub fn algorithm(&self) -> &str {
+        &self.algorithm
+    }
+    
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.digest
+    }
+    
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.digest)
+    }
+}
+
+impl fmt::Display for FileHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm, self.to_hex())
+    }
+}
+
+/// a reader handing out what `reader` yields while keeping a copy of it, so content is
+/// hashed in the same pass that reads it
+pub(crate) struct Tee<'a, R> {
+    reader: R,
+    copy: &'a mut Vec<u8>,
+}
+
+impl<'a, R: Read> Tee<'a, R> {
+    pub(crate) fn new(reader: R, copy: &'a mut Vec<u8>) -> Tee<'a, R> {
+        Tee {
+            reader,
+            copy,
+        }
+    }
+}
+
+impl<R: Read> Read for Tee<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.copy.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// SHA-256, the default
+pub struct Sha256Hasher;
+
+impl ContentHasher for Sha256Hasher {
+    fn name(&self) -> &'static str {
+        "sha256"
+    }
+    
+    fn hash(&self, reader: &mut dyn Read) -> io::Result<Vec<u8>> {
+        process_sha256::<Sha256, _>(reader)
+    }
+}
+
+/// BLAKE3, still cryptographic but much faster than SHA-256
+#[cfg(feature = "blake3")]
+pub struct Blake3Hasher;
+
+#[cfg(feature = "blake3")]
+impl ContentHasher for Blake3Hasher {
+    fn name(&self) -> &'static str {
+        "blake3"
+    }
+    
+    fn hash(&self, reader: &mut dyn Read) -> io::Result<Vec<u8>> {
+        let mut hasher = blake3::Hasher::new();
+        read_chunks(reader, |chunk| {
+            hasher.update(chunk);
+        })?;
+        Ok(hasher.finalize().as_bytes().to_vec())
+    }
+}
+
+/// 64 bit xxHash with seed 0
+#[cfg(feature = "xxhash")]
+pub struct XxHash64Hasher;
+
+#[cfg(feature = "xxhash")]
+impl ContentHasher for XxHash64Hasher {
+    fn name(&self) -> &'static str {
+        "xxh64"
+    }
+    
+    fn hash(&self, reader: &mut dyn Read) -> io::Result<Vec<u8>> {
+        let mut hasher = xxhash_rust::xxh64::Xxh64::new(0);
+        read_chunks(reader, |chunk| hasher.update(chunk))?;
+        Ok(hasher.digest().to_be_bytes().to_vec())
+    }
+}
+
+/// feed everything `reader` yields to `consume`, one buffer at a time
+#[cfg(any(feature = "blake3", feature = "xxhash"))]
+fn read_chunks<F: FnMut(&[u8])>(reader: &mut dyn Read, mut consume: F) -> io::Result<()> {
+    let mut buffer = vec![0u8; crate::BUFFER_SIZE];
+    loop {
+        let n = match reader.read(&mut buffer)? {
+            0 => break,
+            n => n,
+        };
+        consume(&buffer[..n]);
+    }
+    Ok(())
+}
+
+/// hashes stored content on a thread of its own, see `HashTiming::Background`.
+/// the thread exits once the worker is dropped and the queue is drained
+pub(crate) struct HashWorker {
+    queue: Mutex<mpsc::Sender<PathBuf>>,
+    // how many queued paths are not hashed yet
+    pending: Arc<(Mutex<usize>, Condvar)>,
+}
+
+impl HashWorker {
+    pub(crate) fn spawn(cache: Arc<CacheSlot>) -> io::Result<HashWorker> {
+        let (queue, jobs) = mpsc::channel::<PathBuf>();
+        let pending = Arc::new((Mutex::new(0), Condvar::new()));
+        let worker_pending = Arc::clone(&pending);
+        thread::Builder::new().name(String::from("gfs-hash")).spawn(move || {
+            for key in jobs {
+                // whichever cache the file was stored in, unless it was swapped out since
+                hash_entry(&current_cache(&cache), &key);
+                let (count, idle) = &*worker_pending;
+                let mut count = lock(count);
+                *count -= 1;
+                if *count == 0 {
+                    idle.notify_all();
+                }
+            }
+        })?;
+        Ok(HashWorker {
+            queue: Mutex::new(queue),
+            pending,
+        })
+    }
+    
+    pub(crate) fn queue(&self, key: PathBuf) {
+        let mut count = lock(&self.pending.0);
+        *count += 1;
+        if lock(&self.queue).send(key).is_err() {
+            // the thread is gone, the next sync check hashes on demand instead
+            *count -= 1;
+        }
+    }
+    
+    /// block until every queued path is hashed
+    pub(crate) fn wait_idle(&self) {
+        let (count, idle) = &*self.pending;
+        let mut count = lock(count);
+        while *count > 0 {
+            count = idle.wait(count).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+}
+
+fn hash_entry(cache: &RwLock<Cache>, key: &Path) {
+    // hash under the read lock only, so reads aren't blocked meanwhile
+    let (content, version, hasher) = {
+        let cache = cache.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if cache.sha2_map.contains_key(key) {
+            // a sync check got to it first
+            return;
+        }
+        match (cache.raw_content(key), cache.meta_map.get(key)) {
+            (Some(content), Some(meta)) => (content, meta.version, Arc::clone(&cache.hasher)),
+            _ => return,
+        }
+    };
+    let hash = hasher.hash_bytes(&content);
+    let mut cache = cache.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    // the file may have been stored again, or the hasher replaced, while hashing
+    let unchanged = match cache.meta_map.get(key) {
+        Some(meta) => meta.version == version && cache.hash_algorithm == hasher.name(),
+        None => false,
+    };
+    if unchanged {
+        cache.sha2_map.insert(key.to_path_buf(), hash.into_boxed_slice());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn check_hasher<H: ContentHasher>(hasher: H) {
+        let long = vec![7u8; crate::BUFFER_SIZE * 3 + 1];
+        assert_eq!(hasher.hash_bytes(&long), hasher.hash_bytes(&long));
+        assert_ne!(hasher.hash_bytes(&long), hasher.hash_bytes(&long[1..]));
+        assert_ne!(hasher.hash_bytes(b"wood"), hasher.hash_bytes(b"rock"));
+    }
+    
+    #[test]
+    fn hashers_tell_content_apart() {
+        check_hasher(Sha256Hasher);
+        #[cfg(feature = "blake3")]
+        check_hasher(Blake3Hasher);
+        #[cfg(feature = "xxhash")]
+        check_hasher(XxHash64Hasher);
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{
+        self,
+        Read,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    process,
+    sync::{
+        atomic::{
+            AtomicU64,
+            Ordering,
+        },
+        Mutex,
+    },
+    time::Duration,
+};
+
+use crate::{
+    backend::percent_encode,
+    lock,
+    manifest::to_hex,
+    BackendMeta,
+    ContentHasher,
+    throttle::throttled,
+    FileBackend,
+    Sha256Hasher,
+    Throttle,
+};
+
+/// how long a download may stall before it fails, unless `timeout` says otherwise
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// files served by a web server or CDN under a base URL, mounted with `mount_backend`.
+/// every download is kept in a local cache directory, named after the SHA-256 of its URL,
+/// and only downloaded again if the server says it changed, going by its ETag. while the
+/// server can't be reached, files downloaded before are served as they are. `list` is
+/// always empty, a web server can't be asked what it has
+pub struct HttpBackend {
+    base_url: String,
+    cache_dir: PathBuf,
+    agent: ureq::Agent,
+    throttle: Option<Throttle>,
+    /// local copies `metadata` just downloaded, which the read after it needn't check again
+    fresh: Mutex<HashSet<PathBuf>>,
+}
+
+impl HttpBackend {
+    /// `base_url` like "https://cdn.example.com/game/v1", `cache_dir` is created if needed
+    pub fn new<P: AsRef<Path>>(base_url: &str, cache_dir: P) -> io::Result<HttpBackend> {
+        fs::create_dir_all(cache_dir.as_ref())?;
+        Ok(HttpBackend {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            cache_dir: cache_dir.as_ref().to_path_buf(),
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+            throttle: None,
+            fresh: Mutex::new(HashSet::new()),
+        })
+    }
+    
+    /// how long a download may stall before it fails, 30 seconds by default
+    pub fn timeout(mut self, timeout: Duration) -> HttpBackend {
+        self.agent = ureq::AgentBuilder::new().timeout(timeout).build();
+        self
+    }
+    
+    /// download no faster than `throttle` lets through, together with everything else it was
+    /// handed to
+    pub fn throttle(mut self, throttle: &Throttle) -> HttpBackend {
+        self.throttle = Some(throttle.clone());
+        self
+    }
+    
+    fn url(&self, file_path: &Path) -> String {
+        let segments: Vec<_> = file_path.components()
+            .map(|component| percent_encode(&component.as_os_str().to_string_lossy()))
+            .collect();
+        format!("{}/{}", self.base_url, segments.join("/"))
+    }
+    
+    fn local(&self, url: &str) -> PathBuf {
+        self.cache_dir.join(to_hex(&Sha256Hasher.hash_bytes(url.as_bytes())))
+    }
+    
+    /// the local copy of a file, downloaded first unless it is still current
+    fn fetch(&self, file_path: &Path) -> io::Result<PathBuf> {
+        let url = self.url(file_path);
+        let local = self.local(&url);
+        if lock(&self.fresh).remove(&local) {
+            return Ok(local);
+        }
+        let etag_path = local.with_extension("etag");
+        let mut request = self.agent.get(&url);
+        if local.is_file() {
+            if let Ok(etag) = fs::read_to_string(&etag_path) {
+                request = request.set("If-None-Match", etag.trim());
+            }
+        }
+        match request.call() {
+            Ok(response) if response.status() == 304 => Ok(local),
+            Ok(response) => {
+                let etag = response.header("ETag").map(String::from);
+                // written aside first, so a broken download never replaces a good copy. numbered
+                // per download, since lookups of one file from several threads each fetch it
+                static DOWNLOADS: AtomicU64 = AtomicU64::new(0);
+                let mut temp_name = local.clone().into_os_string();
+                temp_name.push(format!(".{}.{}.tmp", process::id(), DOWNLOADS.fetch_add(1, Ordering::Relaxed)));
+                let temp_path = PathBuf::from(temp_name);
+                let downloaded = fs::File::create(&temp_path)
+                    .and_then(|mut temp| io::copy(&mut throttled(&self.throttle, response.into_reader()), &mut temp))
+                    .and_then(|_| fs::rename(&temp_path, &local));
+                if let Err(err) = downloaded {
+                    let _ = fs::remove_file(&temp_path);
+                    return Err(err);
+                }
+                match etag {
+                    Some(etag) => fs::write(&etag_path, etag)?,
+                    None => {
+                        let _ = fs::remove_file(&etag_path);
+                    }
+                }
+                Ok(local)
+            }
+            Err(ureq::Error::Status(404, _)) | Err(ureq::Error::Status(410, _)) => {
+                let mut err = String::from("Not on the server: ");
+                err.push_str(&format!("{:#?}", url));
+                Err(io::Error::new(io::ErrorKind::NotFound, err))
+            }
+            Err(ureq::Error::Status(status, _)) => {
+                let mut err = String::from("Download failed: ");
+                err.push_str(&format!("{:#?} answered {}", url, status));
+                Err(io::Error::other(err))
+            }
+            Err(err) => match local.is_file() {
+                true => {
+                    warn!("failed to reach {}, serving the copy downloaded before: {}", url, err);
+                    Ok(local)
+                }
+                false => Err(io::Error::other(err.to_string())),
+            },
+        }
+    }
+}
+
+impl FileBackend for HttpBackend {
+    fn open(&self, file_path: &Path) -> io::Result<Box<dyn Read + Send + '_>> {
+        Ok(Box::new(fs::File::open(self.fetch(file_path)?)?))
+    }
+    
+    fn read_all(&self, file_path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(self.fetch(file_path)?)
+    }
+    
+    fn metadata(&self, file_path: &Path) -> io::Result<BackendMeta> {
+        // lookups ask for this before every read, which checks with the server anyway
+        let local = self.local(&self.url(file_path));
+        let meta = match fs::metadata(&local) {
+            Ok(meta) => meta,
+            Err(_) => {
+                let local = self.fetch(file_path)?;
+                let meta = fs::metadata(&local)?;
+                lock(&self.fresh).insert(local);
+                meta
+            }
+        };
+        Ok(BackendMeta {
+            size: meta.len(),
+            modified: meta.modified().ok(),
+            etag: None,
+        })
+    }
+    
+    fn list(&self) -> io::Result<Vec<(PathBuf, u64)>> {
+        Ok(Vec::new())
+    }
+    
+    fn throttle(&self) -> Option<&Throttle> {
+        self.throttle.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        GemFileSystem,
+        ReadFile,
+    };
+    use std::{
+        io::Write,
+        net::TcpListener,
+        sync::{
+            atomic::{
+                AtomicBool,
+                AtomicUsize,
+                Ordering,
+            },
+            Arc,
+        },
+        thread,
+    };
+    
+    /// serves "/game/levels/level%201.txt" with ETag "v1", counting full downloads.
+    /// hangs up without an answer while `down` is set
+    fn serve(downloads: Arc<AtomicUsize>, down: Arc<AtomicBool>) -> String {
+        serve_counting(downloads, Arc::new(AtomicUsize::new(0)), down)
+    }
+    
+    /// `serve`, also counting every request
+    fn serve_counting(downloads: Arc<AtomicUsize>, requests: Arc<AtomicUsize>, down: Arc<AtomicBool>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = Vec::new();
+                let mut byte = [0u8; 1];
+                while !request.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+                    request.push(byte[0]);
+                }
+                if down.load(Ordering::SeqCst) {
+                    continue;
+                }
+                requests.fetch_add(1, Ordering::SeqCst);
+                let request = String::from_utf8_lossy(&request);
+                let response = match request.starts_with("GET /game/levels/level%201.txt ") {
+                    true if request.contains("If-None-Match: \"v1\"") => String::from("HTTP/1.0 304 Not Modified\r\n\r\n"),
+                    true => {
+                        downloads.fetch_add(1, Ordering::SeqCst);
+                        String::from("HTTP/1.0 200 OK\r\nETag: \"v1\"\r\nContent-Length: 7\r\n\r\nlevel 1")
+                    }
+                    false => String::from("HTTP/1.0 404 Not Found\r\n\r\n"),
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        format!("http://{}/game", address)
+    }
+    
+    #[test]
+    fn downloads_are_cached_on_disk() {
+        let cache_dir = test_root("http");
+        let downloads = Arc::new(AtomicUsize::new(0));
+        let down = Arc::new(AtomicBool::new(false));
+        let base_url = serve(Arc::clone(&downloads), Arc::clone(&down));
+        let mut gfs = GemFileSystem::new(cache_dir.join("empty"));
+        gfs.mount_backend("cdn", HttpBackend::new(&base_url, &cache_dir).unwrap(), 0);
+    
+        assert_eq!(&gfs.read_file("levels/level 1.txt").unwrap()[..], b"level 1");
+        assert!(gfs.read_file("levels/level 2.txt").is_err());
+        gfs.clear();
+        assert_eq!(&gfs.read_file("levels/level 1.txt").unwrap()[..], b"level 1");
+        assert_eq!(downloads.load(Ordering::SeqCst), 1);
+    
+        down.store(true, Ordering::SeqCst);
+        gfs.clear();
+        assert_eq!(&gfs.read_file("levels/level 1.txt").unwrap()[..], b"level 1");
+        assert!(gfs.read_file("levels/level 2.txt").is_err());
+    }
+    
+    #[test]
+    fn lookups_fetch_each_file_once() {
+        let cache_dir = test_root("http_lookups");
+        let downloads = Arc::new(AtomicUsize::new(0));
+        let requests = Arc::new(AtomicUsize::new(0));
+        let base_url = serve_counting(Arc::clone(&downloads), Arc::clone(&requests), Arc::new(AtomicBool::new(false)));
+        let backend = HttpBackend::new(&base_url, &cache_dir).unwrap();
+        let level = Path::new("levels/level 1.txt");
+    
+        // the download for the metadata is what the read after it gets
+        assert_eq!(backend.metadata(level).unwrap().size, 7);
+        assert_eq!(backend.read_all(level).unwrap(), b"level 1");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        backend.metadata(level).unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    
+        // threads fetching the same file each download into a temp file of their own
+        fs::remove_file(backend.local(&backend.url(level))).unwrap();
+        let backend = Arc::new(backend);
+        let readers: Vec<_> = (0..4).map(|_| {
+            let backend = Arc::clone(&backend);
+            thread::spawn(move || backend.read_all(Path::new("levels/level 1.txt")).unwrap())
+        }).collect();
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), b"level 1");
+        }
+        assert!(downloads.load(Ordering::SeqCst) >= 2);
+        assert!(!fs::read_dir(&cache_dir).unwrap().any(|entry| entry.unwrap().path().extension() == Some("tmp".as_ref())));
+    }
+}
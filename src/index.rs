@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::{
+        Duration,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+use crate::{
+    manifest::to_hex,
+    GfsError,
+};
+
+const HEADER: &str = "gfs-index 1";
+const INDEX_FILE: &str = "index";
+const CONTENT_DIR: &str = "content";
+
+/// the hashes of files as they were on disk, written by `GemFileSystem::save_index` to skip
+/// hashing them again on the next start. as text, a `gfs-index 1 <hash algorithm>` line
+/// followed by one `<hash in hex> <size> <modified> <content saved: c or -> <path>` line per file
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DiskIndex {
+    pub(crate) hash_algorithm: String,
+    pub(crate) entries: HashMap<PathBuf, IndexEntry>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct IndexEntry {
+    pub(crate) hash: Box<[u8]>,
+    pub(crate) size: u64,
+    pub(crate) modified: SystemTime,
+    /// the content is saved as well, under `content/<hash in hex>`
+    pub(crate) saved: bool,
+}
+
+impl DiskIndex {
+    /// the hash of a file on disk, if it still has the size and modification time it had
+    /// when it was indexed
+    pub(crate) fn hash(&self, key: &Path, disk_meta: &fs::Metadata) -> Option<&[u8]> {
+        let entry = self.entries.get(key)?;
+        match entry.size == disk_meta.len() && Some(entry.modified) == disk_meta.modified().ok() {
+            true => Some(&entry.hash),
+            false => None,
+        }
+    }
+    
+    pub(crate) fn content_path(dir: &Path, entry: &IndexEntry) -> PathBuf {
+        dir.join(CONTENT_DIR).join(to_hex(&entry.hash))
+    }
+    
+    /// write the index into `dir`, with `content` for every entry saved along with it
+    pub(crate) fn save(&self, dir: &Path, content: &HashMap<PathBuf, Vec<u8>>) -> Result<(), GfsError> {
+        fs::create_dir_all(dir.join(CONTENT_DIR))?;
+        let mut text = format!("{} {}\n", HEADER, self.hash_algorithm);
+        for (key, entry) in &self.entries {
+            if let Some(bytes) = content.get(key) {
+                let content_path = DiskIndex::content_path(dir, entry);
+                // named after its hash, so it is already there if it didn't change
+                if !content_path.is_file() {
+                    fs::write(content_path, bytes)?;
+                }
+            }
+            let modified = entry.modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+            let saved = match entry.saved {
+                true => "c",
+                false => "-",
+            };
+            let key: Vec<_> = key.components().map(|component| component.as_os_str().to_string_lossy()).collect();
+            text.push_str(&format!("{} {} {}.{:09} {} {}\n", to_hex(&entry.hash), entry.size,
+                                   modified.as_secs(), modified.subsec_nanos(), saved, key.join("/")));
+        }
+        // written aside first, so a crash never leaves half an index behind
+        let temp_path = dir.join(format!("{}.tmp", INDEX_FILE));
+        fs::write(&temp_path, text)?;
+        fs::rename(&temp_path, dir.join(INDEX_FILE))?;
+        Ok(())
+    }
+    
+    pub(crate) fn load(dir: &Path) -> Result<DiskIndex, GfsError> {
+        let text = fs::read_to_string(dir.join(INDEX_FILE))?;
+        let mut lines = text.lines();
+        let hash_algorithm = match lines.next().and_then(|header| header.strip_prefix(HEADER)) {
+            Some(hash_algorithm) => hash_algorithm.trim().to_string(),
+            None => return Err(invalid_index(String::from("Not a gfs index"))),
+        };
+        let mut entries = HashMap::new();
+        for line in lines.filter(|line| !line.is_empty()) {
+            match parse_entry(line) {
+                Some((key, entry)) => {
+                    entries.insert(key, entry);
+                }
+                None => {
+                    let mut err = String::from("Malformed index line: ");
+                    err.push_str(&format!("{:#?}", line));
+                    return Err(invalid_index(err));
+                }
+            }
+        }
+        Ok(DiskIndex {
+            hash_algorithm,
+            entries,
+        })
+    }
+}
+
+fn parse_entry(line: &str) -> Option<(PathBuf, IndexEntry)> {
+    let mut fields = line.splitn(5, ' ');
+    let hash = from_hex(fields.next()?)?;
+    let size = fields.next()?.parse().ok()?;
+    let (secs, nanos) = fields.next()?.split_once('.')?;
+    let modified = UNIX_EPOCH + Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+    let saved = match fields.next()? {
+        "c" => true,
+        "-" => false,
+        _ => return None,
+    };
+    let entry = IndexEntry {
+        hash,
+        size,
+        modified,
+        saved,
+    };
+    Some((PathBuf::from(fields.next()?), entry))
+}
+
+fn from_hex(hex: &str) -> Option<Box<[u8]>> {
+    hex.as_bytes().chunks(2)
+        .map(|digits| match digits.len() {
+            2 => u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+fn invalid_index(err: String) -> GfsError {
+    GfsError::Io(io::Error::new(io::ErrorKind::InvalidData, err))
+}
@@ -1,34 +1,292 @@
-extern crate utils;
 #[macro_use]
 extern crate log;
 use std::{
+    alloc::{
+        self,
+        Layout,
+    },
+    any::TypeId,
+    cell::RefCell,
     collections::{
+        BTreeMap,
+        BTreeSet,
         HashMap,
+        HashSet,
     },
+    ffi::OsStr,
     fmt,
+    fs,
     io::{
         self,
-        Cursor,
         Read,
+        Seek,
+        SeekFrom,
+        Write,
+    },
+    mem,
+    ops::{
+        Deref,
+        DerefMut,
     },
     path::{
         self,
         PathBuf,
     },
+    process,
+    ptr,
+    slice,
+    sync::{
+        mpsc,
+        Arc,
+        Condvar,
+        Mutex,
+        MutexGuard,
+        RwLock,
+        RwLockReadGuard,
+        RwLockWriteGuard,
+        atomic::{
+            AtomicU64,
+            AtomicUsize,
+            Ordering,
+        },
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+    },
 };
+#[cfg(feature = "diagnostics")]
+use std::collections::VecDeque;
+
+use sha2::Digest;
 
-use sha2::{Digest, Sha256};
+#[cfg(feature = "android")]
+mod android;
+#[cfg(feature = "zip")]
+mod archive;
+mod asset;
+mod backend;
+mod builder;
+#[cfg(feature = "async")]
+mod async_read;
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+mod codec;
+mod content;
+#[cfg(feature = "encryption")]
+mod crypto;
+mod error;
+mod fetch;
+mod glob;
+mod hash;
+#[cfg(feature = "http")]
+mod http;
+mod index;
+mod loader;
+mod manifest;
+mod pack;
+mod patch;
+mod reader;
+mod retry;
+#[cfg(feature = "s3")]
+mod s3;
+mod self_test;
+mod store;
+mod throttle;
+mod trace;
+mod variant;
+#[cfg(feature = "watch")]
+mod watch;
+
+#[cfg(feature = "android")]
+pub use android::AndroidBackend;
+#[cfg(feature = "zip")]
+use archive::ZipMount;
+#[cfg(feature = "async")]
+pub use async_read::AsyncReadFile;
+pub use asset::{
+    AssetLoader,
+    ErasedAsset,
+    Handle,
+    HandleId,
+};
+use asset::{
+    AnyAsset,
+    Assets,
+    ErasedLoader,
+};
+pub use backend::{
+    BackendMeta,
+    DirBackend,
+    EmbeddedBackend,
+    FileBackend,
+    MemoryBackend,
+};
+pub use builder::GemFileSystemBuilder;
+#[cfg(any(feature = "zstd", feature = "lz4"))]
+pub use codec::Codec;
+pub use content::FileContent;
+use content::WeakContent;
+#[cfg(feature = "encryption")]
+pub use crypto::{
+    seal,
+    KeyProvider,
+};
+pub use error::GfsError;
+pub use fetch::{
+    ByteProvider,
+    FetchBackend,
+};
+#[cfg(feature = "blake3")]
+pub use hash::Blake3Hasher;
+#[cfg(feature = "xxhash")]
+pub use hash::XxHash64Hasher;
+pub use hash::{
+    ContentHasher,
+    FileHash,
+    Sha256Hasher,
+};
+#[cfg(feature = "http")]
+pub use http::HttpBackend;
+use hash::{
+    HashWorker,
+    Tee,
+};
+use index::{
+    DiskIndex,
+    IndexEntry,
+};
+pub use loader::{
+    CancellationToken,
+    LoadTicket,
+    Loader,
+    Priority,
+};
+pub use manifest::{
+    diff_manifests,
+    ChangeSet,
+    Manifest,
+    ManifestCheck,
+    ManifestEntry,
+};
+pub use pack::{
+    write_pack,
+    PackEntry,
+    PackIndex,
+};
+use pack::PackMount;
+pub use patch::Patch;
+pub use reader::GfsReader;
+use reader::Reported;
+use retry::Retrying;
+pub use retry::RetryPolicy;
+#[cfg(feature = "s3")]
+pub use s3::S3Backend;
+pub use self_test::{
+    SelfTestReport,
+    SelfTestStage,
+};
+#[cfg(feature = "mmap")]
+pub use store::MmapStore;
+pub use store::{
+    ContentStore,
+    HeapStore,
+};
+pub use throttle::Throttle;
+use trace::Stage;
+#[cfg(feature = "watch")]
+pub use watch::GfsWatcher;
+#[cfg(all(feature = "async", feature = "watch"))]
+pub use watch::{
+    ChangeStream,
+    FileChangeEvent,
+};
 
-const BUFFER_SIZE: usize = 1024;
+/// how much hashers take in at once, and the default of `buffer_size`
+const BUFFER_SIZE: usize = 64 * 1024;
+/// how many more times a file that keeps changing while being read is read again
+const MAX_READ_RETRIES: usize = 3;
+/// how often a read waiting on another one for the same file checks whether it was cancelled
+const CANCEL_POLL: Duration = Duration::from_millis(10);
+/// how many failed lookups `recent_errors` remembers
+#[cfg(feature = "diagnostics")]
+const RECENT_ERRORS_CAPACITY: usize = 16;
 
 pub trait ReadFile {
-    fn read_file<P: AsRef<path::Path>>(&mut self, file_path: P) -> io::Result<&Box<[u8]>>;
+    fn read_file<P: AsRef<path::Path>>(&self, file_path: P) -> Result<FileContent, GfsError>;
+}
+
+pub trait WriteFile {
+    fn write_file<P: AsRef<path::Path>>(&self, file_path: P, bytes: &[u8]) -> Result<(), GfsError>;
+    fn remove_file<P: AsRef<path::Path>>(&self, file_path: P) -> Result<(), GfsError>;
+    fn rename<P: AsRef<path::Path>, Q: AsRef<path::Path>>(&self, from: P, to: Q) -> Result<(), GfsError>;
 }
 
 pub trait PathMapper {
     fn map<P: AsRef<path::Path>>(&self, file_name: P) -> Box<path::Path>;
 }
 
+/// `ReadFile` for trait objects, so one `Box<dyn DynReadFile>` can hold a `GemFileSystem`, a
+/// `MemoryBackend` or any other reader. everything implementing `ReadFile` implements it
+pub trait DynReadFile {
+    fn read_path(&self, file_path: &path::Path) -> Result<FileContent, GfsError>;
+}
+
+impl<T: ReadFile> DynReadFile for T {
+    fn read_path(&self, file_path: &path::Path) -> Result<FileContent, GfsError> {
+        self.read_file(file_path)
+    }
+}
+
+/// `WriteFile` for trait objects, see `DynReadFile`
+pub trait DynWriteFile {
+    fn write_path(&self, file_path: &path::Path, bytes: &[u8]) -> Result<(), GfsError>;
+    fn remove_path(&self, file_path: &path::Path) -> Result<(), GfsError>;
+    fn rename_path(&self, from: &path::Path, to: &path::Path) -> Result<(), GfsError>;
+}
+
+impl<T: WriteFile> DynWriteFile for T {
+    fn write_path(&self, file_path: &path::Path, bytes: &[u8]) -> Result<(), GfsError> {
+        self.write_file(file_path, bytes)
+    }
+    
+    fn remove_path(&self, file_path: &path::Path) -> Result<(), GfsError> {
+        self.remove_file(file_path)
+    }
+    
+    fn rename_path(&self, from: &path::Path, to: &path::Path) -> Result<(), GfsError> {
+        self.rename(from, to)
+    }
+}
+
+/// `PathMapper` for trait objects, see `DynReadFile`
+pub trait DynPathMapper {
+    fn map_path(&self, file_name: &path::Path) -> Box<path::Path>;
+}
+
+impl<T: PathMapper> DynPathMapper for T {
+    fn map_path(&self, file_name: &path::Path) -> Box<path::Path> {
+        self.map(file_name)
+    }
+}
+
+/// where gfs gets the current time from, so time-based behavior can be tested
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// the real monotonic clock, used by default. it panics on `wasm32-unknown-unknown`, where
+/// `reload_if_changed` needs a clock from `set_clock` instead and `unload_unused` doesn't
+/// read it, see `unload_grace`
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
 impl fmt::Debug for GemFileSystem {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "ResourceLoader Path: {:#?}", self.root)
@@ -41,10 +299,120 @@ impl fmt::Display for GemFileSystem {
     }
 }
 
+/// what is known about a cached file besides its content and hash
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileMeta {
+    /// modification time of the file on disk when it was read
+    pub modified: Option<SystemTime>,
+    /// the file kept changing while it was read, so the cached content may be torn
+    pub volatile: bool,
+    /// size of the content as read from disk, before any compression
+    pub size: u64,
+    /// the content in `content_map` is lz4-compressed
+    pub compressed: bool,
+    /// bumped every time the file is stored with different content
+    pub version: u64,
+    /// CRC-32 of the archive entry the file was read from, None for a plain file.
+    /// sync checks compare it against the archive instead of hashing anything
+    pub archive_crc: Option<u32>,
+    /// `BackendMeta::etag` of the file when it was read from a backend reporting one
+    pub etag: Option<String>,
+}
+
+/// a file or directory found by `GemFileSystem::list_dir`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DirEntry {
+    pub name: String,
+    pub is_dir: bool,
+    /// in bytes, as stored. 0 for a directory
+    pub size: u64,
+}
+
+/// what `GemFileSystem::metadata` tells about a file or directory without reading it
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    /// in bytes, as stored. 0 for a directory
+    pub size: u64,
+    /// None where it isn't known, as for files in packs and archives
+    pub modified: Option<SystemTime>,
+    /// can't be written through gfs, always true for packs, archives and backends
+    pub readonly: bool,
+    pub is_dir: bool,
+}
+
+/// outcome of `GemFileSystem::preload_dir` and `GemFileSystem::prefetch_manifest`
+#[derive(Debug, Default)]
+pub struct PreloadReport {
+    /// files now cached, including those that already were
+    pub files: usize,
+    /// their content, in bytes
+    pub bytes: u64,
+    pub failures: Vec<(PathBuf, GfsError)>,
+}
+
+/// cheap, opaque marker of which version of a file was read, see `GemFileSystem::has_changed`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChangeToken(u64);
+
 pub struct Cache {
     // storing the pointer of the file content: [T] in a HashMap
     pub sha2_map: HashMap<PathBuf, Box<[u8]>>,
-    pub content_map: HashMap<PathBuf, Box<[u8]>>,
+    /// handed out as is by reads, so content stays alive for as long as any caller holds it,
+    /// even after it is dropped from the cache
+    pub content_map: HashMap<PathBuf, FileContent>,
+    pub meta_map: HashMap<PathBuf, FileMeta>,
+    /// the digest the hashes in `sha2_map` were computed with
+    pub hash_algorithm: String,
+    hasher: Arc<dyn ContentHasher>,
+    last_version: u64,
+    #[cfg(feature = "lz4")]
+    compress: bool,
+    /// most bytes `content_map` may hold before least recently used entries are evicted
+    budget: Option<usize>,
+    /// content by its hash, for files with identical content to share it, see `deduplicate`
+    shared: Option<HashMap<Box<[u8]>, WeakContent>>,
+    /// where newly cached content is kept, see `content_store`
+    store: Arc<dyn ContentStore>,
+    /// see `spill_to`
+    spill_dir: Option<PathBuf>,
+    spilled: HashMap<PathBuf, Spilled>,
+    // bumped on every store and read, so reads only need a shared borrow to record a use
+    use_counter: AtomicU64,
+    last_used: HashMap<PathBuf, AtomicU64>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    disk_bytes: AtomicU64,
+}
+
+/// an entry evicted into a file by `Cache::spill_to`, with what the cache knew about it
+struct Spilled {
+    path: PathBuf,
+    meta: FileMeta,
+    hash: Option<Box<[u8]>>,
+}
+
+/// counters of how a cache has been used, see `Cache::stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CacheStats {
+    /// reads served for a file that was cached
+    pub hits: u64,
+    /// reads for a file that was not cached, whether or not it was found on disk
+    pub misses: u64,
+    /// entries dropped to stay within the budget
+    pub evictions: u64,
+    /// what `Cache::current_bytes` reports
+    pub total_bytes: usize,
+    pub entries: usize,
+    /// file content read from disk to serve reads, hashing for sync checks aside
+    pub bytes_read_from_disk: u64,
+    /// bytes a second downloaded through the `Throttle`s of mounted backends, over the last
+    /// second. only `GemFileSystem::stats` knows, `Cache::stats` leaves it 0
+    pub download_bytes_per_sec: u64,
 }
 
 impl Cache {
@@ -52,149 +420,5290 @@ impl Cache {
         Cache {
             content_map: HashMap::new(),
             sha2_map: HashMap::new(),
+            meta_map: HashMap::new(),
+            hash_algorithm: String::from(Sha256Hasher.name()),
+            hasher: Arc::new(Sha256Hasher),
+            last_version: 0,
+            #[cfg(feature = "lz4")]
+            compress: false,
+            budget: None,
+            shared: None,
+            store: Arc::new(HeapStore),
+            spill_dir: None,
+            spilled: HashMap::new(),
+            use_counter: AtomicU64::new(0),
+            last_used: HashMap::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            disk_bytes: AtomicU64::new(0),
         }
     }
-    pub fn store_file(&mut self, key: PathBuf, content_ptr: Box<[u8]>) {
-        let hash = process_sha256::<Sha256, _>(&mut Cursor::new(&content_ptr));
+    pub fn store_file<C: Into<FileContent>>(&mut self, key: PathBuf, content_ptr: C) {
+        self.store_file_with_meta(key, content_ptr, FileMeta::default());
+    }
+    
+    pub fn store_file_with_meta<C: Into<FileContent>>(&mut self, key: PathBuf, content_ptr: C, meta: FileMeta) {
+        let content_ptr = content_ptr.into();
+        // always hash the raw content, so sync checks against the disk file still work
+        let hash = {
+            let step = trace::step(Stage::Hash, &key);
+            step.bytes(content_ptr.len());
+            self.hasher.hash_bytes(&content_ptr)
+        };
         debug!("{:#?}",key);
         debug!("{:#?}",hash);
-        self.sha2_map.insert(key.clone(), hash.into_boxed_slice());
-        self.content_map.insert(key.clone(), content_ptr);
+        self.insert(key, content_ptr, meta, Some(hash));
     }
-}
-/// two purposes of gfs:
-/// read, cache, and manage file in the heap, regardless of file location
-/// map relative file path to absolute path for external usage 
-pub struct GemFileSystem {
-    pub cache: Cache,
-    pub root: path::PathBuf,
-}
-
-pub enum FileSyncState {
-    HashMatch,
-    HashUnmatch,
-}
-
-impl GemFileSystem {
-    pub fn new<P: AsRef<path::Path>>(root: P) -> GemFileSystem {
-        GemFileSystem {
-            cache: Cache::new(),
-            root: root.as_ref().to_path_buf(),
-        }
+    
+    /// same as `store_file_with_meta`, with `hash` already computed by `hasher`, e.g. while
+    /// the content was read
+    pub fn store_file_with_hash<C: Into<FileContent>>(&mut self, key: PathBuf, content_ptr: C, meta: FileMeta,
+                                                      hash: Vec<u8>) {
+        self.insert(key, content_ptr.into(), meta, Some(hash));
     }
     
-    /// load and return file into self.cache
-    // since Box<[u8]> holds the ownership of the file content, we can only return
-    // a reference to it.
-    pub fn fetch_and_cache_file<P: AsRef<path::Path>>(&mut self, file_path: P)
-        -> Option<&Box<[u8]>> {
-        let mut absolute_path = self.root.clone();
-        absolute_path.push(file_path.as_ref().clone());
-        debug!("{}", absolute_path.display());
-        
-        match absolute_path.exists() & &absolute_path.is_file() {
+    /// same as `store_file_with_meta`, but leaves hashing to the first sync check
+    pub fn store_file_unhashed<C: Into<FileContent>>(&mut self, key: PathBuf, content_ptr: C, meta: FileMeta) {
+        self.insert(key, content_ptr.into(), meta, None);
+    }
+    
+    fn insert(&mut self, key: PathBuf, content_ptr: FileContent, mut meta: FileMeta, hash: Option<Vec<u8>>) {
+        meta.size = content_ptr.len() as u64;
+        meta.version = match (&hash, self.sha2_map.get(&key), self.meta_map.get(&key)) {
+            (Some(hash), Some(old_hash), Some(old_meta)) if old_hash[..] == hash[..] => old_meta.version,
+            _ => {
+                self.last_version += 1;
+                self.last_version
+            }
+        };
+        #[cfg(feature = "lz4")]
+        let content_ptr = match self.compress {
             true => {
-                let file_ptr = utils::load_file_as_u8(&absolute_path);
-                self.cache.store_file(file_path.as_ref().to_path_buf(), file_ptr);
-                // now file_ptr is moved, the ownership is transferred to Cache
-                self.cache.content_map.get(file_path.as_ref())
+                meta.compressed = true;
+                FileContent::from(lz4_flex::compress_prepend_size(&content_ptr))
             }
-            false => {
-                None
+            false => content_ptr,
+        };
+        let content_ptr = match self.store.store(content_ptr.clone()) {
+            Ok(stored) => stored,
+            Err(err) => {
+                warn!("keeping {} on the heap, its content store failed: {}", key.display(), err);
+                content_ptr
+            }
+        };
+        let content_ptr = match (&hash, self.shared.is_some()) {
+            (Some(hash), true) => self.share(hash, content_ptr),
+            _ => content_ptr,
+        };
+        match hash {
+            Some(hash) => self.sha2_map.insert(key.clone(), hash.into_boxed_slice()),
+            None => self.sha2_map.remove(&key),
+        };
+        self.meta_map.insert(key.clone(), meta);
+        self.content_map.insert(key.clone(), content_ptr);
+        self.last_used.insert(key.clone(), AtomicU64::new(self.next_use()));
+        self.enforce_budget(Some(&key));
+    }
+    
+    /// the content of another cached file with the same hash and bytes, if there is one,
+    /// or else `content_ptr`, to be shared with the next file having that content
+    fn share(&mut self, hash: &[u8], content_ptr: FileContent) -> FileContent {
+        let entries = self.content_map.len();
+        let shared = match &mut self.shared {
+            Some(shared) => shared,
+            None => return content_ptr,
+        };
+        if let Some(existing) = shared.get(hash).and_then(WeakContent::upgrade) {
+            // the bytes are compared too, a non-cryptographic digest may collide
+            if existing == content_ptr {
+                return existing;
             }
+            return content_ptr;
         }
+        // forget content nobody holds anymore now and then, rather than on every eviction
+        if shared.len() > 2 * entries + 16 {
+            shared.retain(|_, content| content.is_alive());
+        }
+        shared.insert(Box::from(hash), content_ptr.downgrade());
+        content_ptr
     }
     
-    pub fn check_for_sync_file<P: AsRef<path::Path>>(&mut self, file_path: P) -> io::Result<FileSyncState> {
-        let if_file_in_cache = self.cache.sha2_map.contains_key(file_path.as_ref());
-        match if_file_in_cache {
-            false => {
-                let mut err = String::from("Resource not found in cache, cannot check for \
-                synchronicity");
-                err.push_str(&format!("{:#?}", file_path.as_ref().to_path_buf()));
-                Err(io::Error::new(io::ErrorKind::Other, err))
+    /// move every entry of `other` into this cache, with `policy` deciding which side wins
+    /// for paths cached in both. with `MergePolicy::Error` nothing is merged if any path
+    /// is in both caches
+    pub fn merge(&mut self, mut other: Cache, policy: MergePolicy) -> Result<(), GfsError> {
+        // hashes of different digests can't be compared against each other
+        if other.hash_algorithm != self.hash_algorithm {
+            return Err(GfsError::HashAlgorithmMismatch {
+                expected: self.hash_algorithm.clone(),
+                found: other.hash_algorithm.clone(),
+            });
+        }
+        if let MergePolicy::Error = policy {
+            if let Some(path) = other.content_map.keys().find(|key| self.content_map.contains_key(*key)) {
+                return Err(GfsError::MergeConflict { path: path.clone() });
             }
-            true => {
-                let mut absolute_path = self.root.clone();
-                absolute_path.push(file_path.as_ref().clone());
-                debug!("{}",absolute_path.display());
-                
-                if absolute_path.exists() && absolute_path.is_file() {
-                    let disk_file = utils::load_file_as_u8(&absolute_path);
-                    let disk_file_hash = process_sha256::<Sha256, _>(&mut Cursor::new(disk_file));
-                    let cached_file_hash = self.cache.sha2_map.get(file_path.as_ref()).unwrap();
-                    let diff_count = disk_file_hash
-                        .iter()
-                        .zip(cached_file_hash.iter())
-                        .filter(|&
-                                 (a, b)| a
-                            != b).count();
-                    if diff_count == 0 {
-                        return Ok(FileSyncState::HashMatch);
-                    } else {
-                        return Ok(FileSyncState::HashUnmatch);
-                    }
-                } else {
-                    let mut err = String::from("Resource not found at path: ");
-                    err.push_str(&format!("{:#?}", file_path.as_ref().to_path_buf()));
-                    Err(io::Error::new(io::ErrorKind::Other, err))
+        }
+        for (key, content_ptr) in other.content_map.drain() {
+            let mut meta = other.meta_map.remove(&key).unwrap_or_default();
+            if let Some(old_meta) = self.meta_map.get(&key) {
+                let keep_old = match policy {
+                    MergePolicy::PreferNewer => old_meta.modified >= meta.modified,
+                    MergePolicy::PreferOther | MergePolicy::Error => false,
+                };
+                if keep_old {
+                    continue;
                 }
             }
+            // versions are only meaningful within one cache
+            self.last_version += 1;
+            meta.version = self.last_version;
+            match other.sha2_map.remove(&key) {
+                Some(hash) => self.sha2_map.insert(key.clone(), hash),
+                None => self.sha2_map.remove(&key),
+            };
+            self.meta_map.insert(key.clone(), meta);
+            self.last_used.insert(key.clone(), AtomicU64::new(self.next_use()));
+            self.content_map.insert(key, content_ptr);
         }
+        self.enforce_budget(None);
+        Ok(())
     }
-}
-
-impl ReadFile for GemFileSystem {
-    /// format: gfs.read_file(&"models/chest.obj")
-    /// or anything, typed AsRef<path::Path>, with a string formatted as "models/chest.obj" or like
-    fn read_file<P: AsRef<path::Path>>(&mut self, file_path: P) -> io::Result<&Box<[u8]>> {
-        let if_file_in_cache = self.cache.content_map.contains_key(file_path.as_ref());
-        match if_file_in_cache {
-            false => {
-                if let Some(file_ptr) = self.fetch_and_cache_file(&file_path) {
-                    return Ok(file_ptr);
-                } else {
-                    // if reach here, it means it cannot find the file both in cache or in disk
-                    let mut err = String::from("Resource not found at path: ");
-                    err.push_str(&format!("{:#?}", file_path.as_ref()));
-                    Err(io::Error::new(io::ErrorKind::Other, err))
+    
+    /// hash with `hasher` from now on, e.g. a faster non-cryptographic one. switching to a
+    /// different digest drops every hash computed so far, sync checks then rehash on demand
+    pub fn set_hasher<H: ContentHasher + 'static>(&mut self, hasher: H) {
+        if hasher.name() != self.hash_algorithm {
+            self.sha2_map.clear();
+            self.hash_algorithm = String::from(hasher.name());
+        }
+        self.hasher = Arc::new(hasher);
+    }
+    
+    /// hash the cached content of a file again, e.g. one stored without a hash.
+    /// returns false if the file is not cached
+    pub fn rehash<P: AsRef<path::Path>>(&mut self, key: P) -> bool {
+        let hash = match self.raw_content(key.as_ref()) {
+            Some(content) => {
+                let step = trace::step(Stage::Hash, key.as_ref());
+                step.bytes(content.len());
+                self.hasher.hash_bytes(&content)
+            }
+            None => return false,
+        };
+        self.sha2_map.insert(key.as_ref().to_path_buf(), hash.into_boxed_slice());
+        true
+    }
+    
+    /// the hash of a cached file, None if it isn't cached or isn't hashed yet
+    pub fn file_hash<P: AsRef<path::Path>>(&self, key: P) -> Option<FileHash> {
+        let digest = self.sha2_map.get(key.as_ref())?;
+        Some(FileHash::new(&self.hash_algorithm, digest.clone()))
+    }
+    
+    /// the cached content of a file as it was read from disk, i.e. decompressed if needed
+    fn raw_content(&self, key: &path::Path) -> Option<FileContent> {
+        #[cfg(feature = "lz4")]
+        {
+            if let Some(FileMeta { compressed: true, .. }) = self.meta_map.get(key) {
+                return self.decompress(key).map(FileContent::from);
+            }
+        }
+        self.content_map.get(key).cloned()
+    }
+    
+    /// drop a file and its hash from the cache, returning the content if it was cached
+    pub fn remove_file<P: AsRef<path::Path>>(&mut self, key: P) -> Option<FileContent> {
+        if let Some(spilled) = self.spilled.remove(key.as_ref()) {
+            let _ = fs::remove_file(spilled.path);
+        }
+        self.sha2_map.remove(key.as_ref());
+        self.meta_map.remove(key.as_ref());
+        self.last_used.remove(key.as_ref());
+        self.content_map.remove(key.as_ref())
+    }
+    
+    /// move a file, its hash and meta to another key, replacing whatever was cached there.
+    /// returns whether `from` was cached
+    pub fn rename_file<P: AsRef<path::Path>, Q: AsRef<path::Path>>(&mut self, from: P, to: Q) -> bool {
+        if from.as_ref() == to.as_ref() {
+            return self.content_map.contains_key(from.as_ref());
+        }
+        self.remove_file(to.as_ref());
+        let to = to.as_ref().to_path_buf();
+        if let Some(hash) = self.sha2_map.remove(from.as_ref()) {
+            self.sha2_map.insert(to.clone(), hash);
+        }
+        if let Some(meta) = self.meta_map.remove(from.as_ref()) {
+            self.meta_map.insert(to.clone(), meta);
+        }
+        if let Some(last_used) = self.last_used.remove(from.as_ref()) {
+            self.last_used.insert(to.clone(), last_used);
+        }
+        match self.content_map.remove(from.as_ref()) {
+            Some(content) => {
+                self.content_map.insert(to, content);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// cap the bytes held by `content_map`, evicting the least recently stored or read entries
+    /// once storing a file goes over the budget. the file just stored is never evicted, even
+    /// if it doesn't fit on its own. None (no limit) by default.
+    /// evicted content is only freed once no caller holds on to it anymore
+    pub fn set_budget(&mut self, bytes: Option<usize>) {
+        self.budget = bytes;
+        self.enforce_budget(None);
+    }
+    
+    pub fn budget(&self) -> Option<usize> {
+        self.budget
+    }
+    
+    /// record that a cached file was just used, so it is evicted last
+    fn touch(&self, key: &path::Path) {
+        if let Some(last_used) = self.last_used.get(key) {
+            last_used.store(self.next_use(), Ordering::Relaxed);
+        }
+    }
+    
+    fn next_use(&self) -> u64 {
+        self.use_counter.fetch_add(1, Ordering::Relaxed) + 1
+    }
+    
+    /// evict least recently used entries other than `keep` until the cache fits its budget
+    fn enforce_budget(&mut self, keep: Option<&path::Path>) {
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None => return,
+        };
+        let mut current = self.current_bytes();
+        while current > budget {
+            // entries put into content_map directly were never used through the cache
+            let oldest = self.content_map.keys()
+                .filter(|key| Some(key.as_path()) != keep)
+                .min_by_key(|key| self.last_used.get(*key).map_or(0, |last_used| last_used.load(Ordering::Relaxed)))
+                .cloned();
+            let oldest = match oldest {
+                Some(oldest) => oldest,
+                None => return,
+            };
+            debug!("evicting {} to stay within the cache budget", oldest.display());
+            let spilled = match self.spill_dir {
+                Some(_) => self.raw_content(&oldest).zip(self.meta_map.get(&oldest).cloned())
+                    .map(|(raw, meta)| (raw, meta, self.sha2_map.get(&oldest).cloned())),
+                None => None,
+            };
+            if let Some(content) = self.remove_file(&oldest) {
+                if let Some((raw, meta, hash)) = spilled {
+                    self.spill(oldest, &raw, meta, hash);
                 }
+                current = match self.shared {
+                    // shared content is only freed along with the last file having it
+                    Some(_) => self.current_bytes(),
+                    None => current - content.len(),
+                };
+                self.evictions.fetch_add(1, Ordering::Relaxed);
             }
-            true => {
-                return Ok(self.fetch_and_cache_file(&file_path).unwrap());
+        }
+    }
+    
+    /// write an evicted entry into the spill directory, dropping it if that fails
+    fn spill(&mut self, key: PathBuf, content: &[u8], meta: FileMeta, hash: Option<Box<[u8]>>) {
+        let dir = match &self.spill_dir {
+            Some(dir) => dir,
+            None => return,
+        };
+        // versions are unique within the cache, and one file only ever has one of them spilled
+        let path = dir.join(format!("{}.spill", meta.version));
+        match fs::create_dir_all(dir).and_then(|_| fs::write(&path, content)) {
+            Ok(_) => {
+                debug!("spilled {} into {}", key.display(), path.display());
+                self.spilled.insert(key, Spilled { path, meta, hash });
+            }
+            Err(err) => debug!("could not spill {}: {}", key.display(), err),
+        }
+    }
+    
+    /// sync every spill file and the spill directory to disk, see `GemFileSystem::flush`
+    fn sync_spilled(&self) -> io::Result<()> {
+        for spilled in self.spilled.values() {
+            fs::File::open(&spilled.path)?.sync_all()?;
+        }
+        match &self.spill_dir {
+            Some(dir) if !self.spilled.is_empty() => sync_dir(dir),
+            _ => Ok(()),
+        }
+    }
+    
+    /// move evicted entries into files under `dir` instead of dropping them, e.g. content that
+    /// took a download or a decompression to get, for the next read to take it back from there.
+    /// dropping an entry in any other way drops its spilled copy too, as does dropping the cache.
+    /// None, the default, stops spilling, what is spilled already stays where it is
+    pub fn spill_to(&mut self, dir: Option<PathBuf>) {
+        self.spill_dir = dir;
+    }
+    
+    /// put a spilled entry back, as it was before it was spilled. None if it wasn't
+    /// spilled or the spill file can't be read anymore
+    fn unspill(&mut self, key: &path::Path) -> Option<FileContent> {
+        let spilled = self.spilled.remove(key)?;
+        let content = fs::read(&spilled.path);
+        let _ = fs::remove_file(&spilled.path);
+        let content = FileContent::from(content.ok()?);
+        debug!("taking {} back from {}", key.display(), spilled.path.display());
+        let version = spilled.meta.version;
+        self.insert(key.to_path_buf(), content, spilled.meta, spilled.hash.map(Vec::from));
+        // the same content, so the same version for change tokens
+        if let Some(meta) = self.meta_map.get_mut(key) {
+            meta.version = version;
+        }
+        self.raw_content(key)
+    }
+    
+    /// what the cache keeps for a file just stored, which may be content shared with identical
+    /// files or a copy in the content store rather than what it was handed. None if compressed
+    fn kept_content(&self, key: &path::Path) -> Option<FileContent> {
+        match self.meta_map.get(key) {
+            Some(FileMeta { compressed: true, .. }) => None,
+            _ => self.content_map.get(key).cloned(),
+        }
+    }
+    
+    /// have files that are stored with identical content share a single copy of it, e.g.
+    /// a texture shipped by several mod packs. only content hashed as it is stored is
+    /// shared, i.e. not with `HashTiming::Lazy`. entries already in the cache are left as they are
+    pub fn deduplicate(&mut self, enabled: bool) {
+        self.shared = match enabled {
+            true => Some(self.shared.take().unwrap_or_default()),
+            false => None,
+        };
+    }
+    
+    /// keep newly cached content in `store`, e.g. an `MmapStore` for a large cache that
+    /// would fragment the heap. entries already in the cache are left as they are
+    pub fn content_store<S: ContentStore + 'static>(&mut self, store: S) {
+        self.store = Arc::new(store);
+    }
+    
+    /// store newly cached content lz4-compressed, trading a decompression on every read
+    /// for memory. entries already in the cache are left as they are
+    #[cfg(feature = "lz4")]
+    pub fn compress_cache(&mut self, enabled: bool) {
+        self.compress = enabled;
+    }
+    
+    /// the uncompressed content of a cached file that was stored compressed
+    #[cfg(feature = "lz4")]
+    pub fn decompress<P: AsRef<path::Path>>(&self, key: P) -> Option<Box<[u8]>> {
+        let compressed = self.content_map.get(key.as_ref())?;
+        match lz4_flex::decompress_size_prepended(compressed) {
+            Ok(content) => Some(content.into_boxed_slice()),
+            Err(err) => {
+                error!("corrupted compressed cache entry {}: {}", key.as_ref().display(), err);
+                None
             }
         }
     }
+    
+    /// a snapshot of the counters, which keep running across `GemFileSystem::swap_cache`
+    /// only as part of the cache they belong to
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            total_bytes: self.current_bytes(),
+            entries: self.content_map.len(),
+            bytes_read_from_disk: self.disk_bytes.load(Ordering::Relaxed),
+            download_bytes_per_sec: 0,
+        }
+    }
+    
+    /// start counting hits, misses, evictions and disk reads from zero again
+    pub fn reset_stats(&self) {
+        for counter in &[&self.hits, &self.misses, &self.evictions, &self.disk_bytes] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+    
+    fn record_hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    fn record_miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    fn record_disk_read(&self, bytes: u64) {
+        self.disk_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+    
+    /// bytes held by `content_map`, i.e. after compression, counting content shared
+    /// between files once. this is what `set_budget` limits
+    pub fn current_bytes(&self) -> usize {
+        let mut counted = HashSet::new();
+        self.content_map.values()
+            .filter(|content| counted.insert((content.as_ptr(), content.len())))
+            .map(|content| content.len())
+            .sum()
+    }
+    
+    /// uncompressed size of the cached content divided by `current_bytes`,
+    /// 1.0 when nothing is compressed
+    pub fn compression_ratio(&self) -> f64 {
+        let current = self.current_bytes();
+        if current == 0 {
+            return 1.0;
+        }
+        let raw: u64 = self.meta_map.values().map(|meta| meta.size).sum();
+        raw as f64 / current as f64
+    }
 }
 
-impl PathMapper for GemFileSystem {
-    fn map<P: AsRef<path::Path>>(&mut self, file_path: P) -> Box<path::Path> {
-        let mut absolute_path = self.root.clone();
-        absolute_path.push(file_path.as_ref().clone());
-        absolute_path.into_boxed_path()
+impl Default for Cache {
+    fn default() -> Cache {
+        Cache::new()
+    }
+}
+
+impl Drop for Cache {
+    fn drop(&mut self) {
+        for spilled in self.spilled.values() {
+            let _ = fs::remove_file(&spilled.path);
+        }
+    }
+}
+/// where a GemFileSystem keeps its cache. the slot is only locked to take or replace the
+/// pointer, so `swap_cache` never waits for reads and reads never wait for it
+pub(crate) type CacheSlot = RwLock<Arc<RwLock<Cache>>>;
+
+pub(crate) fn current_cache(slot: &CacheSlot) -> Arc<RwLock<Cache>> {
+    Arc::clone(&slot.read().unwrap_or_else(|poisoned| poisoned.into_inner()))
+}
+
+/// shared access to a cache, see `GemFileSystem::cache`. keeps working on the cache it was
+/// taken from even once `swap_cache` swapped in another
+pub struct CacheReadGuard {
+    // declared first, so it is dropped before the lock it borrows from
+    guard: RwLockReadGuard<'static, Cache>,
+    _cache: Arc<RwLock<Cache>>,
+}
+
+impl CacheReadGuard {
+    fn new(cache: Arc<RwLock<Cache>>) -> CacheReadGuard {
+        // a panicking reader leaves the cache itself intact, so keep serving
+        let guard = cache.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // SAFETY: the lock lives on the heap for as long as `_cache` holds it, which outlives
+        // the guard
+        let guard = unsafe { mem::transmute::<RwLockReadGuard<'_, Cache>, RwLockReadGuard<'static, Cache>>(guard) };
+        CacheReadGuard {
+            guard,
+            _cache: cache,
+        }
     }
+}
+
+impl Deref for CacheReadGuard {
+    type Target = Cache;
     
+    fn deref(&self) -> &Cache {
+        &self.guard
+    }
 }
-fn process_sha256<D: Digest + Default, R: Read>(reader: &mut R) -> Vec<u8> {
-    let mut sh = D::default();
-    let mut buffer = [0u8; BUFFER_SIZE];
-    loop {
-        let n = match reader.read(&mut buffer) {
-            Ok(n) => n,
-            Err(_) => panic!(),
-        };
-        sh.input(&buffer[..n]);
-        if n == 0 || n < BUFFER_SIZE {
-            break;
+
+/// exclusive access to a cache, see `GemFileSystem::cache_mut`
+pub struct CacheWriteGuard {
+    // declared first, so it is dropped before the lock it borrows from
+    guard: RwLockWriteGuard<'static, Cache>,
+    _cache: Arc<RwLock<Cache>>,
+}
+
+impl CacheWriteGuard {
+    fn new(cache: Arc<RwLock<Cache>>) -> CacheWriteGuard {
+        let guard = cache.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // SAFETY: as for `CacheReadGuard`
+        let guard = unsafe { mem::transmute::<RwLockWriteGuard<'_, Cache>, RwLockWriteGuard<'static, Cache>>(guard) };
+        CacheWriteGuard {
+            guard,
+            _cache: cache,
         }
     }
-    sh.result().to_vec()
 }
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
+impl Deref for CacheWriteGuard {
+    type Target = Cache;
+    
+    fn deref(&self) -> &Cache {
+        &self.guard
+    }
+}
+
+impl DerefMut for CacheWriteGuard {
+    fn deref_mut(&mut self) -> &mut Cache {
+        &mut self.guard
+    }
+}
+
+/// what `SharedGemFileSystem` used to be for, sharing a GemFileSystem between threads,
+/// takes no more than an `Arc` now that reads take `&self`
+#[deprecated(note = "share a GemFileSystem in an Arc, it has swap_cache itself")]
+pub type SharedGemFileSystem = Arc<GemFileSystem>;
+
+/// two purposes of gfs:
+/// read, cache, and manage file in the heap, regardless of file location
+/// map relative file path to absolute path for external usage 
+/// all reads take `&self`, so a single GemFileSystem can be shared between threads in an `Arc`
+pub struct GemFileSystem {
+    // shared with the hash worker, if there is one
+    cache: Arc<CacheSlot>,
+    pub root: path::PathBuf,
+    /// read invalidated files back from disk right away in `apply_change_feed`
+    pub reload_on_change: bool,
+    #[cfg(feature = "diagnostics")]
+    recent_errors: Mutex<VecDeque<(PathBuf, String)>>,
+    miss_log_level: log::Level,
+    miss_log_sample: u64,
+    misses_until_log: Mutex<u64>,
+    /// extra roots with their priority, highest first, see `mount`
+    mounts: Vec<(Mount, i32)>,
+    /// searched in order when a file is not under `root`, see `add_fallback_root`
+    fallback_roots: Vec<(PathBuf, bool)>,
+    clock: Box<dyn Clock>,
+    /// whether `clock` came from `set_clock`, `SystemClock` can't be read on the web
+    #[cfg(target_arch = "wasm32")]
+    clock_set: bool,
+    min_reload_interval: Duration,
+    last_reload: Mutex<HashMap<PathBuf, Instant>>,
+    /// disk reads under way, which other reads of the same file wait for instead of reading it again
+    in_flight: Mutex<HashMap<PathBuf, Arc<InFlight>>>,
+    /// see `register_loader`
+    assets: Mutex<Assets>,
+    unload_grace: Duration,
+    evict_dependents: EvictDependents,
+    /// glob patterns with their callback, see `on_change`
+    change_callbacks: Mutex<Vec<(String, ChangeCallback)>>,
+    /// files changed since the last `dispatch_changes`, with the time of their latest change
+    pending_changes: Mutex<HashMap<PathBuf, Instant>>,
+    change_debounce: Duration,
+    /// files written by `write_file_deferred` that `flush` still has to sync
+    unsynced: Mutex<HashSet<PathBuf>>,
+    /// handed out for missing files under a directory, see `prefix_placeholder`
+    prefix_placeholders: Vec<(PathBuf, FileContent)>,
+    /// handed out for missing files with an extension, see `extension_placeholder`
+    extension_placeholders: Vec<(String, FileContent)>,
+    miss_handler: Option<Box<MissHandler>>,
+    hash_timing: HashTiming,
+    sync_policy: SyncPolicy,
+    /// see `paranoid_sync`
+    paranoid_sync: bool,
+    hash_worker: Option<HashWorker>,
+    stream_threshold: Option<u64>,
+    /// see `buffer_size`
+    buffer_size: usize,
+    retry_policy: RetryPolicy,
+    /// see `read_timeout`
+    read_timeout: Option<Duration>,
+    /// see `lowercase_keys`
+    lowercase_keys: bool,
+    /// see `strip_bom`
+    strip_bom: bool,
+    /// see `case_insensitive`
+    case_insensitive: bool,
+    symlink_policy: SymlinkPolicy,
+    /// prefixes like "textures" of paths like "textures://wood.png" with the directory they
+    /// stand for, see `alias`
+    aliases: Vec<(String, PathBuf)>,
+    /// normalized prefixes like "pack://bundle1" with the mount they are looked up in, see `route`
+    routes: Vec<(PathBuf, PathBuf)>,
+    /// see `set_locale`
+    locale: Option<String>,
+    locale_pattern: String,
+    /// see `variant_extensions`
+    variant_extensions: Vec<String>,
+    /// see `quality_tiers`, lowest first
+    quality_tiers: Vec<String>,
+    quality_pattern: String,
+    quality: Option<String>,
+    /// keys with the variant of the file they resolved to, see `set_locale`
+    resolved: Mutex<HashMap<PathBuf, PathBuf>>,
+    /// hashes of files on disk brought in by `load_index`
+    disk_index: RwLock<DiskIndex>,
+    #[cfg(feature = "mmap")]
+    mmap_extensions: Vec<String>,
+    #[cfg(any(feature = "zstd", feature = "lz4"))]
+    codecs: Vec<(String, Codec)>,
+    #[cfg(feature = "encryption")]
+    key_provider: Option<Box<dyn KeyProvider>>,
+    /// the signed manifests of directories mounted with `mount_verified`
+    #[cfg(feature = "signing")]
+    verified: HashMap<PathBuf, Manifest>,
+}
+
+/// one disk read shared by every thread wanting the file meanwhile. the content is
+/// Some(None) once the read failed, the waiters then read for themselves
+#[derive(Default)]
+struct InFlight {
+    content: Mutex<Option<Option<FileContent>>>,
+    landed: Condvar,
+}
+
+/// hands the result of a disk read to everyone waiting on it, even if the read panicked
+struct Landing<'a> {
+    gfs: &'a GemFileSystem,
+    file_path: &'a path::Path,
+    flight: Arc<InFlight>,
+    content: Option<FileContent>,
+}
+
+impl Drop for Landing<'_> {
+    fn drop(&mut self) {
+        lock(&self.gfs.in_flight).remove(self.file_path);
+        *lock(&self.flight.content) = Some(self.content.take());
+        self.flight.landed.notify_all();
+    }
+}
+
+/// something mounted with `GemFileSystem::mount`
+enum Mount {
+    Dir(PathBuf),
+    Pack(PackMount),
+    #[cfg(feature = "zip")]
+    Zip(ZipMount),
+    Backend(PathBuf, Box<dyn FileBackend>),
+}
+
+impl Mount {
+    fn path(&self) -> &path::Path {
+        match self {
+            Mount::Dir(dir) => dir,
+            Mount::Pack(pack) => &pack.path,
+            #[cfg(feature = "zip")]
+            Mount::Zip(archive) => &archive.path,
+            Mount::Backend(name, _) => name,
+        }
+    }
+    
+    fn root(&self) -> Root<'_> {
+        match self {
+            Mount::Dir(dir) => Root::Dir(dir),
+            Mount::Pack(pack) => Root::Pack(pack),
+            #[cfg(feature = "zip")]
+            Mount::Zip(archive) => Root::Zip(archive),
+            Mount::Backend(name, backend) => Root::Backend(name, &**backend),
+        }
+    }
+}
+
+/// one place files are looked up in, see `GemFileSystem::search_order`
+#[derive(Clone, Copy)]
+enum Root<'a> {
+    Dir(&'a path::Path),
+    Pack(&'a PackMount),
+    #[cfg(feature = "zip")]
+    Zip(&'a ZipMount),
+    /// with the name it was mounted under
+    Backend(&'a path::Path, &'a dyn FileBackend),
+}
+
+/// a heap block whose first byte sits on a caller-chosen boundary, for handing
+/// file content straight to APIs with alignment requirements (e.g. GPU staging buffers)
+pub struct AlignedBytes {
+    ptr: ptr::NonNull<u8>,
+    len: usize,
+    layout: Layout,
+}
+
+// AlignedBytes uniquely owns its block, just like Box<[u8]>
+unsafe impl Send for AlignedBytes {}
+
+unsafe impl Sync for AlignedBytes {}
+
+impl AlignedBytes {
+    /// copy `bytes` into a freshly allocated block aligned to `align`,
+    /// which has to be a non-zero power of two
+    pub fn copy_from_slice(bytes: &[u8], align: usize) -> io::Result<AlignedBytes> {
+        // zero-sized allocations are not allowed, so an empty file still gets one byte
+        let layout = match Layout::from_size_align(bytes.len().max(1), align) {
+            Ok(layout) => layout,
+            Err(_) => {
+                let mut err = String::from("Alignment has to be a non-zero power of two, got: ");
+                err.push_str(&format!("{}", align));
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, err));
+            }
+        };
+        let ptr = match ptr::NonNull::new(unsafe { alloc::alloc(layout) }) {
+            Some(ptr) => ptr,
+            None => alloc::handle_alloc_error(layout),
+        };
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), ptr.as_ptr(), bytes.len());
+        }
+        Ok(AlignedBytes {
+            ptr,
+            len: bytes.len(),
+            layout,
+        })
+    }
+    
+    pub fn align(&self) -> usize {
+        self.layout.align()
+    }
+}
+
+impl Deref for AlignedBytes {
+    type Target = [u8];
+    
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for AlignedBytes {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
+
+impl fmt::Debug for AlignedBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AlignedBytes {{ len: {}, align: {} }}", self.len, self.align())
+    }
+}
+
+/// whether a cached file still has something on disk to be reloaded from
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BackingStatus {
+    /// absolute path of the file backing the entry, None if it no longer exists
+    pub resolved: Option<PathBuf>,
+    /// the cached content no longer matches the backing file, or there is none
+    pub stale: bool,
+}
+
+/// which entry `Cache::merge` keeps for a path cached on both sides
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// the one read from disk more recently, keeping the existing entry on a tie
+    PreferNewer,
+    /// always the entry of the cache being merged in, e.g. a downloaded delta
+    PreferOther,
+    /// refuse to merge at all
+    Error,
+}
+
+/// how a single `read_file_opts` call deals with hashing
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashMode {
+    /// hash content when it is stored as `GemFileSystem::hash_timing` says, just like `read_file`
+    #[default]
+    Default,
+    /// don't hash at all, the next sync check hashes the cached content on demand
+    Skip,
+    /// hash the content even when it is served from the cache
+    Force,
+}
+
+/// when content read from disk gets hashed for sync checks, see `GemFileSystem::hash_timing`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashTiming {
+    /// right away, before the read returns
+    #[default]
+    Eager,
+    /// on the first sync check, so files that are never checked are never hashed
+    Lazy,
+    /// on a worker thread, `flush` waits for it to catch up
+    Background,
+}
+
+/// whether cached files are hashed to be checked against their source, see
+/// `GemFileSystem::sync_policy`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncPolicy {
+    #[default]
+    Enabled,
+    /// nothing is hashed, for shipping builds whose content never changes while they run.
+    /// sync checks fail with `GfsError::SyncDisabled`
+    Disabled,
+}
+
+/// called by `GemFileSystem::dispatch_changes` with a changed file and its new content
+type ChangeCallback = Arc<dyn Fn(&path::Path, &FileContent) + Send + Sync>;
+
+type MissHandler = dyn Fn(&path::Path) -> Option<Box<[u8]>> + Send + Sync;
+
+/// content read from disk, with its hash if it was hashed while it was read
+type Hashed = (Box<[u8]>, Option<Vec<u8>>);
+
+/// told how many bytes of a file are read and how many there are, see `read_file_with_progress`
+type Progress<'a> = &'a dyn Fn(u64, u64);
+
+/// what follows a single load while it is read
+#[derive(Clone, Copy, Default)]
+struct Tracking<'a> {
+    progress: Option<Progress<'a>>,
+    /// see `read_file_cancellable`
+    cancel: Option<&'a CancellationToken>,
+    /// see `GemFileSystem::read_timeout`
+    deadline: Option<(&'a dyn Clock, Instant)>,
+}
+
+impl Tracking<'_> {
+    fn report(&self, done: u64, total: u64) {
+        if let Some(progress) = self.progress {
+            progress(done, total);
+        }
+    }
+    
+    fn cancelled(&self) -> bool {
+        self.cancel.is_some_and(CancellationToken::is_cancelled)
+    }
+    
+    /// how long is left until the deadline, None without one
+    fn time_left(&self) -> Option<Duration> {
+        self.deadline.map(|(clock, deadline)| deadline.saturating_duration_since(clock.now()))
+    }
+    
+    fn timed_out(&self) -> bool {
+        self.time_left() == Some(Duration::ZERO)
+    }
+}
+
+/// what `GemFileSystem::evict` does about the files depending on the one it evicts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvictDependents {
+    Ignore,
+    /// log a warning if any of them is still cached
+    #[default]
+    Warn,
+    /// evict them as well
+    Cascade,
+}
+
+/// which symlinks under a root files may be read through, see `GemFileSystem::symlink_policy`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    #[default]
+    Follow,
+    /// no symlinks at all, besides the root itself
+    Deny,
+    /// only symlinks that lead to somewhere inside the same root
+    DenyOutsideRoot,
+}
+
+/// per-call options for `GemFileSystem::read_file_opts`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadOptions {
+    pub hash: HashMode,
+    /// keep a file loaded from disk in the cache
+    pub cache: bool,
+}
+
+impl Default for ReadOptions {
+    fn default() -> ReadOptions {
+        ReadOptions {
+            hash: HashMode::Default,
+            cache: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FileSyncState {
+    HashMatch,
+    HashUnmatch,
+    /// the file is cached but gone from disk. only `check_for_sync_all` reports this,
+    /// `check_for_sync_file` returns an error instead
+    MissingOnDisk,
+}
+
+impl GemFileSystem {
+    pub fn new<P: AsRef<path::Path>>(root: P) -> GemFileSystem {
+        GemFileSystem {
+            cache: Arc::new(RwLock::new(Arc::new(RwLock::new(Cache::new())))),
+            root: root.as_ref().to_path_buf(),
+            reload_on_change: false,
+            #[cfg(feature = "diagnostics")]
+            recent_errors: Mutex::new(VecDeque::with_capacity(RECENT_ERRORS_CAPACITY)),
+            miss_log_level: log::Level::Debug,
+            miss_log_sample: 1,
+            misses_until_log: Mutex::new(0),
+            mounts: Vec::new(),
+            fallback_roots: Vec::new(),
+            clock: Box::new(SystemClock),
+            #[cfg(target_arch = "wasm32")]
+            clock_set: false,
+            min_reload_interval: Duration::from_secs(0),
+            last_reload: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+            assets: Mutex::new(Assets::default()),
+            unload_grace: Duration::from_secs(0),
+            evict_dependents: EvictDependents::Warn,
+            change_callbacks: Mutex::new(Vec::new()),
+            pending_changes: Mutex::new(HashMap::new()),
+            change_debounce: Duration::from_millis(100),
+            unsynced: Mutex::new(HashSet::new()),
+            prefix_placeholders: Vec::new(),
+            extension_placeholders: Vec::new(),
+            miss_handler: None,
+            hash_timing: HashTiming::Eager,
+            sync_policy: SyncPolicy::Enabled,
+            paranoid_sync: false,
+            hash_worker: None,
+            stream_threshold: None,
+            buffer_size: BUFFER_SIZE,
+            retry_policy: RetryPolicy::default(),
+            read_timeout: None,
+            lowercase_keys: false,
+            strip_bom: true,
+            case_insensitive: false,
+            symlink_policy: SymlinkPolicy::Follow,
+            aliases: Vec::new(),
+            routes: Vec::new(),
+            locale: None,
+            locale_pattern: String::from("{dir}/{stem}.{locale}{ext}"),
+            variant_extensions: Vec::new(),
+            quality_tiers: Vec::new(),
+            quality_pattern: String::new(),
+            quality: None,
+            resolved: Mutex::new(HashMap::new()),
+            disk_index: RwLock::new(DiskIndex::default()),
+            #[cfg(feature = "mmap")]
+            mmap_extensions: Vec::new(),
+            #[cfg(any(feature = "zstd", feature = "lz4"))]
+            codecs: Vec::new(),
+            #[cfg(feature = "encryption")]
+            key_provider: None,
+            #[cfg(feature = "signing")]
+            verified: HashMap::new(),
+        }
+    }
+    
+    /// root the filesystem at `subdir` inside the resource directory of the running app
+    /// bundle, i.e. `Foo.app/Contents/Resources` on macOS and `Foo.app` itself on iOS
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub fn from_app_bundle<P: AsRef<path::Path>>(subdir: P) -> Result<GemFileSystem, GfsError> {
+        let exe = std::env::current_exe()?;
+        match bundle_resource_dir(&exe) {
+            Some(mut root) => {
+                root.push(subdir);
+                Ok(GemFileSystem::new(root))
+            }
+            None => {
+                let mut err = String::from("Executable is not inside an app bundle: ");
+                err.push_str(&format!("{:#?}", exe));
+                Err(GfsError::Io(io::Error::new(io::ErrorKind::NotFound, err)))
+            }
+        }
+    }
+    
+    /// severity used when logging reads that could not be served from the cache,
+    /// `Debug` by default
+    pub fn miss_log_level(&mut self, level: log::Level) -> &mut GemFileSystem {
+        self.miss_log_level = level;
+        self
+    }
+    
+    /// only log one in every `one_in` misses, so a flood of missing assets doesn't
+    /// flood the log as well. 1 (log every miss) by default
+    pub fn miss_log_sample(&mut self, one_in: u64) -> &mut GemFileSystem {
+        self.miss_log_sample = one_in.max(1);
+        *lock(&self.misses_until_log) = 0;
+        self
+    }
+    
+    /// see `Cache::compress_cache`
+    #[cfg(feature = "lz4")]
+    pub fn compress_cache(&mut self, enabled: bool) -> &mut GemFileSystem {
+        self.cache_mut().compress_cache(enabled);
+        self
+    }
+    
+    /// see `Cache::deduplicate`
+    pub fn deduplicate(&mut self, enabled: bool) -> &mut GemFileSystem {
+        self.cache_mut().deduplicate(enabled);
+        self
+    }
+    
+    /// see `Cache::content_store`
+    pub fn content_store<S: ContentStore + 'static>(&mut self, store: S) -> &mut GemFileSystem {
+        self.cache_mut().content_store(store);
+        self
+    }
+    
+    /// see `Cache::spill_to`
+    pub fn spill_dir<P: AsRef<path::Path>>(&mut self, dir: Option<P>) -> &mut GemFileSystem {
+        self.cache_mut().spill_to(dir.map(|dir| dir.as_ref().to_path_buf()));
+        self
+    }
+    
+    /// see `Cache::set_budget`
+    pub fn cache_budget(&mut self, bytes: Option<usize>) -> &mut GemFileSystem {
+        self.cache_mut().set_budget(bytes);
+        self
+    }
+    
+    /// when content read from disk is hashed, `HashTiming::Eager` by default
+    pub fn hash_timing(&mut self, timing: HashTiming) -> &mut GemFileSystem {
+        self.hash_worker = None;
+        self.hash_timing = timing;
+        if let HashTiming::Background = timing {
+            match HashWorker::spawn(Arc::clone(&self.cache)) {
+                Ok(worker) => self.hash_worker = Some(worker),
+                Err(err) => {
+                    warn!("failed to start the hash worker, hashing lazily instead: {}", err);
+                    self.hash_timing = HashTiming::Lazy;
+                }
+            }
+        }
+        self
+    }
+    
+    /// `SyncPolicy::Disabled` stops hashing files as they are cached, `hash_timing` aside, so
+    /// `check_for_sync_file`, `reload_if_changed` and the like fail with `GfsError::SyncDisabled`
+    /// and `read_file_synced` is just `read_file`. files with identical content are then never
+    /// shared, see `deduplicate`. `SyncPolicy::Enabled` by default
+    pub fn sync_policy(&mut self, policy: SyncPolicy) -> &mut GemFileSystem {
+        self.sync_policy = policy;
+        self
+    }
+    
+    /// hash files on disk for every sync check. by default a file in a directory that still has
+    /// the modification time and size it had when it was cached is taken to be unchanged
+    /// without reading it, which misses changes that keep both, e.g. by tools restoring the
+    /// modification time
+    pub fn paranoid_sync(&mut self, enabled: bool) -> &mut GemFileSystem {
+        self.paranoid_sync = enabled;
+        self
+    }
+    
+    /// never cache files bigger than `bytes`. `read_file` still hands them out, but `open`
+    /// is the way to get at them without holding the whole file in memory. no limit by default
+    pub fn stream_threshold(&mut self, bytes: Option<u64>) -> &mut GemFileSystem {
+        self.stream_threshold = bytes;
+        self
+    }
+    
+    /// read files streamed from disk, by `open`, `write_to` or to hash them, `bytes` at a time.
+    /// 64 KiB by default. how big a file may be to be cached at all is up to `stream_threshold`
+    pub fn buffer_size(&mut self, bytes: usize) -> &mut GemFileSystem {
+        self.buffer_size = bytes.max(1);
+        self
+    }
+    
+    /// how reads failing with `ErrorKind::Interrupted` or `ErrorKind::WouldBlock`, e.g. of
+    /// files on network drives, are retried. 3 tries, 1 ms apart and then 2, by default
+    pub fn retry_policy(&mut self, policy: RetryPolicy) -> &mut GemFileSystem {
+        self.retry_policy = policy;
+        self
+    }
+    
+    /// give up on reading a file with `GfsError::Timeout` once it takes longer than `timeout`,
+    /// e.g. to notice a dead server or a stalled network drive. files in directories are
+    /// given up on in between two buffers, anything else once it failed, e.g. with a timeout of
+    /// its backend like `HttpBackend::timeout`. a read that never returns can't be given up on
+    /// from the thread making it, `read_file_with_timeout` reads on a thread of its own for that.
+    /// no timeout by default
+    pub fn read_timeout(&mut self, timeout: Option<Duration>) -> &mut GemFileSystem {
+        self.read_timeout = timeout;
+        self
+    }
+    
+    /// `Tracking` with nothing but the `read_timeout`
+    fn timed(&self) -> Tracking<'_> {
+        Tracking {
+            deadline: self.read_timeout.map(|timeout| (&*self.clock, self.clock.now() + timeout)),
+            ..Tracking::default()
+        }
+    }
+    
+    fn timed_out(&self, file_path: &path::Path) -> GfsError {
+        GfsError::Timeout {
+            path: file_path.to_path_buf(),
+            after: self.read_timeout.unwrap_or_default(),
+        }
+    }
+    
+    pub(crate) fn buffered(&self, file: fs::File) -> io::BufReader<Retrying<fs::File>> {
+        io::BufReader::with_capacity(self.buffer_size, self.retry_policy.reader(file))
+    }
+    
+    /// lowercase cache keys, so "Models/Chest.obj" and "models/chest.obj" share one entry.
+    /// files are then looked up on disk by the lowercased path too, so only turn this on
+    /// for assets stored with lowercase names. off by default
+    pub fn lowercase_keys(&mut self, enabled: bool) -> &mut GemFileSystem {
+        self.lowercase_keys = enabled;
+        self
+    }
+    
+    /// leave out the UTF-8 byte order mark some editors start text files with from what
+    /// `read_to_string` and `read_lines` hand out. on by default
+    pub fn strip_bom(&mut self, enabled: bool) -> &mut GemFileSystem {
+        self.strip_bom = enabled;
+        self
+    }
+    
+    /// look files up regardless of case when there is no exact match, so "Textures/Wood.PNG"
+    /// finds "textures/wood.png" on a case-sensitive filesystem, e.g. for assets authored on
+    /// Windows. the cache still tells the spellings apart, unless `lowercase_keys` is on too.
+    /// off by default
+    pub fn case_insensitive(&mut self, enabled: bool) -> &mut GemFileSystem {
+        self.case_insensitive = enabled;
+        self
+    }
+    
+    /// which symlinks under a root files may be read through, e.g. `DenyOutsideRoot` so a
+    /// link planted in a mod folder can't expose the rest of the filesystem. a file behind a
+    /// refused link is skipped like a missing one, and fails with `GfsError::OutsideRoot`
+    /// if no other root has it. `SymlinkPolicy::Follow` by default
+    pub fn symlink_policy(&mut self, policy: SymlinkPolicy) -> &mut GemFileSystem {
+        self.symlink_policy = policy;
+        self
+    }
+    
+    /// let paths like "textures://wood.png" stand for "assets/textures/wood.png" after
+    /// `alias("textures", "assets/textures")`, so code doesn't hard-code where assets live.
+    /// `dir` is relative to the roots like any path, setting an alias again replaces it
+    pub fn alias<P: AsRef<path::Path>>(&mut self, name: &str, dir: P) -> &mut GemFileSystem {
+        self.aliases.retain(|(alias, _)| alias != name);
+        self.aliases.push((String::from(name), dir.as_ref().to_path_buf()));
+        self
+    }
+    
+    /// look files addressed like "pack://bundle1/models/chest.obj" up only in the mount at
+    /// `mount`, after `route("pack://bundle1", "bundles/bundle1.gfspack")`, whatever else is
+    /// mounted. `mount` is the path it was mounted with, by `mount`, `mount_pack` or
+    /// `mount_zip`. "file://" always stands for the usual lookup through every root
+    pub fn route<P: AsRef<path::Path>>(&mut self, prefix: &str, mount: P) -> &mut GemFileSystem {
+        if let Some(prefix) = normalize(path::Path::new(prefix)) {
+            self.routes.retain(|(routed, _)| *routed != prefix);
+            self.routes.push((prefix, mount.as_ref().to_path_buf()));
+        }
+        self
+    }
+    
+    /// resolve paths to their variant for `locale`, e.g. "ui/strings.json" to the first of
+    /// "ui/strings.de-DE.json", "ui/strings.de.json" and the file itself that exists for
+    /// "de-DE". every method taking a path resolves it, so the variant is cached and synced
+    /// under its own name. resolutions are remembered until the locale, the mounts or, through
+    /// `apply_change_feed`, the files change. None turns localization off again
+    pub fn set_locale(&mut self, locale: Option<&str>) -> &mut GemFileSystem {
+        self.locale = locale.map(String::from);
+        self.forget_resolutions();
+        self
+    }
+    
+    /// how a localized variant is named, "{dir}/{stem}.{locale}{ext}" by default. `{dir}` is
+    /// the directory of the file, `{file}` its name, `{stem}` its name without extension and
+    /// `{ext}` the extension with its dot, so "{dir}/{locale}/{file}" looks for "ui/de/strings.json"
+    pub fn locale_pattern(&mut self, pattern: &str) -> &mut GemFileSystem {
+        self.locale_pattern = String::from(pattern);
+        self.forget_resolutions();
+        self
+    }
+    
+    /// resolve paths with no file of their own to the first of `extensions` a file exists
+    /// with, e.g. "textures/wood" to "textures/wood.ktx2" on Vulkan and "textures/wood.dds"
+    /// on D3D with `variant_extensions(&["ktx2", "dds"])` or `(&["dds"])`. the file picked is
+    /// what `PathMapper::map` hands out, and it is localized like any other with `set_locale`
+    pub fn variant_extensions(&mut self, extensions: &[&str]) -> &mut GemFileSystem {
+        self.variant_extensions = extensions.iter()
+            .map(|extension| String::from(extension.trim_start_matches('.')))
+            .collect();
+        self.forget_resolutions();
+        self
+    }
+    
+    /// serve files from the quality tier set with `set_quality`, falling back to lower tiers and
+    /// then the file itself where a tier doesn't have it. `tiers` go from lowest to highest, e.g.
+    /// `&["low", "medium", "high"]`, and `pattern` names a file of a tier like `locale_pattern`
+    /// with `{tier}`, e.g. "{tier}/{dir}/{file}" for tier directories or "{dir}/{stem}@{tier}{ext}"
+    /// for suffixes
+    pub fn quality_tiers(&mut self, tiers: &[&str], pattern: &str) -> &mut GemFileSystem {
+        self.quality_tiers = tiers.iter().map(|tier| String::from(*tier)).collect();
+        self.quality_pattern = String::from(pattern);
+        self.forget_resolutions();
+        self
+    }
+    
+    /// switch to one of the `quality_tiers`, e.g. when the graphics settings change. files
+    /// already read from another tier stay cached under their own name. None, or a tier that
+    /// isn't one of them, serves the files themselves
+    pub fn set_quality(&mut self, tier: Option<&str>) -> &mut GemFileSystem {
+        self.quality = tier.map(String::from);
+        self.forget_resolutions();
+        self
+    }
+    
+    fn forget_resolutions(&mut self) {
+        self.resolved.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+    }
+    
+    /// memory map files with this extension instead of copying them onto the heap, e.g. big
+    /// read-only asset packs. the cache then hands out the mapping itself, so the file must
+    /// not be modified or truncated while any of its content is held
+    #[cfg(feature = "mmap")]
+    pub fn mmap_extension(&mut self, extension: &str) -> &mut GemFileSystem {
+        self.mmap_extensions.push(String::from(extension.trim_start_matches('.')));
+        self
+    }
+    
+    /// decompress files with this extension, e.g. `level.json.zst`, whether they come from a
+    /// directory, a pack or an archive. the cache holds and hands out the decompressed content
+    /// under the name the file is stored as, and sync checks compare it against the
+    /// decompressed file. files that fail to decompress are reported as not found.
+    /// `open`, `write_to` and `read_regions` still hand out files as they are stored
+    #[cfg(any(feature = "zstd", feature = "lz4"))]
+    pub fn decompress_extension(&mut self, extension: &str, codec: Codec) -> &mut GemFileSystem {
+        let extension = String::from(extension.trim_start_matches('.'));
+        self.codecs.retain(|(registered, _)| *registered != extension);
+        self.codecs.push((extension, codec));
+        self
+    }
+    
+    /// decrypt every file `provider` has a key for, files stored as `seal` writes them. like
+    /// compressed files they are cached and synced in their decrypted form, and decrypted
+    /// before being decompressed. files that fail to decrypt are reported as not found.
+    /// `open`, `write_to` and `read_regions` still hand out files as they are stored
+    #[cfg(feature = "encryption")]
+    pub fn set_key_provider<K: KeyProvider + 'static>(&mut self, provider: K) -> &mut GemFileSystem {
+        self.key_provider = Some(Box::new(provider));
+        self
+    }
+    
+    /// see `Cache::set_hasher`
+    pub fn set_hasher<H: ContentHasher + 'static>(&mut self, hasher: H) -> &mut GemFileSystem {
+        self.cache_mut().set_hasher(hasher);
+        self
+    }
+    
+    /// replace the clock used for time-based behavior such as `min_reload_interval`
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) -> &mut GemFileSystem {
+        self.clock = Box::new(clock);
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.clock_set = true;
+        }
+        self
+    }
+    
+    /// never let `reload_if_changed` reload the same file more than once per `interval`,
+    /// so a tool saving a file over and over doesn't stall the app. no limit by default
+    pub fn min_reload_interval(&mut self, interval: Duration) -> &mut GemFileSystem {
+        self.min_reload_interval = interval;
+        self
+    }
+    
+    /// how long a file has to stay unchanged before its `on_change` callbacks run, as editors
+    /// tend to write a file several times when saving it. 100ms by default
+    pub fn change_debounce(&mut self, debounce: Duration) -> &mut GemFileSystem {
+        self.change_debounce = debounce;
+        self
+    }
+    
+    /// search `dir` for files as well, e.g. `dlc1/` or `mods/` next to the base game in
+    /// `root`. mounts are searched from the highest priority down, `root` counting as
+    /// priority 0, and a mount shadows every mount of the same priority added before it,
+    /// `root` included. fallback roots come after all of them
+    pub fn mount<P: AsRef<path::Path>>(&mut self, dir: P, priority: i32) -> &mut GemFileSystem {
+        self.add_mount(Mount::Dir(dir.as_ref().to_path_buf()), priority);
+        self
+    }
+    
+    /// mount the files inside a `.zip` archive as if it were a directory, see `mount`.
+    /// entries are decompressed into the cache like any file read from disk, and sync checks
+    /// compare the CRC-32 stored in the archive. `open`, `write_to`, `read_regions` and `map`
+    /// only ever look at plain files
+    #[cfg(feature = "zip")]
+    pub fn mount_zip<P: AsRef<path::Path>>(&mut self, archive: P, priority: i32)
+        -> Result<&mut GemFileSystem, GfsError> {
+        let archive = ZipMount::open(archive.as_ref())?;
+        self.add_mount(Mount::Zip(archive), priority);
+        Ok(self)
+    }
+    
+    /// mount a pack built with `write_pack` or the `gfs-pack` tool, see `mount`. its index is
+    /// read once up front, so looking files up costs no syscalls and reading one costs a seek
+    /// and a read. sync checks use the hashes in the index when they were computed with the
+    /// same digest as the cache. as with `mount_zip`, `open` and the like only see plain files
+    pub fn mount_pack<P: AsRef<path::Path>>(&mut self, pack: P, priority: i32)
+        -> Result<&mut GemFileSystem, GfsError> {
+        let pack = PackMount::open(pack.as_ref())?;
+        self.add_mount(Mount::Pack(pack), priority);
+        Ok(self)
+    }
+    
+    /// mount `dir` like `mount`, but only ever serve files from it that are listed with the
+    /// right hash in `manifest`, a file written by `Manifest::sign` with the secret key
+    /// belonging to `public_key`. anything else, e.g. a file tampered with after the build
+    /// shipped, is refused with `GfsError::HashMismatch`. fails with
+    /// `GfsError::InvalidSignature` unless the manifest is signed with that key.
+    /// `open`, `write_to` and `read_regions` don't check anything
+    #[cfg(feature = "signing")]
+    pub fn mount_verified<P, M>(&mut self, dir: P, manifest: M, public_key: &[u8; 32], priority: i32)
+        -> Result<&mut GemFileSystem, GfsError>
+        where P: AsRef<path::Path>,
+              M: AsRef<path::Path> {
+        let signed = fs::read_to_string(manifest.as_ref())?;
+        let manifest = Manifest::verify(&signed, public_key, manifest.as_ref())?;
+        self.verified.insert(dir.as_ref().to_path_buf(), manifest);
+        Ok(self.mount(dir, priority))
+    }
+    
+    /// a file system serving files from `backend` first, e.g. an `AndroidBackend`, with `root`
+    /// for files it doesn't have and for writes. the backend is mounted under the name ""
+    pub fn with_backend<P, B>(root: P, backend: B) -> GemFileSystem
+        where P: AsRef<path::Path>,
+              B: FileBackend + 'static {
+        let mut gfs = GemFileSystem::new(root);
+        gfs.mount_backend("", backend, 0);
+        gfs
+    }
+    
+    /// mount a `FileBackend`, see `mount`. `name` stands for the backend wherever a mount path
+    /// is expected, as in `unmount` and `route`
+    pub fn mount_backend<P, B>(&mut self, name: P, backend: B, priority: i32) -> &mut GemFileSystem
+        where P: AsRef<path::Path>,
+              B: FileBackend + 'static {
+        self.add_mount(Mount::Backend(name.as_ref().to_path_buf(), Box::new(backend)), priority);
+        self
+    }
+    
+    fn add_mount(&mut self, mount: Mount, priority: i32) {
+        let at = self.mounts.iter()
+            .position(|(_, mounted)| *mounted <= priority)
+            .unwrap_or(self.mounts.len());
+        self.mounts.insert(at, (mount, priority));
+        self.forget_resolutions();
+    }
+    
+    /// stop searching a mounted directory or archive, returning false if it wasn't mounted.
+    /// files already cached from it stay cached
+    pub fn unmount<P: AsRef<path::Path>>(&mut self, dir: P) -> bool {
+        let before = self.mounts.len();
+        self.mounts.retain(|(mounted, _)| mounted.path() != dir.as_ref());
+        #[cfg(feature = "signing")]
+        self.verified.remove(dir.as_ref());
+        self.forget_resolutions();
+        self.mounts.len() != before
+    }
+    
+    /// have `read_file` hand out `content` instead of failing for a missing file with this
+    /// extension, e.g. a magenta checker for ".png", to keep running with incomplete content.
+    /// placeholders are never cached, the file itself is read as soon as it shows up.
+    /// `prefix_placeholder` takes precedence
+    pub fn extension_placeholder<C: Into<FileContent>>(&mut self, extension: &str, content: C) -> &mut GemFileSystem {
+        self.extension_placeholders.push((String::from(extension.trim_start_matches('.')), content.into()));
+        self
+    }
+    
+    /// same as `extension_placeholder`, but for missing files under `dir`, e.g. silence for
+    /// "sounds". the placeholder of the longest matching `dir` is handed out
+    pub fn prefix_placeholder<P: AsRef<path::Path>, C: Into<FileContent>>(&mut self, dir: P, content: C)
+        -> &mut GemFileSystem {
+        let dir = self.cache_key(dir);
+        self.prefix_placeholders.push((dir, content.into()));
+        self
+    }
+    
+    /// ask `handler` for a file found in no root or mount before giving up on it, e.g. to
+    /// generate it or fetch it from another tool. what it returns is handed out by `read_file`
+    /// ahead of any placeholder and is not cached, so the handler runs on every read of a
+    /// missing file. `None` lets the read go on to the placeholder or the error
+    pub fn set_miss_handler<F>(&mut self, handler: F) -> &mut GemFileSystem
+        where F: Fn(&path::Path) -> Option<Box<[u8]>> + Send + Sync + 'static {
+        self.miss_handler = Some(Box::new(handler));
+        self
+    }
+    
+    fn placeholder(&self, file_path: &path::Path) -> Option<FileContent> {
+        let by_prefix = self.prefix_placeholders.iter()
+            .filter(|(dir, _)| file_path.starts_with(dir))
+            .max_by_key(|(dir, _)| dir.components().count())
+            .map(|(_, content)| content);
+        let by_extension = || {
+            let extension = file_path.extension()?;
+            self.extension_placeholders.iter()
+                .find(|(registered, _)| OsStr::new(registered) == extension)
+                .map(|(_, content)| content)
+        };
+        by_prefix.or_else(by_extension).cloned()
+    }
+    
+    /// add a last-resort root, e.g. a shared network cache, searched only when no mount, not
+    /// `root` and no fallback root added before has the file. with `cache` set to false, files
+    /// found there are handed out without being kept in the cache
+    pub fn add_fallback_root<P: AsRef<path::Path>>(&mut self, dir: P, cache: bool) {
+        self.fallback_roots.push((dir.as_ref().to_path_buf(), cache));
+        self.forget_resolutions();
+    }
+    
+    /// shared access to the cache. every thread loading a file waits for the guard to be dropped
+    pub fn cache(&self) -> CacheReadGuard {
+        CacheReadGuard::new(current_cache(&self.cache))
+    }
+    
+    /// exclusive access to the cache, e.g. to store files loaded elsewhere
+    pub fn cache_mut(&self) -> CacheWriteGuard {
+        CacheWriteGuard::new(current_cache(&self.cache))
+    }
+    
+    /// replace the whole cache in one step, e.g. with one built in the background from a
+    /// freshly downloaded content pack, and return the old one.
+    /// concurrent reads see either all of the old content or all of the new, never a mix.
+    /// only the pointer to the cache is swapped, so reads go on with the new cache right away,
+    /// while this waits for those still using the old one before handing it back
+    pub fn swap_cache(&self, new: Cache) -> Cache {
+        let old = {
+            let mut slot = self.cache.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+            mem::replace(&mut *slot, Arc::new(RwLock::new(new)))
+        };
+        mem::take(&mut *CacheWriteGuard::new(old))
+    }
+    
+    /// load file into self.cache and return a handle to it
+    /// if the modification time of the file changes while it is read, e.g. because another
+    /// process is still writing it, it is read again up to MAX_READ_RETRIES times. a file that
+    /// never settles is cached anyway but marked volatile, so the next `check_for_sync_file`
+    /// reports it as out of sync instead of trusting its hash
+    pub fn fetch_and_cache_file<P: AsRef<path::Path>>(&self, file_path: P) -> Option<FileContent> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        self.fetch_with(file_path, ReadOptions::default(), self.timed()).ok()
+    }
+    
+    /// read a file from disk, unless another thread is already reading it. then its
+    /// result is shared instead
+    fn fetch_with(&self, file_path: &path::Path, opts: ReadOptions, tracking: Tracking<'_>)
+        -> Result<FileContent, GfsError> {
+        let (flight, joined) = {
+            let mut in_flight = lock(&self.in_flight);
+            match in_flight.get(file_path) {
+                Some(flight) => (Arc::clone(flight), true),
+                None => {
+                    let flight = Arc::new(InFlight::default());
+                    in_flight.insert(file_path.to_path_buf(), Arc::clone(&flight));
+                    (flight, false)
+                }
+            }
+        };
+        if joined {
+            let mut content = lock(&flight.content);
+            while content.is_none() {
+                if tracking.cancelled() {
+                    return Err(GfsError::Cancelled { path: file_path.to_path_buf() });
+                }
+                let wait = match (tracking.time_left(), tracking.cancel.is_some()) {
+                    (Some(Duration::ZERO), _) => return Err(self.timed_out(file_path)),
+                    // woken now and then, to stop waiting soon after being cancelled
+                    (left, true) => Some(left.map_or(CANCEL_POLL, |left| left.min(CANCEL_POLL))),
+                    (left, false) => left,
+                };
+                content = match wait {
+                    Some(wait) => flight.landed.wait_timeout(content, wait)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .0,
+                    None => flight.landed.wait(content).unwrap_or_else(|poisoned| poisoned.into_inner()),
+                };
+            }
+            match content.clone().flatten() {
+                Some(content) => return Ok(content),
+                // read again to get an error of our own
+                None => return self.fetch_uncoalesced(file_path, opts, tracking),
+            }
+        }
+        let mut landing = Landing {
+            gfs: self,
+            file_path,
+            flight,
+            content: None,
+        };
+        let fetched = self.fetch_uncoalesced(file_path, opts, tracking);
+        landing.content = fetched.as_ref().ok().cloned();
+        fetched
+    }
+    
+    fn fetch_uncoalesced(&self, file_path: &path::Path, opts: ReadOptions, tracking: Tracking<'_>)
+        -> Result<FileContent, GfsError> {
+        let step = trace::step(Stage::Fetch, file_path);
+        let (root, cache) = match self.locate(file_path) {
+            Some(found) => found,
+            None => return Err(self.not_found_error(file_path)),
+        };
+        let hasher = match cache && opts.cache && self.hashes_on_store(opts.hash) {
+            true => Some(Arc::clone(&self.cache().hasher)),
+            false => None,
+        };
+        // read without holding the lock, so other threads keep being served from the cache
+        let (file_ptr, meta, hash) = match self.load_from(root, file_path, hasher.as_deref(), tracking) {
+            // read in full or not, it isn't wanted anymore
+            _ if tracking.cancelled() => return Err(GfsError::Cancelled { path: file_path.to_path_buf() }),
+            Ok(loaded) => loaded,
+            Err(_) if tracking.timed_out() => return Err(self.timed_out(file_path)),
+            Err(err) => {
+                error!("failed to read {}: {}", file_path.display(), err);
+                self.record_error(file_path, &err);
+                return Err(err);
+            }
+        };
+        self.cache().record_disk_read(file_ptr.len() as u64);
+        step.bytes(file_ptr.len());
+        if cache && opts.cache {
+            // the cache and the caller share the same content
+            return Ok(self.store_loaded(file_path, file_ptr, meta, opts.hash, hash));
+        }
+        Ok(file_ptr)
+    }
+    
+    /// read a file from wherever `locate` found it, decrypted and decompressed if
+    /// `set_key_provider` and `decompress_extension` say so. a plain file in a directory is
+    /// hashed with `hasher` while it is read, if there is one. `progress` follows files in
+    /// directories as they are read, anything else is reported once it is read in full
+    fn load_from(&self, root: Root<'_>, file_path: &path::Path, hasher: Option<&dyn ContentHasher>,
+                 tracking: Tracking<'_>) -> Result<(FileContent, FileMeta, Option<Vec<u8>>), GfsError> {
+        let file_path = &self.sandboxed(file_path)?;
+        let key = self.key_for(root, file_path);
+        let mut hash = None;
+        let (content, meta) = match root {
+            Root::Dir(dir) => {
+                let absolute_path = dir.join(file_path);
+                debug!("{}", absolute_path.display());
+                let copied = key.is_some() || self.decodes(file_path);
+                let hasher = hasher.filter(|_| !copied);
+                let (content, meta, read_hash) = self.load_file(&absolute_path, copied, hasher, tracking)?;
+                hash = read_hash;
+                if meta.volatile {
+                    warn!("{} kept changing while being read", absolute_path.display());
+                }
+                #[cfg(feature = "signing")]
+                self.check_signed(dir, file_path, &content)?;
+                (content, meta)
+            }
+            Root::Pack(pack) => self.retry_policy.run(|| {
+                pack.read(file_path).unwrap_or_else(|| Err(vanished(&pack.path)))
+            })?,
+            #[cfg(feature = "zip")]
+            Root::Zip(archive) => self.retry_policy.run(|| {
+                archive.read(file_path).unwrap_or_else(|| Err(vanished(&archive.path)))
+            })?,
+            Root::Backend(name, backend) => {
+                debug!("{} from {}", file_path.display(), name.display());
+                let backend_meta = backend.metadata(file_path).unwrap_or_default();
+                let meta = FileMeta {
+                    modified: backend_meta.modified,
+                    etag: backend_meta.etag,
+                    ..FileMeta::default()
+                };
+                (FileContent::from(self.retry_policy.run(|| backend.read_all(file_path))?), meta)
+            }
+        };
+        if !matches!(root, Root::Dir(_)) {
+            tracking.report(content.len() as u64, content.len() as u64);
+        }
+        #[cfg(feature = "encryption")]
+        let content = match key {
+            Some(key) => FileContent::from(crypto::open(&key, &content)?),
+            None => content,
+        };
+        Ok((self.decode(file_path, content)?, meta, hash))
+    }
+    
+    /// read a file from disk, or map it if its extension is set up with `mmap_extension`.
+    /// `copied` files are never mapped, their content is copied out to decrypt or decompress it.
+    /// read files are hashed with `hasher` in the same pass, mapped ones are left to the cache
+    #[cfg_attr(not(feature = "mmap"), allow(unused_variables))]
+    fn load_file(&self, absolute_path: &path::Path, copied: bool, hasher: Option<&dyn ContentHasher>,
+                 tracking: Tracking<'_>) -> io::Result<(FileContent, FileMeta, Option<Vec<u8>>)> {
+        #[cfg(feature = "mmap")]
+        {
+            let mapped = absolute_path.extension()
+                .map(|extension| self.mmap_extensions.iter().any(|mapped| OsStr::new(mapped) == extension));
+            if let (Some(true), false) = (mapped, copied) {
+                match map_file(absolute_path) {
+                    Ok((content, meta)) => {
+                        tracking.report(content.len() as u64, content.len() as u64);
+                        return Ok((content, meta, None));
+                    }
+                    Err(err) => warn!("failed to map {}, reading it instead: {}", absolute_path.display(), err),
+                }
+            }
+        }
+        let (loaded, meta) = load_stable(
+            || fs::metadata(absolute_path).and_then(|meta| meta.modified()).ok(),
+            || self.read_hashed(absolute_path, hasher, tracking),
+        );
+        let (content, hash) = loaded?;
+        Ok((FileContent::from(content), meta, hash))
+    }
+    
+    /// read a file, hashing it with `hasher` as it comes in instead of hashing the whole
+    /// of it afterwards
+    fn read_hashed(&self, absolute_path: &path::Path, hasher: Option<&dyn ContentHasher>,
+                   tracking: Tracking<'_>) -> io::Result<Hashed> {
+        let file = fs::File::open(absolute_path)?;
+        let len = file.metadata().map_or(0, |meta| meta.len());
+        let mut content = Vec::with_capacity(len as usize);
+        let hash = {
+            let mut tee = Tee::new(Reported::new(self.buffered(file), len, tracking), &mut content);
+            let hash = hasher.map(|hasher| {
+                let step = trace::step(Stage::Hash, absolute_path);
+                step.bytes(len as usize);
+                hasher.hash(&mut tee)
+            }).transpose()?;
+            // whatever the hasher left unread still belongs to the content
+            io::copy(&mut tee, &mut io::sink())?;
+            hash
+        };
+        Ok((content.into_boxed_slice(), hash))
+    }
+    
+    /// refuse a file of a `mount_verified` directory that doesn't match its signed manifest
+    #[cfg(feature = "signing")]
+    fn check_signed(&self, dir: &path::Path, file_path: &path::Path, content: &[u8]) -> Result<(), GfsError> {
+        let manifest = match self.verified.get(dir) {
+            Some(manifest) => manifest,
+            None => return Ok(()),
+        };
+        let matches = match manifest.entries.get(file_path) {
+            Some(entry) => {
+                entry.size == content.len() as u64 && Sha256Hasher.hash_bytes(content)[..] == entry.sha256[..]
+            }
+            // a file slipped into the build counts as tampered with as well
+            None => false,
+        };
+        match matches {
+            true => Ok(()),
+            false => Err(GfsError::HashMismatch { path: file_path.to_path_buf() }),
+        }
+    }
+    
+    /// the key `set_key_provider` has for a file found under `root`, if it is encrypted
+    #[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+    fn key_for(&self, root: Root<'_>, file_path: &path::Path) -> Option<[u8; 32]> {
+        #[cfg(feature = "encryption")]
+        {
+            if let Some(provider) = &self.key_provider {
+                let mount = match root {
+                    Root::Dir(dir) => dir,
+                    Root::Pack(pack) => &pack.path,
+                    #[cfg(feature = "zip")]
+                    Root::Zip(archive) => &archive.path,
+                    Root::Backend(name, _) => name,
+                };
+                return provider.key(file_path, mount);
+            }
+        }
+        None
+    }
+    
+    /// whether `decompress_extension` was set up for the extension of `file_path`
+    #[cfg_attr(not(any(feature = "zstd", feature = "lz4")), allow(unused_variables))]
+    fn decodes(&self, file_path: &path::Path) -> bool {
+        #[cfg(any(feature = "zstd", feature = "lz4"))]
+        {
+            if let Some(extension) = file_path.extension() {
+                return self.codecs.iter().any(|(registered, _)| OsStr::new(registered) == extension);
+            }
+        }
+        false
+    }
+    
+    #[cfg_attr(not(any(feature = "zstd", feature = "lz4")), allow(unused_variables))]
+    fn decode(&self, file_path: &path::Path, content: FileContent) -> io::Result<FileContent> {
+        #[cfg(any(feature = "zstd", feature = "lz4"))]
+        {
+            let codec = file_path.extension()
+                .and_then(|extension| self.codecs.iter().find(|(registered, _)| OsStr::new(registered) == extension));
+            if let Some((_, codec)) = codec {
+                let step = trace::step(Stage::Decompress, file_path);
+                let decoded = codec.decompress(&content)?;
+                step.bytes(decoded.len());
+                return Ok(FileContent::from(decoded));
+            }
+        }
+        Ok(content)
+    }
+    
+    /// whether content read from disk is hashed before it is stored, as `store_loaded` does
+    fn hashes_on_store(&self, mode: HashMode) -> bool {
+        match (mode, self.hash_timing, &self.hash_worker) {
+            _ if self.sync_policy == SyncPolicy::Disabled => false,
+            (HashMode::Skip, _, _) | (HashMode::Default, HashTiming::Lazy, _) => false,
+            (HashMode::Default, HashTiming::Background, Some(_)) => false,
+            _ => true,
+        }
+    }
+    
+    /// store content freshly read from disk, hashing it when `hash_timing` says so
+    /// unless `mode` overrides it, or taking `hash` if it was hashed while it was read.
+    /// returns the content to hand out, which is the copy shared with identical files
+    /// if the cache deduplicates
+    fn store_loaded(&self, file_path: &path::Path, content: FileContent, meta: FileMeta, mode: HashMode,
+                    hash: Option<Vec<u8>>) -> FileContent {
+        if let Some(threshold) = self.stream_threshold {
+            if content.len() as u64 > threshold {
+                debug!("not caching {}, it is bigger than the stream threshold", file_path.display());
+                return content;
+            }
+        }
+        let step = trace::step(Stage::Cache, file_path);
+        step.bytes(content.len());
+        let key = file_path.to_path_buf();
+        let mut cache = self.cache_mut();
+        match (self.sync_policy, mode, self.hash_timing, &self.hash_worker) {
+            (SyncPolicy::Disabled, _, _, _) | (_, HashMode::Skip, _, _) | (_, HashMode::Default, HashTiming::Lazy, _) => {
+                cache.store_file_unhashed(key.clone(), content.clone(), meta)
+            }
+            (_, HashMode::Default, HashTiming::Background, Some(worker)) => {
+                cache.store_file_unhashed(key.clone(), content.clone(), meta);
+                worker.queue(key.clone());
+            }
+            _ => match hash {
+                Some(hash) => cache.store_file_with_hash(key.clone(), content.clone(), meta, hash),
+                None => cache.store_file_with_meta(key.clone(), content.clone(), meta),
+            },
+        }
+        cache.kept_content(&key).unwrap_or(content)
+    }
+    
+    /// same as `read_file`, but lets a single call decide whether to hash and whether to cache.
+    /// with `cache: false` a file that is not cached yet is read from disk and handed out
+    /// without being kept
+    pub fn read_file_opts<P: AsRef<path::Path>>(&self, file_path: P, opts: ReadOptions)
+        -> Result<FileContent, GfsError> {
+        self.read_reported(file_path.as_ref(), opts, Tracking::default())
+    }
+    
+    /// same as `read_file`, but `progress` is told how many bytes are read and how many there
+    /// are in all as the file comes in, e.g. for the bar of a loading screen. files in directories
+    /// are followed buffer by buffer, anything else, like files in packs or on servers, is
+    /// reported once it is read in full. cached files are reported done right away
+    pub fn read_file_with_progress<P, F>(&self, file_path: P, progress: F) -> Result<FileContent, GfsError>
+        where P: AsRef<path::Path>,
+              F: FnMut(u64, u64) {
+        let progress = RefCell::new(progress);
+        let report = |done, total| (progress.borrow_mut())(done, total);
+        let tracking = Tracking {
+            progress: Some(&report),
+            ..Tracking::default()
+        };
+        self.read_reported(file_path.as_ref(), ReadOptions::default(), tracking)
+    }
+    
+    /// same as `read_file`, but gives up with `GfsError::Cancelled` once `cancel` is cancelled,
+    /// before the file is read or in between two buffers of it. what was read so far is
+    /// dropped, nothing is cached
+    pub fn read_file_cancellable<P: AsRef<path::Path>>(&self, file_path: P, cancel: &CancellationToken)
+        -> Result<FileContent, GfsError> {
+        let tracking = Tracking {
+            cancel: Some(cancel),
+            ..Tracking::default()
+        };
+        self.read_reported(file_path.as_ref(), ReadOptions::default(), tracking)
+    }
+    
+    /// same as `read_file`, but read on a thread of its own, so the calling thread gets
+    /// `GfsError::Timeout` after `timeout` even if reading hangs for good, e.g. on a network
+    /// drive that stopped answering. the thread is left to finish, the file is cached if it does
+    pub fn read_file_with_timeout<P: AsRef<path::Path>>(self: &Arc<GemFileSystem>, file_path: P, timeout: Duration)
+        -> Result<FileContent, GfsError> {
+        let file_path = self.cache_key(file_path);
+        if let Some(content) = self.cached_content(&file_path) {
+            self.cache().record_hit();
+            return Ok(content);
+        }
+        let (done, result) = mpsc::channel();
+        let gfs = Arc::clone(self);
+        let read_path = file_path.clone();
+        thread::Builder::new()
+            .name(String::from("gfs-read"))
+            .spawn(move || {
+                // nobody is waiting anymore if this fails
+                let _ = done.send(gfs.read_file(&read_path));
+            })?;
+        match result.recv_timeout(timeout) {
+            Ok(read) => read,
+            Err(_) => Err(GfsError::Timeout {
+                path: file_path,
+                after: timeout,
+            }),
+        }
+    }
+    
+    fn read_reported(&self, file_path: &path::Path, opts: ReadOptions, tracking: Tracking<'_>)
+        -> Result<FileContent, GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        if tracking.cancelled() {
+            return Err(GfsError::Cancelled { path: file_path.to_path_buf() });
+        }
+        let tracking = Tracking {
+            deadline: self.timed().deadline,
+            ..tracking
+        };
+        let if_file_in_cache = self.cache().content_map.contains_key(file_path);
+        let content = match if_file_in_cache {
+            false => {
+                self.cache().record_miss();
+                if let Some(content) = self.cache_mut().unspill(file_path) {
+                    return Ok(content);
+                }
+                let found_on_disk = self.locate(file_path).is_some();
+                self.log_miss(file_path, found_on_disk);
+                if !found_on_disk {
+                    // if reach here, it means it cannot find the file both in cache or in disk
+                    let err = self.not_found_error(file_path);
+                    if let GfsError::NotFound { .. } | GfsError::BrokenSymlink { .. } = err {
+                        let handled = self.miss_handler.as_ref().and_then(|handler| handler(file_path));
+                        if let Some(content) = handled {
+                            debug!("{} is missing, handing out what the miss handler made", file_path.display());
+                            return Ok(FileContent::from(content));
+                        }
+                        if let Some(placeholder) = self.placeholder(file_path) {
+                            debug!("{} is missing, handing out its placeholder", file_path.display());
+                            return Ok(placeholder);
+                        }
+                    }
+                    return Err(err);
+                }
+                return self.fetch_with(file_path, opts, tracking);
+            }
+            true => {
+                self.cache().record_hit();
+                if opts.hash == HashMode::Force && self.sync_policy == SyncPolicy::Enabled {
+                    self.cache_mut().rehash(file_path);
+                }
+                self.cached_content(file_path)
+            }
+        };
+        match content {
+            Some(content) => {
+                tracking.report(content.len() as u64, content.len() as u64);
+                Ok(content)
+            }
+            // the file vanished between looking it up and reading it
+            None => Err(GfsError::NotFound { path: file_path.to_path_buf() }),
+        }
+    }
+    
+    /// read a file from disk even if it is cached, replacing the cached content.
+    /// `read_file` serves cached files from memory, this is for when re-reading is intended
+    pub fn force_refresh<P: AsRef<path::Path>>(&self, file_path: P) -> Result<FileContent, GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        self.fetch_with(file_path, ReadOptions::default(), self.timed())
+    }
+    
+    /// same as `read_file`, but a cached file is first checked against the disk and read
+    /// again if it changed, so the content handed out is always current
+    pub fn read_file_synced<P: AsRef<path::Path>>(&self, file_path: P) -> Result<FileContent, GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        if self.sync_policy == SyncPolicy::Disabled || !self.cache().content_map.contains_key(file_path) {
+            return self.read_file(file_path);
+        }
+        let content = match self.check_for_sync_file(file_path)? {
+            FileSyncState::HashMatch => self.cached_content(file_path),
+            FileSyncState::HashUnmatch => {
+                debug!("resyncing {}", file_path.display());
+                return self.fetch_with(file_path, ReadOptions::default(), self.timed());
+            }
+            FileSyncState::MissingOnDisk => None,
+        };
+        match content {
+            Some(content) => Ok(content),
+            None => Err(self.not_found_error(file_path)),
+        }
+    }
+    
+    /// bring a file to its next version with a `Patch` made from what is on disk now, and save
+    /// the result like `write_file` does. a file that doesn't match what the patch was made
+    /// from, e.g. one that was patched already, is left as it is with `GfsError::HashMismatch`
+    pub fn apply_patch<P: AsRef<path::Path>>(&self, file_path: P, patch: &Patch) -> Result<(), GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let old = self.read_file_synced(file_path)?;
+        let hasher = Arc::clone(&self.cache().hasher);
+        if !patch.applies_to(&old, &*hasher)? {
+            return Err(GfsError::HashMismatch {
+                path: file_path.to_path_buf(),
+            });
+        }
+        let new = patch.apply(&old, &*hasher)?;
+        self.write_file(file_path, &new)
+    }
+    
+    /// parse files into `T` with `loader` for `load`, replacing the loader registered
+    /// for `T` before along with everything it parsed
+    pub fn register_loader<T, L>(&mut self, loader: L) -> &mut GemFileSystem
+        where T: Send + Sync + 'static,
+              L: AssetLoader<T> + 'static {
+        lock(&self.assets).register(loader);
+        self
+    }
+    
+    /// a file parsed into `T` by the loader registered for it, e.g.
+    /// `gfs.load::<Texture>("textures/wood.png")`. the parsed asset is handed out again for
+    /// as long as the file stays cached with the same content, and parsed anew once it
+    /// is reloaded with different content
+    pub fn load<T: Send + Sync + 'static>(&self, file_path: impl AsRef<path::Path>) -> Result<Arc<T>, GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let no_loader = || GfsError::NoLoader {
+            path: file_path.to_path_buf(),
+            asset_type: String::from(std::any::type_name::<T>()),
+        };
+        let loader = lock(&self.assets).loader(TypeId::of::<T>()).ok_or_else(no_loader)?;
+        let asset = self.load_with(file_path, TypeId::of::<T>(), loader)?;
+        asset.downcast::<T>().map_err(|_| no_loader())
+    }
+    
+    /// same as `load`, but as a `Handle` that keeps the asset loaded for as long as it or any
+    /// of its clones is around. assets without a handle are unloaded by `unload_unused`,
+    /// which every call of this makes first
+    pub fn acquire<T: Send + Sync + 'static>(&self, file_path: impl AsRef<path::Path>) -> Result<Handle<T>, GfsError> {
+        self.unload_unused();
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let asset = self.load::<T>(file_path)?;
+        Ok(lock(&self.assets).handle(file_path, TypeId::of::<T>(), asset))
+    }
+    
+    /// another handle to the asset `id` was handed out for, None once it was unloaded
+    pub fn handle<T: Send + Sync + 'static>(&self, id: HandleId) -> Option<Handle<T>> {
+        let (file_path, live) = lock(&self.assets).handle_of(id)?;
+        let asset = self.load::<T>(&file_path).ok()?;
+        // keeps the asset from being unloaded while it is looked up
+        drop(live);
+        Some(lock(&self.assets).handle(&file_path, TypeId::of::<T>(), asset))
+    }
+    
+    /// how long an asset stays loaded after its last handle is dropped, 0 by default. the
+    /// time starts with the first `unload_unused` to find it unused. on
+    /// `wasm32-unknown-unknown` it takes a clock from `set_clock`, without one assets are
+    /// unloaded as soon as they are found unused
+    pub fn unload_grace(&mut self, grace: Duration) -> &mut GemFileSystem {
+        self.unload_grace = grace;
+        self
+    }
+    
+    /// evict the files every handle from `acquire` is gone for, along with every asset parsed
+    /// from them, returning how many files were unloaded
+    pub fn unload_unused(&self) -> usize {
+        #[cfg(target_arch = "wasm32")]
+        let now = match self.clock_set {
+            true => Some(self.clock.now()),
+            false => None,
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let now = Some(self.clock.now());
+        let unused = lock(&self.assets).unused(now, self.unload_grace);
+        for file_path in &unused {
+            debug!("unloading {}, no handle to it is left", file_path.display());
+            self.evict(file_path);
+        }
+        unused.len()
+    }
+    
+    /// have `load_any` load files with this extension, e.g. ".obj", as `T`, with the loader
+    /// registered for `T`
+    pub fn extension_loader<T: 'static>(&mut self, extension: &str) -> &mut GemFileSystem {
+        lock(&self.assets).map_extension::<T>(extension);
+        self
+    }
+    
+    /// a file parsed by the loader for its extension, see `extension_loader`, as an asset
+    /// of whichever type that parses it into. cached just like the assets `load` hands out
+    pub fn load_any(&self, file_path: impl AsRef<path::Path>) -> Result<ErasedAsset, GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let no_loader = |asset_type: String| GfsError::NoLoader {
+            path: file_path.to_path_buf(),
+            asset_type,
+        };
+        let (type_id, type_name, loader) = {
+            let assets = lock(&self.assets);
+            let (type_id, type_name) = match assets.type_for(file_path) {
+                Some(found) => found,
+                None => {
+                    let extension = file_path.extension().unwrap_or_default().to_string_lossy();
+                    return Err(no_loader(format!(".{} files", extension)));
+                }
+            };
+            match assets.loader(type_id) {
+                Some(loader) => (type_id, type_name, loader),
+                None => return Err(no_loader(String::from(type_name))),
+            }
+        };
+        Ok(ErasedAsset {
+            asset: self.load_with(file_path, type_id, loader)?,
+            type_name,
+        })
+    }
+    
+    fn load_with(&self, file_path: &path::Path, type_id: TypeId, loader: ErasedLoader) -> Result<AnyAsset, GfsError> {
+        let content = self.read_file(file_path)?;
+        // files `read_file` hands out without caching them are parsed on every load
+        let token = self.cached_version(file_path, &content);
+        if let Some(asset) = token.and_then(|token| lock(&self.assets).loaded(file_path, type_id, token)) {
+            return Ok(asset);
+        }
+        // parsed without holding the lock, a loader may well load other assets
+        let (asset, dependencies) = loader(file_path, &content)?;
+        let dependencies = dependencies.into_iter().map(|dependency| self.cache_key(dependency)).collect();
+        let mut assets = lock(&self.assets);
+        if let Some(token) = token {
+            assets.store(file_path, type_id, Arc::clone(&asset), token);
+        }
+        assets.set_dependencies(file_path, dependencies);
+        Ok(asset)
+    }
+    
+    /// the version of the cached content of `file_path`, if `content` is what is cached. None
+    /// for a file read without being cached, or evicted or reloaded since it was read
+    fn cached_version(&self, file_path: &path::Path, content: &FileContent) -> Option<ChangeToken> {
+        let cache = self.cache();
+        let meta = cache.meta_map.get(file_path)?;
+        let cached = match cache.kept_content(file_path) {
+            Some(kept) => FileContent::ptr_eq(&kept, content),
+            // compressed entries are decompressed into a new copy on every read
+            None => cache.raw_content(file_path).is_some_and(|raw| raw == *content),
+        };
+        match cached {
+            true => Some(ChangeToken(meta.version)),
+            false => None,
+        }
+    }
+    
+    /// record that `file_path` depends on `dependency`, besides what its loaders declare
+    /// with `AssetLoader::dependencies`, e.g. a shader on the files it includes
+    pub fn add_dependency<P: AsRef<path::Path>, Q: AsRef<path::Path>>(&self, file_path: P, dependency: Q) {
+        lock(&self.assets).add_dependencies(&self.cache_key(file_path), Some(self.cache_key(dependency)));
+    }
+    
+    /// the files `file_path` directly depends on
+    pub fn dependencies<P: AsRef<path::Path>>(&self, file_path: P) -> Vec<PathBuf> {
+        lock(&self.assets).dependencies(&self.cache_key(file_path))
+    }
+    
+    /// every file depending on `file_path`, directly or through other files
+    pub fn dependents<P: AsRef<path::Path>>(&self, file_path: P) -> Vec<PathBuf> {
+        lock(&self.assets).dependents(&self.cache_key(file_path))
+    }
+    
+    /// what `evict` does about the files depending on the one evicted,
+    /// `EvictDependents::Warn` by default
+    pub fn evict_dependents(&mut self, policy: EvictDependents) -> &mut GemFileSystem {
+        self.evict_dependents = policy;
+        self
+    }
+    
+    /// have every asset depending on a file that changed parsed again on its next load
+    fn invalidate_dependents(&self, file_path: &path::Path) {
+        let mut assets = lock(&self.assets);
+        for dependent in assets.dependents(file_path) {
+            debug!("{} changed, {} is parsed again", file_path.display(), dependent.display());
+            assets.forget_parsed(&dependent);
+        }
+    }
+    
+    /// same as `read_file`, but also returns a token to later ask `has_changed` whether the
+    /// file was reloaded with different content since, without hashing anything
+    pub fn read_with_token<P: AsRef<path::Path>>(&self, file_path: P)
+        -> Result<(FileContent, ChangeToken), GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let content = self.read_file(file_path)?;
+        match self.cached_version(file_path, &content) {
+            Some(token) => Ok((content, token)),
+            None => Err(GfsError::NotCached { path: file_path.to_path_buf() }),
+        }
+    }
+    
+    /// true if the cached content of `file_path` differs from the one `token` was handed out
+    /// with, or if the file is no longer cached at all
+    pub fn has_changed<P: AsRef<path::Path>>(&self, file_path: P, token: ChangeToken) -> bool {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        match self.cache().meta_map.get(file_path) {
+            Some(meta) => meta.version != token.0,
+            None => true,
+        }
+    }
+    
+    /// write a file into `sink`, returning how many bytes were written. cached files are
+    /// written from the cache, anything else is streamed from disk without being cached
+    pub fn write_to<P: AsRef<path::Path>, W: Write>(&self, file_path: P, sink: &mut W)
+        -> Result<u64, GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        if let Some(content) = self.cached_content(file_path) {
+            self.cache().record_hit();
+            sink.write_all(&content)?;
+            return Ok(content.len() as u64);
+        }
+        self.cache().record_miss();
+        match self.resolve(file_path) {
+            Some((absolute_path, _)) => {
+                debug!("{}", absolute_path.display());
+                let copied = io::copy(&mut self.buffered(fs::File::open(absolute_path)?), sink)?;
+                self.cache().record_disk_read(copied);
+                Ok(copied)
+            }
+            None => Err(self.not_found_error(file_path)),
+        }
+    }
+    
+    /// same as `read_file`, but returns a copy of the content aligned to `align` bytes.
+    /// the unaligned content stays in the cache as usual
+    pub fn read_aligned<P: AsRef<path::Path>>(&self, file_path: P, align: usize)
+        -> Result<AlignedBytes, GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let content = self.read_file(file_path)?;
+        Ok(AlignedBytes::copy_from_slice(&content, align)?)
+    }
+    
+    /// same as `read_file`, but for a text file, e.g. a config or a shader. fails with
+    /// `GfsError::InvalidUtf8` at the first byte that isn't UTF-8, see `strip_bom`
+    pub fn read_to_string<P: AsRef<path::Path>>(&self, file_path: P) -> Result<String, GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let content = self.read_file(file_path)?;
+        let bytes = match self.strip_bom {
+            true => content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&content),
+            false => &content,
+        };
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Ok(String::from(text)),
+            Err(err) => Err(GfsError::InvalidUtf8 {
+                path: file_path.to_path_buf(),
+                // counted from the start of the file, byte order mark included
+                offset: content.len() - bytes.len() + err.valid_up_to(),
+            }),
+        }
+    }
+    
+    /// the lines of a text file, without their `\n` or `\r\n`, see `read_to_string`
+    pub fn read_lines<P: AsRef<path::Path>>(&self, file_path: P) -> Result<Vec<String>, GfsError> {
+        Ok(self.read_to_string(file_path)?.lines().map(String::from).collect())
+    }
+    
+    /// deserialize a JSON file. the file is cached as it is, like by `read_file`, and every
+    /// call deserializes it again
+    #[cfg(feature = "json")]
+    pub fn read_json<T, P>(&self, file_path: P) -> Result<T, GfsError>
+        where T: serde::de::DeserializeOwned,
+              P: AsRef<path::Path> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let text = self.read_to_string(file_path)?;
+        serde_json::from_str(&text).map_err(|err| parse_error(file_path, "JSON", err))
+    }
+    
+    /// same as `read_json`, for TOML
+    #[cfg(feature = "toml")]
+    pub fn read_toml<T, P>(&self, file_path: P) -> Result<T, GfsError>
+        where T: serde::de::DeserializeOwned,
+              P: AsRef<path::Path> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let text = self.read_to_string(file_path)?;
+        toml::from_str(&text).map_err(|err| parse_error(file_path, "TOML", err))
+    }
+    
+    /// same as `read_json`, for RON
+    #[cfg(feature = "ron")]
+    pub fn read_ron<T, P>(&self, file_path: P) -> Result<T, GfsError>
+        where T: serde::de::DeserializeOwned,
+              P: AsRef<path::Path> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let text = self.read_to_string(file_path)?;
+        ron::from_str(&text).map_err(|err| parse_error(file_path, "RON", err))
+    }
+    
+    /// reload a cached file if it changed on disk, returning whether it was reloaded.
+    /// a change within `min_reload_interval` of the last reload of the same file is left
+    /// for a later call, once the interval has passed
+    pub fn reload_if_changed<P: AsRef<path::Path>>(&self, file_path: P) -> Result<bool, GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        if let FileSyncState::HashMatch = self.check_for_sync_file(file_path)? {
+            return Ok(false);
+        }
+        let now = self.clock.now();
+        // held until the reload is done, so two threads never reload the same change
+        let mut last_reload = lock(&self.last_reload);
+        if let Some(last_reload) = last_reload.get(file_path) {
+            if now.duration_since(*last_reload) < self.min_reload_interval {
+                debug!("deferring reload of {}", file_path.display());
+                return Ok(false);
+            }
+        }
+        self.fetch_with(file_path, ReadOptions::default(), self.timed())?;
+        last_reload.insert(file_path.to_path_buf(), now);
+        self.invalidate_dependents(file_path);
+        Ok(true)
+    }
+    
+    /// invalidate every cached path listed in `changed`, e.g. relative paths pushed by a
+    /// remote asset server. paths which are not cached are ignored.
+    /// returns how many cache entries were invalidated
+    pub fn apply_change_feed<I, P>(&self, changed: I) -> usize
+        where I: IntoIterator<Item=P>,
+              P: AsRef<path::Path> {
+        let mut invalidated = 0;
+        // a new or deleted file may be a variant other files resolve to
+        lock(&self.resolved).clear();
+        for file_path in changed {
+            let file_path = self.cache_key(file_path);
+            if !lock(&self.change_callbacks).is_empty() {
+                lock(&self.pending_changes).insert(file_path.clone(), self.clock.now());
+            }
+            if self.cache_mut().remove_file(&file_path).is_none() {
+                continue;
+            }
+            invalidated += 1;
+            self.invalidate_dependents(&file_path);
+            if self.reload_on_change {
+                // a file deleted on the other end simply stays out of the cache
+                self.fetch_and_cache_file(&file_path);
+            }
+        }
+        invalidated
+    }
+    
+    /// call `callback` with the new content of every file matching the glob `pattern`, e.g.
+    /// "shaders/**/*.glsl" or just "config.toml", once it changed and then stayed unchanged
+    /// for `change_debounce`. changes come from `watch` or `apply_change_feed`
+    pub fn on_change<F>(&self, pattern: &str, callback: F)
+        where F: Fn(&path::Path, &FileContent) + Send + Sync + 'static {
+        lock(&self.change_callbacks).push((String::from(pattern), Arc::new(callback)));
+    }
+    
+    /// run the `on_change` callbacks of the files that changed at least `change_debounce` ago,
+    /// each with the content read back from disk, returning how many callbacks ran. the
+    /// watcher of `watch` does so by itself, anyone feeding `apply_change_feed` calls this
+    /// e.g. once per frame. files that can't be read back are skipped
+    pub fn dispatch_changes(&self) -> usize {
+        let now = self.clock.now();
+        let settled: Vec<PathBuf> = {
+            let mut pending = lock(&self.pending_changes);
+            let settled: Vec<PathBuf> = pending.iter()
+                .filter(|(_, changed)| now.saturating_duration_since(**changed) >= self.change_debounce)
+                .map(|(file_path, _)| file_path.clone())
+                .collect();
+            for file_path in &settled {
+                pending.remove(file_path);
+            }
+            settled
+        };
+        // called without holding the lock, a callback may well register another one
+        let callbacks = lock(&self.change_callbacks).clone();
+        let mut ran = 0;
+        for file_path in settled {
+            let key: Vec<_> = file_path.components().map(|component| component.as_os_str().to_string_lossy()).collect();
+            let key = key.join("/");
+            let matching: Vec<_> = callbacks.iter().filter(|(pattern, _)| glob::matches(pattern, &key)).collect();
+            if matching.is_empty() {
+                continue;
+            }
+            let content = match self.read_file(&file_path) {
+                Ok(content) => content,
+                Err(err) => {
+                    debug!("{} changed, but can't be read back: {}", file_path.display(), err);
+                    continue;
+                }
+            };
+            for (_, callback) in matching {
+                callback(&file_path, &content);
+                ran += 1;
+            }
+        }
+        ran
+    }
+    
+    /// drop a single file from the cache, returning how many bytes that freed, 0 if it
+    /// wasn't cached. unlike `apply_change_feed` it is never read back. the files depending
+    /// on it are dealt with as `evict_dependents` says
+    pub fn evict<P: AsRef<path::Path>>(&self, file_path: P) -> usize {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let dependents = lock(&self.assets).dependents(file_path);
+        let mut freed = 0;
+        match self.evict_dependents {
+            EvictDependents::Ignore => {}
+            EvictDependents::Warn => {
+                let cached = self.cache();
+                let cached: Vec<_> = dependents.iter().filter(|dependent| cached.content_map.contains_key(*dependent)).collect();
+                if !cached.is_empty() {
+                    warn!("evicting {}, which {:?} still depend on", file_path.display(), cached);
+                }
+            }
+            EvictDependents::Cascade => {
+                for dependent in &dependents {
+                    lock(&self.assets).forget_prefix(dependent);
+                    freed += self.cache_mut().remove_file(dependent).map_or(0, |content| content.len());
+                }
+            }
+        }
+        lock(&self.assets).forget_prefix(file_path);
+        freed + match self.cache_mut().remove_file(file_path) {
+            Some(content) => content.len(),
+            None => 0,
+        }
+    }
+    
+    /// drop every cached file under `dir`, e.g. the assets of a level that was unloaded,
+    /// returning how many bytes that freed
+    pub fn invalidate_prefix<P: AsRef<path::Path>>(&self, dir: P) -> usize {
+        let dir: &path::Path = &self.cache_key(dir);
+        lock(&self.assets).forget_prefix(dir);
+        let mut cache = self.cache_mut();
+        let under_dir: Vec<PathBuf> = cache.content_map.keys()
+            .filter(|key| key.starts_with(dir))
+            .cloned()
+            .collect();
+        under_dir.iter()
+            .filter_map(|key| cache.remove_file(key))
+            .map(|content| content.len())
+            .sum()
+    }
+    
+    /// drop everything from the cache, returning how many bytes that freed.
+    /// settings such as the budget are kept
+    pub fn clear(&self) -> usize {
+        self.invalidate_prefix("")
+    }
+    
+    /// read only the given `(offset, len)` regions of a file from the root that has it, e.g.
+    /// the live parts of a padded asset, without loading the rest or touching the cache.
+    /// a region running past the end of the file is an error
+    pub fn read_regions<P: AsRef<path::Path>>(&self, file_path: P, regions: &[(u64, usize)])
+        -> Result<Vec<Box<[u8]>>, GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let result = match self.locate(file_path) {
+            Some((root, _)) => self.regions_from(root, file_path, regions),
+            None => return Err(self.not_found_error(file_path)),
+        };
+        match result {
+            Ok(ref regions) => {
+                let bytes: usize = regions.iter().map(|region| region.len()).sum();
+                self.cache().record_disk_read(bytes as u64);
+            }
+            Err(ref err) => self.record_error(file_path, err),
+        }
+        result
+    }
+    
+    /// read `regions` of a file from wherever `locate` found it. files in directories and
+    /// packs are read region by region, archives and backends can't seek so the whole file is
+    /// read to take the regions from
+    fn regions_from(&self, root: Root<'_>, file_path: &path::Path, regions: &[(u64, usize)])
+        -> Result<Vec<Box<[u8]>>, GfsError> {
+        let file_path = &self.sandboxed(file_path)?;
+        let regions_of = |content: &[u8]| -> io::Result<Vec<Box<[u8]>>> {
+            regions.iter().map(|&(offset, len)| {
+                check_region(offset, len, content.len() as u64)?;
+                Ok(Box::from(&content[offset as usize..offset as usize + len]))
+            }).collect()
+        };
+        let regions = match root {
+            Root::Dir(dir) => {
+                let absolute_path = dir.join(file_path);
+                debug!("{}", absolute_path.display());
+                fs::File::open(&absolute_path).and_then(|mut file| {
+                    let file_len = file.metadata()?.len();
+                    regions.iter().map(|&(offset, len)| {
+                        check_region(offset, len, file_len)?;
+                        let mut region = vec![0u8; len].into_boxed_slice();
+                        file.seek(SeekFrom::Start(offset))?;
+                        file.read_exact(&mut region)?;
+                        Ok(region)
+                    }).collect()
+                })
+            }
+            Root::Pack(pack) => pack.read_regions(file_path, regions)
+                .unwrap_or_else(|| Err(vanished(&pack.path))),
+            #[cfg(feature = "zip")]
+            Root::Zip(archive) => archive.read(file_path)
+                .unwrap_or_else(|| Err(vanished(&archive.path)))
+                .and_then(|(content, _)| regions_of(&content)),
+            Root::Backend(name, backend) => {
+                debug!("{} from {}", file_path.display(), name.display());
+                backend.read_all(file_path).and_then(|content| regions_of(&content))
+            }
+        };
+        regions.map_err(GfsError::Io)
+    }
+    
+    pub fn check_for_sync_file<P: AsRef<path::Path>>(&self, file_path: P) -> Result<FileSyncState, GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        if self.sync_policy == SyncPolicy::Disabled {
+            return Err(GfsError::SyncDisabled { path: file_path.to_path_buf() });
+        }
+        let if_file_in_cache = self.cache().content_map.contains_key(file_path);
+        match if_file_in_cache {
+            false => {
+                let err = GfsError::NotCached { path: file_path.to_path_buf() };
+                self.record_error(file_path, &err);
+                Err(err)
+            }
+            true => {
+                // a torn read has a meaningless hash, it can only be reloaded
+                if let Some(FileMeta { volatile: true, .. }) = self.cache().meta_map.get(file_path) {
+                    return Ok(FileSyncState::HashUnmatch);
+                }
+                let hasher = Arc::clone(&self.cache().hasher);
+                let on_disk = self.sandboxed(file_path)?;
+                let disk_file_hash = match self.locate(file_path) {
+                    Some((root, _)) if self.decodes(&on_disk) || self.key_for(root, &on_disk).is_some() => {
+                        // the cache holds the decrypted and decompressed content, so hash that
+                        let (disk_file, _, _) = self.load_from(root, file_path, None, Tracking::default())?;
+                        hasher.hash_bytes(&disk_file)
+                    }
+                    #[cfg(feature = "zip")]
+                    Some((Root::Zip(archive), _)) => {
+                        // the archive has a CRC-32 of every entry, no need to hash anything
+                        let cached_crc = self.cache().meta_map.get(file_path)
+                            .and_then(|meta| meta.archive_crc);
+                        return match cached_crc.is_some() && cached_crc == archive.crc32(&on_disk) {
+                            true => Ok(FileSyncState::HashMatch),
+                            false => Ok(FileSyncState::HashUnmatch),
+                        };
+                    }
+                    Some((Root::Dir(root), _)) => {
+                        let absolute_path = root.join(&on_disk);
+                        debug!("{}",absolute_path.display());
+                        let unchanged = || {
+                            let meta = self.cache().meta_map.get(file_path).cloned()?;
+                            Some(unchanged_since_read(&meta, &fs::metadata(&absolute_path).ok()?))
+                        };
+                        if !self.paranoid_sync && unchanged() == Some(true) {
+                            // still hashed on the first check as `HashTiming` promises, from memory
+                            if !self.cache().sha2_map.contains_key(file_path) {
+                                self.cache_mut().rehash(file_path);
+                            }
+                            return Ok(FileSyncState::HashMatch);
+                        }
+                        match self.indexed_hash(file_path, &absolute_path, &*hasher) {
+                            Some(hash) => hash,
+                            // one buffer at a time, the cache already has the whole file
+                            None => {
+                                let _step = trace::step(Stage::Hash, &absolute_path);
+                                hasher.hash(&mut self.buffered(fs::File::open(&absolute_path)?))?
+                            }
+                        }
+                    }
+                    Some((Root::Pack(pack), _)) => match self.retry_policy.run(|| pack.hash(&on_disk, &*hasher).transpose())? {
+                        Some(hash) => hash,
+                        None => return Err(self.not_found_error(file_path)),
+                    },
+                    Some((Root::Backend(_, backend), _)) => {
+                        let cached_etag = self.cache().meta_map.get(file_path)
+                            .and_then(|meta| meta.etag.clone());
+                        match cached_etag {
+                            Some(cached_etag) => {
+                                return match backend.metadata(&on_disk)?.etag == Some(cached_etag) {
+                                    true => Ok(FileSyncState::HashMatch),
+                                    false => Ok(FileSyncState::HashUnmatch),
+                                };
+                            }
+                            None => hasher.hash_bytes(&self.retry_policy.run(|| backend.read_all(&on_disk))?),
+                        }
+                    }
+                    None => return Err(self.not_found_error(file_path)),
+                };
+                let if_hashed = self.cache().sha2_map.contains_key(file_path);
+                if !if_hashed {
+                    self.cache_mut().rehash(file_path);
+                }
+                let cache = self.cache();
+                let cached_file_hash = match cache.sha2_map.get(file_path) {
+                    Some(cached_file_hash) => cached_file_hash,
+                    // dropped from the cache by another thread in the meantime
+                    None => return Ok(FileSyncState::HashUnmatch),
+                };
+                let diff_count = disk_file_hash
+                    .iter()
+                    .zip(cached_file_hash.iter())
+                    .filter(|&
+                             (a, b)| a
+                        != b).count();
+                if diff_count == 0 {
+                    Ok(FileSyncState::HashMatch)
+                } else {
+                    Ok(FileSyncState::HashUnmatch)
+                }
+            }
+        }
+    }
+    
+    /// `check_for_sync_file` for every cached file at once. the cache is only locked to list
+    /// the files, not while they are hashed
+    pub fn check_for_sync_all(&self) -> HashMap<PathBuf, FileSyncState> {
+        let cached: Vec<PathBuf> = self.cache().content_map.keys().cloned().collect();
+        cached.into_iter()
+            .filter_map(|file_path| {
+                let state = match self.check_for_sync_file(&file_path) {
+                    Ok(state) => state,
+                    Err(GfsError::NotFound { .. }) | Err(GfsError::BrokenSymlink { .. }) => FileSyncState::MissingOnDisk,
+                    // dropped from the cache by another thread in the meantime
+                    Err(_) => return None,
+                };
+                Some((file_path, state))
+            })
+            .collect()
+    }
+    
+    /// report whether a cached file still exists on disk and still matches the cache,
+    /// e.g. to warn about orphaned entries. the file is only re-hashed when its modification
+    /// time or size changed. returns None if the file is not cached
+    pub fn backing_status<P: AsRef<path::Path>>(&self, file_path: P) -> Option<BackingStatus> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let meta = self.cache().meta_map.get(file_path)?.clone();
+        let resolved = self.resolve(file_path)
+            .and_then(|(absolute_path, _)| Some((fs::metadata(&absolute_path).ok()?, absolute_path)));
+        let (disk_meta, absolute_path) = match resolved {
+            Some(resolved) => resolved,
+            None => {
+                return Some(BackingStatus {
+                    resolved: None,
+                    stale: true,
+                });
+            }
+        };
+    
+        let stale = match unchanged_since_read(&meta, &disk_meta) {
+            true => false,
+            false => match fs::File::open(&absolute_path) {
+                Ok(file) => {
+                    let hasher = Arc::clone(&self.cache().hasher);
+                    match (hasher.hash(&mut self.buffered(file)), self.cache().sha2_map.get(file_path)) {
+                        (Ok(disk_file_hash), Some(cached_file_hash)) => **cached_file_hash != disk_file_hash[..],
+                        _ => true,
+                    }
+                }
+                Err(_) => true,
+            },
+        };
+        Some(BackingStatus {
+            resolved: Some(absolute_path),
+            stale,
+        })
+    }
+    
+    /// write the hash, size and modification time of every cached file into `dir`, for the
+    /// next start to bring back in with `load_index`, together with what was loaded or saved
+    /// before. with `content`, the content of every cached file is written out as well
+    pub fn save_index<P: AsRef<path::Path>>(&self, dir: P, content: bool) -> Result<(), GfsError> {
+        let mut index = self.disk_index.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+        let mut saved = HashMap::new();
+        {
+            let mut cache = self.cache_mut();
+            if index.hash_algorithm != cache.hasher.name() {
+                index = DiskIndex {
+                    hash_algorithm: String::from(cache.hasher.name()),
+                    entries: HashMap::new(),
+                };
+            }
+            let cached: Vec<PathBuf> = cache.content_map.keys().cloned().collect();
+            for key in cached {
+                if !cache.sha2_map.contains_key(&key) {
+                    cache.rehash(&key);
+                }
+                // a torn read or a file that never was on disk can't be checked against it
+                let (size, modified) = match cache.meta_map.get(&key) {
+                    Some(FileMeta { volatile: false, modified: Some(modified), size, .. }) => (*size, *modified),
+                    _ => continue,
+                };
+                let hash = match cache.sha2_map.get(&key) {
+                    Some(hash) => hash.clone(),
+                    None => continue,
+                };
+                if content {
+                    if let Some(bytes) = cache.raw_content(&key) {
+                        saved.insert(key.clone(), bytes.to_vec());
+                    }
+                }
+                // content saved before under its hash is still there
+                let saved = content || index.entries.get(&key)
+                    .map(|entry| entry.saved && entry.hash == hash)
+                    .unwrap_or(false);
+                index.entries.insert(key, IndexEntry { hash, size, modified, saved });
+            }
+        }
+        index.save(dir.as_ref(), &saved)?;
+        *self.disk_index.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = index;
+        Ok(())
+    }
+    
+    /// bring in an index written by `save_index`. sync checks then take the hash of a file on
+    /// disk from the index as long as its size and modification time haven't changed, instead
+    /// of reading and hashing it. files saved with their content are cached right away, as
+    /// they were when saved, so check them with `check_for_sync_all`. returns how many were
+    /// cached. fails with `GfsError::HashAlgorithmMismatch` for another digest than the cache's
+    pub fn load_index<P: AsRef<path::Path>>(&self, dir: P) -> Result<usize, GfsError> {
+        let index = DiskIndex::load(dir.as_ref())?;
+        let expected = String::from(self.cache().hasher.name());
+        if index.hash_algorithm != expected {
+            return Err(GfsError::HashAlgorithmMismatch {
+                expected,
+                found: index.hash_algorithm,
+            });
+        }
+        let mut cached = 0;
+        for (key, entry) in index.entries.iter().filter(|(_, entry)| entry.saved) {
+            let bytes = match fs::read(DiskIndex::content_path(dir.as_ref(), entry)) {
+                Ok(bytes) if bytes.len() as u64 == entry.size => bytes,
+                _ => continue,
+            };
+            let meta = FileMeta {
+                modified: Some(entry.modified),
+                ..FileMeta::default()
+            };
+            self.cache_mut().insert(key.clone(), FileContent::from(bytes), meta, Some(entry.hash.to_vec()));
+            cached += 1;
+        }
+        *self.disk_index.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = index;
+        Ok(cached)
+    }
+    
+    /// the hash `load_index` has for a file on disk, if it is unchanged since
+    fn indexed_hash(&self, file_path: &path::Path, absolute_path: &path::Path, hasher: &dyn ContentHasher)
+        -> Option<Vec<u8>> {
+        let index = self.disk_index.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if index.hash_algorithm != hasher.name() {
+            return None;
+        }
+        index.hash(file_path, &fs::metadata(absolute_path).ok()?).map(<[u8]>::to_vec)
+    }
+    
+    /// make sure the hashes in the cache were computed with the digest it hashes disk files
+    /// with, e.g. after swapping in a cache restored from elsewhere. mismatching hashes would
+    /// otherwise make every sync check report `HashUnmatch`
+    pub fn check_hash_algorithm(&self) -> Result<(), GfsError> {
+        let cache = self.cache();
+        match cache.hash_algorithm == cache.hasher.name() {
+            true => Ok(()),
+            false => Err(GfsError::HashAlgorithmMismatch {
+                expected: String::from(cache.hasher.name()),
+                found: cache.hash_algorithm.clone(),
+            }),
+        }
+    }
+    
+    /// the SHA-256 and size of every file under root, whether cached or not, e.g. to ship
+    /// with an installation and check it later with `verify_manifest`
+    pub fn export_manifest(&self) -> Result<Manifest, GfsError> {
+        let mut files = Vec::new();
+        pack::collect_files(&self.root, &self.root, &mut files)?;
+        let mut manifest = Manifest::new();
+        for (key, absolute_path) in files {
+            manifest.entries.insert(PathBuf::from(key), ManifestEntry::of_file(&absolute_path)?);
+        }
+        Ok(manifest)
+    }
+    
+    /// compare every file listed in `manifest` with the file under root, e.g. to check an
+    /// installation is intact. files under root the manifest doesn't list are ignored
+    pub fn verify_manifest(&self, manifest: &Manifest) -> BTreeMap<PathBuf, ManifestCheck> {
+        manifest.entries.iter()
+            .map(|(file_path, expected)| {
+                let absolute_path = normalize(file_path).map(|file_path| self.root.join(file_path));
+                let check = match absolute_path.map(|absolute_path| ManifestEntry::of_file(&absolute_path)) {
+                    // a manifest can't vouch for anything outside of root
+                    None => ManifestCheck::Mismatch,
+                    Some(Ok(found)) if found == *expected => ManifestCheck::Match,
+                    Some(Ok(_)) => ManifestCheck::Mismatch,
+                    Some(Err(ref err)) if err.kind() == io::ErrorKind::NotFound => ManifestCheck::Missing,
+                    Some(Err(err)) => {
+                        warn!("failed to verify {}: {}", file_path.display(), err);
+                        ManifestCheck::Mismatch
+                    }
+                };
+                (file_path.clone(), check)
+            })
+            .collect()
+    }
+    
+    /// see `Cache::stats`, with the throughput of mounted backends
+    pub fn stats(&self) -> CacheStats {
+        let mut throttles: Vec<&Throttle> = Vec::new();
+        for (mount, _) in &self.mounts {
+            if let Mount::Backend(_, backend) = mount {
+                match backend.throttle() {
+                    Some(throttle) if !throttles.iter().any(|seen| seen.same(throttle)) => throttles.push(throttle),
+                    _ => {}
+                }
+            }
+        }
+        CacheStats {
+            download_bytes_per_sec: throttles.iter().map(|throttle| throttle.throughput()).sum(),
+            ..self.cache().stats()
+        }
+    }
+    
+    /// see `Cache::reset_stats`
+    pub fn reset_stats(&self) {
+        self.cache().reset_stats();
+    }
+    
+    /// see `Cache::merge`
+    pub fn merge_cache(&self, other: Cache, policy: MergePolicy) -> Result<(), GfsError> {
+        self.cache_mut().merge(other, policy)
+    }
+    
+    /// finish everything gfs still has pending and make sure it is durable on disk, as a
+    /// clean point to call before shutting down: files queued with `HashTiming::Background`
+    /// are hashed, and files written with `write_file_deferred` and spilled by `spill_dir`
+    /// are synced to disk along with their directories. a file that fails to sync is
+    /// tried again by the next flush
+    pub fn flush(&self) -> Result<(), GfsError> {
+        if let Some(worker) = &self.hash_worker {
+            worker.wait_idle();
+        }
+        let unsynced: Vec<PathBuf> = lock(&self.unsynced).drain().collect();
+        let mut failed = None;
+        for absolute_path in unsynced {
+            let synced = fs::File::open(&absolute_path)
+                .and_then(|file| file.sync_all())
+                .and_then(|_| absolute_path.parent().map_or(Ok(()), sync_dir));
+            match synced {
+                Ok(()) => {}
+                // removed or renamed since, nothing of it left to sync
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    warn!("failed to sync {}: {}", absolute_path.display(), err);
+                    lock(&self.unsynced).insert(absolute_path);
+                    failed.get_or_insert(err);
+                }
+            }
+        }
+        if let Err(err) = self.cache().sync_spilled() {
+            failed.get_or_insert(err);
+        }
+        match failed {
+            Some(err) => Err(GfsError::Io(err)),
+            None => Ok(()),
+        }
+    }
+    
+    /// `write_file`, except the file is only synced to disk by the next `flush`, e.g. for
+    /// many small save files written at once. it is renamed into place and cached right away,
+    /// so reads see it, but a crash before the flush may lose it
+    pub fn write_file_deferred<P: AsRef<path::Path>>(&self, file_path: P, bytes: &[u8]) -> Result<(), GfsError> {
+        let absolute_path = self.write_atomic(file_path.as_ref(), bytes, false)?;
+        lock(&self.unsynced).insert(absolute_path);
+        Ok(())
+    }
+    
+    /// the most recent failed lookups, oldest first, with at most one entry per path
+    #[cfg(feature = "diagnostics")]
+    pub fn recent_errors(&self) -> Vec<(PathBuf, String)> {
+        lock(&self.recent_errors).iter().cloned().collect()
+    }
+    
+    /// what is in `dir` ("" for the top level) across root, every mount and every fallback
+    /// root, sorted by name. where several have an entry of the same name, the one a read
+    /// would find wins. fails with `GfsError::NotFound` if the directory is nowhere
+    pub fn list_dir<P: AsRef<path::Path>>(&self, dir: P) -> Result<Vec<DirEntry>, GfsError> {
+        let dir: &path::Path = &self.cache_key(dir);
+        let mut found = false;
+        let mut entries: BTreeMap<String, DirEntry> = BTreeMap::new();
+        for (root, _) in self.search_order() {
+            let listed = match root {
+                Root::Dir(root) => match fs::read_dir(root.join(dir)) {
+                    Ok(listed) => listed.filter_map(|entry| {
+                            let entry = entry.ok()?;
+                            let meta = fs::metadata(entry.path()).ok()?;
+                            let size = match meta.is_dir() {
+                                true => 0,
+                                false => meta.len(),
+                            };
+                            Some((entry.file_name().to_string_lossy().into_owned(), meta.is_dir(), size))
+                        })
+                        .collect(),
+                    Err(_) => continue,
+                },
+                Root::Pack(pack) => list_files(pack.files(), dir),
+                #[cfg(feature = "zip")]
+                Root::Zip(archive) => list_files(archive.files(), dir),
+                Root::Backend(_, backend) => list_files(backend.list().unwrap_or_default(), dir),
+            };
+            // an archive only has the directories its files are in
+            found |= matches!(root, Root::Dir(_)) || !listed.is_empty();
+            for (name, is_dir, size) in listed {
+                entries.entry(name.clone()).or_insert(DirEntry { name, is_dir, size });
+            }
+        }
+        match found {
+            true => Ok(entries.into_values().collect()),
+            false => Err(self.not_found_error(dir)),
+        }
+    }
+    
+    /// whether `read_file` would find the file, cached or under any root or mount, without
+    /// reading it. placeholders and the miss handler don't count
+    pub fn exists<P: AsRef<path::Path>>(&self, file_path: P) -> bool {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        self.is_cached(file_path) || self.locate(file_path).is_some()
+    }
+    
+    /// see `Cache::file_hash`. the digest is the one given to `set_hasher`, SHA-256 by default
+    pub fn file_hash<P: AsRef<path::Path>>(&self, file_path: P) -> Option<FileHash> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        self.cache().file_hash(file_path)
+    }
+    
+    /// whether the file is cached, so `read_file` hands it out without touching the disk
+    pub fn is_cached<P: AsRef<path::Path>>(&self, file_path: P) -> bool {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        self.cache().content_map.contains_key(file_path)
+    }
+    
+    /// how many bytes the cache holds for the file, compressed if it keeps it compressed.
+    /// None if it isn't cached
+    pub fn cached_size<P: AsRef<path::Path>>(&self, file_path: P) -> Option<usize> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        self.cache().content_map.get(file_path).map(|content| content.len())
+    }
+    
+    /// the size and modification time of a file or directory under the first root or mount
+    /// that has it, as `read_file` would find it, without reading or caching anything
+    pub fn metadata<P: AsRef<path::Path>>(&self, file_path: P) -> Result<Metadata, GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let (roots, on_disk) = match self.lookup(file_path) {
+            Some(found) => found,
+            None => return Err(self.not_found_error(file_path)),
+        };
+        let in_files = |files: Vec<(PathBuf, u64)>| {
+            let is_dir = files.iter().any(|(key, _)| key.starts_with(&on_disk) && *key != on_disk);
+            let size = files.into_iter().find(|(key, _)| *key == on_disk).map(|(_, size)| size);
+            match (size, is_dir) {
+                (Some(size), _) => Some(Metadata { size, modified: None, readonly: true, is_dir: false }),
+                (None, true) => Some(Metadata { size: 0, modified: None, readonly: true, is_dir: true }),
+                (None, false) => None,
+            }
+        };
+        let found = roots.into_iter().find_map(|(root, _)| match root {
+            Root::Dir(dir) => {
+                let meta = fs::metadata(dir.join(&on_disk)).ok()?;
+                if !self.links_allowed(dir, &on_disk) {
+                    return None;
+                }
+                Some(Metadata {
+                    size: match meta.is_dir() {
+                        true => 0,
+                        false => meta.len(),
+                    },
+                    modified: meta.modified().ok(),
+                    readonly: meta.permissions().readonly(),
+                    is_dir: meta.is_dir(),
+                })
+            }
+            Root::Pack(pack) => in_files(pack.files()),
+            #[cfg(feature = "zip")]
+            Root::Zip(archive) => in_files(archive.files()),
+            Root::Backend(_, backend) => match backend.metadata(&on_disk) {
+                Ok(meta) => Some(Metadata { size: meta.size, modified: meta.modified, readonly: true, is_dir: false }),
+                Err(_) => in_files(backend.list().unwrap_or_default()),
+            },
+        });
+        match found {
+            Some(meta) => Ok(meta),
+            None => Err(self.not_found_error(file_path)),
+        }
+    }
+    
+    /// every file matching `pattern`, like "textures/**/*.png", across root, every mount and
+    /// every fallback root, sorted and without duplicates. see `list_dir` for single directories
+    pub fn glob(&self, pattern: &str) -> impl Iterator<Item = PathBuf> {
+        let prefix: PathBuf = glob::literal_prefix(pattern).into_iter().collect();
+        let mut matched = BTreeSet::new();
+        for (root, _) in self.search_order() {
+            let keys = match root {
+                Root::Dir(root) => {
+                    let mut files = Vec::new();
+                    // a directory that doesn't exist or can't be read has no matches
+                    let _ = pack::collect_files(root, &root.join(&prefix), &mut files);
+                    files.into_iter().map(|(key, _)| key).collect()
+                }
+                Root::Pack(pack) => keys_of(pack.files()),
+                #[cfg(feature = "zip")]
+                Root::Zip(archive) => keys_of(archive.files()),
+                Root::Backend(_, backend) => keys_of(backend.list().unwrap_or_default()),
+            };
+            matched.extend(keys.into_iter()
+                .filter(|key| glob::matches(pattern, key))
+                .map(PathBuf::from));
+        }
+        matched.into_iter()
+    }
+    
+    /// cache every file under `dir` ("" for everything) across roots and mounts, e.g. to warm
+    /// the cache behind a loading screen. failing files are reported, not fatal
+    pub fn preload_dir<P: AsRef<path::Path>>(&self, dir: P) -> PreloadReport {
+        self.preload_dir_with_progress(dir, |_, _| {})
+    }
+    
+    /// same as `preload_dir`, with `progress` told how many bytes of all the files are read and
+    /// how many there are, as `read_file_with_progress` tells it for one file. the total is
+    /// measured up front and grows if files turn out bigger, e.g. once decompressed
+    pub fn preload_dir_with_progress<P, F>(&self, dir: P, mut progress: F) -> PreloadReport
+        where P: AsRef<path::Path>,
+              F: FnMut(u64, u64) {
+        let dir: &path::Path = &self.cache_key(dir);
+        let mut segments: Vec<_> = dir.components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect();
+        segments.push("**".into());
+        let file_paths: Vec<_> = self.glob(&segments.join("/")).collect();
+        let mut total: u64 = file_paths.iter()
+            .filter_map(|file_path| self.metadata(file_path).ok())
+            .map(|meta| meta.size)
+            .sum();
+        let mut report = PreloadReport::default();
+        for file_path in file_paths {
+            let read = self.read_file_with_progress(&file_path, |done, _| {
+                let done = report.bytes + done;
+                total = total.max(done);
+                progress(done, total);
+            });
+            match read {
+                Ok(content) => {
+                    report.files += 1;
+                    report.bytes += content.len() as u64;
+                }
+                Err(err) => report.failures.push((file_path, err)),
+            }
+        }
+        report
+    }
+    
+    /// `read_file` for many files at once, spread over a thread per core. results are in the
+    /// order of `file_paths`. read one after the other where there are no threads, like the web
+    pub fn read_files<P: AsRef<path::Path> + Sync>(&self, file_paths: &[P]) -> Vec<Result<FileContent, GfsError>> {
+        let workers = thread::available_parallelism().map_or(1, usize::from).min(file_paths.len());
+        if workers <= 1 {
+            return file_paths.iter().map(|file_path| self.read_file(file_path)).collect();
+        }
+        let next = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<Result<FileContent, GfsError>>>> =
+            Mutex::new(file_paths.iter().map(|_| None).collect());
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    // taken one at a time, so a few big files don't hold up a whole share
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let file_path = match file_paths.get(index) {
+                        Some(file_path) => file_path,
+                        None => break,
+                    };
+                    let result = self.read_file(file_path);
+                    lock(&results)[index] = Some(result);
+                });
+            }
+        });
+        results.into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .into_iter()
+            // a worker that panicked has taken the scope down with it
+            .map(|result| result.expect("every file is read before the workers are joined"))
+            .collect()
+    }
+    
+    /// cache every file listed in `manifest`, itself read like any other file but not cached.
+    /// the manifest has one path per line, optionally preceded by a priority and a space
+    /// (0 if there is none). files load highest priority first, otherwise in the order listed.
+    /// blank lines and lines starting with `#` are skipped. `progress` is called after each
+    /// file with how many are done, how many there are and the file just loaded
+    pub fn prefetch_manifest<P, F>(&self, manifest: P, mut progress: F) -> Result<PreloadReport, GfsError>
+        where P: AsRef<path::Path>,
+              F: FnMut(usize, usize, &path::Path) {
+        let listed = self.read_file_opts(&manifest, ReadOptions { cache: false, ..ReadOptions::default() })?;
+        let listed = match std::str::from_utf8(&listed) {
+            Ok(listed) => parse_prefetch_list(listed),
+            Err(_) => {
+                let mut err = String::from("Prefetch manifest is not valid UTF-8: ");
+                err.push_str(&format!("{:#?}", manifest.as_ref()));
+                return Err(GfsError::Io(io::Error::new(io::ErrorKind::InvalidData, err)));
+            }
+        };
+        let mut report = PreloadReport::default();
+        for (done, file_path) in listed.iter().enumerate() {
+            match self.read_file(file_path) {
+                Ok(content) => {
+                    report.files += 1;
+                    report.bytes += content.len() as u64;
+                }
+                Err(err) => report.failures.push((file_path.clone(), err)),
+            }
+            progress(done + 1, listed.len(), file_path);
+        }
+        Ok(report)
+    }
+    
+    /// the key a file is cached under: aliases expanded, `\` taken as a separator, `.` and
+    /// `..` resolved and, with `lowercase_keys`, lowercased. every method taking a path goes
+    /// through it, so "models\\chest.obj" and "models/./chest.obj" are the same file. a path
+    /// that would leave the root is returned as is, for the lookup to refuse
+    pub fn cache_key<P: AsRef<path::Path>>(&self, file_path: P) -> PathBuf {
+        let file_path = file_path.as_ref();
+        let aliased = file_path.to_str()
+            .and_then(|file_path| file_path.split_once("://"))
+            .and_then(|(name, rest)| match name {
+                "file" => Some(PathBuf::from(rest)),
+                _ => {
+                    let (_, dir) = self.aliases.iter().find(|(alias, _)| alias == name)?;
+                    Some(dir.join(rest))
+                }
+            });
+        let file_path = aliased.as_deref().unwrap_or(file_path);
+        let separated = match file_path.to_str() {
+            Some(file_path) if file_path.contains('\\') => PathBuf::from(file_path.replace('\\', "/")),
+            _ => file_path.to_path_buf(),
+        };
+        let key = match normalize(&separated) {
+            Some(key) => key,
+            None => return file_path.to_path_buf(),
+        };
+        self.resolve_variant(self.lowercased(key))
+    }
+    
+    fn lowercased(&self, key: PathBuf) -> PathBuf {
+        match (self.lowercase_keys, key.to_str()) {
+            (true, Some(lowercase)) => PathBuf::from(lowercase.to_lowercase()),
+            _ => key,
+        }
+    }
+    
+    /// the first variant of `key` that is cached or exists, or `key` itself. the localized
+    /// variants of the file in the quality tiers come first, from the active tier down, then
+    /// those of the file itself, then the same for every variant extension
+    fn resolve_variant(&self, key: PathBuf) -> PathBuf {
+        if self.locale.is_none() && self.variant_extensions.is_empty() && self.quality.is_none() {
+            return key;
+        }
+        if let Some(resolved) = lock(&self.resolved).get(&key) {
+            return resolved.clone();
+        }
+        let localized = |file_path: PathBuf| {
+            let locales = self.locale.as_deref().map(variant::locale_chain).unwrap_or_default();
+            let mut candidates: Vec<PathBuf> = locales.into_iter()
+                .filter_map(|locale| variant::render(&self.locale_pattern, &file_path, "locale", locale))
+                .map(|candidate| self.lowercased(candidate))
+                .collect();
+            candidates.push(file_path);
+            candidates
+        };
+        let tiered = |file_path: PathBuf| {
+            let active = self.quality.as_ref()
+                .and_then(|quality| self.quality_tiers.iter().position(|tier| tier == quality));
+            let tiers = match active {
+                Some(active) => &self.quality_tiers[..=active],
+                None => &[],
+            };
+            let mut candidates: Vec<PathBuf> = tiers.iter().rev()
+                .filter_map(|tier| variant::render(&self.quality_pattern, &file_path, "tier", tier))
+                .map(|candidate| self.lowercased(candidate))
+                .collect();
+            candidates.push(file_path);
+            candidates
+        };
+        let with_extensions = self.variant_extensions.iter().map(|extension| {
+            let mut file_path = key.clone().into_os_string();
+            file_path.push(".");
+            file_path.push(extension);
+            self.lowercased(PathBuf::from(file_path))
+        });
+        let resolved = std::iter::once(key.clone())
+            .chain(with_extensions)
+            .flat_map(tiered)
+            .flat_map(localized)
+            .find(|candidate| self.cache().content_map.contains_key(candidate) || self.locate(candidate).is_some())
+            .unwrap_or_else(|| key.clone());
+        lock(&self.resolved).insert(key, resolved.clone());
+        resolved
+    }
+    
+    /// `WriteFile::write_file`, returning where the file ended up. unless it is `durable`
+    /// the file isn't synced to disk, see `write_file_deferred`
+    fn write_atomic(&self, file_path: &path::Path, bytes: &[u8], durable: bool) -> Result<PathBuf, GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let absolute_path = self.writable_path(file_path)?;
+        let file_name = match absolute_path.file_name() {
+            Some(file_name) => file_name.to_string_lossy().into_owned(),
+            None => return Err(self.not_found_error(file_path)),
+        };
+        if let Some(dir) = absolute_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // numbered per write, so concurrent writes of the same file never share a temp file
+        static WRITES: AtomicU64 = AtomicU64::new(0);
+        let temp_path = absolute_path.with_file_name(format!(".{}.{}.{}.tmp", file_name, process::id(),
+                                                             WRITES.fetch_add(1, Ordering::Relaxed)));
+        let written = fs::File::create(&temp_path)
+            .and_then(|mut temp| {
+                temp.write_all(bytes)?;
+                match durable {
+                    true => temp.sync_all(),
+                    false => Ok(()),
+                }
+            })
+            .and_then(|_| fs::rename(&temp_path, &absolute_path))
+            // the rename itself is only durable once the directory is synced
+            .and_then(|_| match (durable, absolute_path.parent()) {
+                (true, Some(dir)) => sync_dir(dir),
+                _ => Ok(()),
+            });
+        if let Err(err) = written {
+            let _ = fs::remove_file(&temp_path);
+            return Err(GfsError::Io(err));
+        }
+        let meta = FileMeta {
+            modified: fs::metadata(&absolute_path).and_then(|meta| meta.modified()).ok(),
+            ..FileMeta::default()
+        };
+        match self.sync_policy {
+            SyncPolicy::Enabled => self.cache_mut().store_file_with_meta(file_path.to_path_buf(), bytes, meta),
+            SyncPolicy::Disabled => self.cache_mut().store_file_unhashed(file_path.to_path_buf(), bytes, meta),
+        }
+        Ok(absolute_path)
+    }
+    
+    /// where `WriteFile` puts a file, refusing paths that would leave root
+    fn writable_path(&self, file_path: &path::Path) -> Result<PathBuf, GfsError> {
+        Ok(self.root.join(self.sandboxed(file_path)?))
+    }
+    
+    /// `file_path` with `.` and `..` resolved, failing with `GfsError::OutsideRoot` if it would
+    /// leave the root it is looked up under
+    fn sandboxed(&self, file_path: &path::Path) -> Result<PathBuf, GfsError> {
+        match self.on_disk(file_path) {
+            Some(normalized) => Ok(normalized),
+            None => {
+                let err = GfsError::OutsideRoot { path: file_path.to_path_buf() };
+                self.record_error(file_path, &err);
+                Err(err)
+            }
+        }
+    }
+    
+    /// every root and archive in the order files are looked up in, with whether files found
+    /// there may be cached
+    fn search_order(&self) -> Vec<(Root<'_>, bool)> {
+        let ahead = self.mounts.iter().filter(|(_, priority)| *priority >= 0);
+        let behind = self.mounts.iter().filter(|(_, priority)| *priority < 0);
+        ahead.map(|(mount, _)| (mount.root(), true))
+            .chain(std::iter::once((Root::Dir(&self.root), true)))
+            .chain(behind.map(|(mount, _)| (mount.root(), true)))
+            .chain(self.fallback_roots.iter().map(|(root, cache)| (Root::Dir(root), *cache)))
+            .collect()
+    }
+    
+    /// the directories of `search_order`, leaving out packs and archives
+    fn search_path(&self) -> Vec<(&path::Path, bool)> {
+        self.search_order().into_iter()
+            .filter_map(|(root, cache)| match root {
+                Root::Dir(dir) => Some((dir, cache)),
+                _ => None,
+            })
+            .collect()
+    }
+    
+    /// `file_path` normalized, and with `case_insensitive` spelled the way the first root
+    /// that has it in any case does if none has it as is. None if it would leave the root
+    fn on_disk(&self, file_path: &path::Path) -> Option<PathBuf> {
+        self.lookup(file_path).map(|(_, on_disk)| on_disk)
+    }
+    
+    /// the roots to look a file up in with its path relative to them, see `on_disk`.
+    /// that is only the routed mount for a path under a prefix set up with `route`
+    fn lookup(&self, file_path: &path::Path) -> Option<(Vec<(Root<'_>, bool)>, PathBuf)> {
+        let normalized = normalize(file_path)?;
+        let routed = self.routes.iter()
+            .find_map(|(prefix, target)| Some((target, normalized.strip_prefix(prefix).ok()?)));
+        let (roots, normalized) = match routed {
+            Some((target, rest)) => {
+                let roots = self.mounts.iter()
+                    .filter(|(mount, _)| mount.path() == target)
+                    .map(|(mount, _)| (mount.root(), true))
+                    .collect();
+                (roots, rest.to_path_buf())
+            }
+            None => (self.search_order(), normalized),
+        };
+        if !self.case_insensitive || self.find_in(&roots, &normalized).is_some() {
+            return Some((roots, normalized));
+        }
+        let folded = roots.iter()
+            .find_map(|(root, _)| match root {
+                Root::Dir(dir) => fold_in_dir(dir, &normalized),
+                Root::Pack(pack) => fold_in_files(pack.files(), &normalized),
+                #[cfg(feature = "zip")]
+                Root::Zip(archive) => fold_in_files(archive.files(), &normalized),
+                Root::Backend(_, backend) => fold_in_files(backend.list().ok()?, &normalized),
+            });
+        Some((roots, folded.unwrap_or(normalized)))
+    }
+    
+    /// the first root or archive of `search_order` that has a file, with whether it may be cached
+    fn locate(&self, file_path: &path::Path) -> Option<(Root<'_>, bool)> {
+        let (roots, file_path) = self.lookup(file_path)?;
+        self.find_in(&roots, &file_path)
+    }
+    
+    fn find_in<'a>(&self, roots: &[(Root<'a>, bool)], file_path: &path::Path) -> Option<(Root<'a>, bool)> {
+        roots.iter()
+            .copied()
+            .find(|(root, _)| match root {
+                Root::Dir(dir) => dir.join(file_path).is_file() && self.links_allowed(dir, file_path),
+                Root::Pack(pack) => pack.contains(file_path),
+                #[cfg(feature = "zip")]
+                Root::Zip(archive) => archive.contains(file_path),
+                Root::Backend(_, backend) => backend.metadata(file_path).is_ok(),
+            })
+    }
+    
+    /// find a file on disk under the first root of the search path that has it.
+    /// also returns whether it may be cached
+    fn resolve(&self, file_path: &path::Path) -> Option<(PathBuf, bool)> {
+        let (roots, file_path) = self.lookup(file_path)?;
+        roots.into_iter()
+            .filter_map(|(root, cache)| match root {
+                Root::Dir(dir) => Some((dir, cache)),
+                _ => None,
+            })
+            .find(|(root, _)| root.join(&file_path).is_file() && self.links_allowed(root, &file_path))
+            .map(|(root, cache)| (root.join(&file_path), cache))
+    }
+    
+    /// whether `symlink_policy` lets `file_path` be read from under `dir`
+    fn links_allowed(&self, dir: &path::Path, file_path: &path::Path) -> bool {
+        match self.symlink_policy {
+            SymlinkPolicy::Follow => true,
+            SymlinkPolicy::Deny => {
+                let mut absolute_path = dir.to_path_buf();
+                file_path.components().all(|component| {
+                    absolute_path.push(component);
+                    match fs::symlink_metadata(&absolute_path) {
+                        Ok(meta) => !meta.file_type().is_symlink(),
+                        Err(_) => false,
+                    }
+                })
+            }
+            SymlinkPolicy::DenyOutsideRoot => match (fs::canonicalize(dir), fs::canonicalize(dir.join(file_path))) {
+                (Ok(dir), Ok(target)) => target.starts_with(dir),
+                _ => false,
+            },
+        }
+    }
+    
+    fn not_found_error(&self, file_path: &path::Path) -> GfsError {
+        let refused = || self.search_path().into_iter().any(|(root, _)| root.join(file_path).is_file());
+        let err = match self.find_broken_symlink(file_path) {
+            // only there behind a link the symlink policy refuses
+            _ if normalize(file_path).is_none() || refused() => GfsError::OutsideRoot { path: file_path.to_path_buf() },
+            Some((link, target)) => GfsError::BrokenSymlink { link, target },
+            None => GfsError::NotFound { path: file_path.to_path_buf() },
+        };
+        self.record_error(file_path, &err);
+        err
+    }
+    
+    /// a symlink named `file_path` under any root whose target doesn't exist, with that target
+    fn find_broken_symlink(&self, file_path: &path::Path) -> Option<(PathBuf, PathBuf)> {
+        let file_path = normalize(file_path)?;
+        self.search_path().into_iter()
+            .map(|(root, _)| root.join(&file_path))
+            .find_map(|link| {
+                // read_link fails for anything but a symlink
+                let target = fs::read_link(&link).ok()?;
+                match link.exists() {
+                    true => None,
+                    false => Some((link, target)),
+                }
+            })
+    }
+    
+    /// the cached content of a file, decompressed if it is stored compressed
+    fn cached_content(&self, file_path: &path::Path) -> Option<FileContent> {
+        let cache = self.cache();
+        cache.touch(file_path);
+        cache.raw_content(file_path)
+    }
+    
+    fn log_miss(&self, file_path: &path::Path, found_on_disk: bool) {
+        {
+            let mut misses_until_log = lock(&self.misses_until_log);
+            if *misses_until_log > 0 {
+                *misses_until_log -= 1;
+                return;
+            }
+            *misses_until_log = self.miss_log_sample - 1;
+        }
+        match found_on_disk {
+            true => log!(self.miss_log_level, "cache miss, loaded from disk: {}", file_path.display()),
+            false => log!(self.miss_log_level, "cache miss, not found on disk: {}", file_path.display()),
+        }
+    }
+    
+    #[cfg_attr(not(feature = "diagnostics"), allow(unused_variables))]
+    fn record_error(&self, file_path: &path::Path, err: &GfsError) {
+        #[cfg(feature = "diagnostics")]
+        {
+            let mut recent_errors = lock(&self.recent_errors);
+            recent_errors.retain(|(path, _)| path != file_path);
+            if recent_errors.len() == RECENT_ERRORS_CAPACITY {
+                recent_errors.pop_front();
+            }
+            recent_errors.push_back((file_path.to_path_buf(), err.to_string()));
+        }
+    }
+}
+
+impl ReadFile for GemFileSystem {
+    /// format: gfs.read_file(&"models/chest.obj")
+    /// or anything, typed AsRef<path::Path>, with a string formatted as "models/chest.obj" or like
+    fn read_file<P: AsRef<path::Path>>(&self, file_path: P) -> Result<FileContent, GfsError> {
+        self.read_file_opts(file_path, ReadOptions::default())
+    }
+}
+
+impl WriteFile for GemFileSystem {
+    /// save `bytes` as the file under root, e.g. a config or save file, and cache them.
+    /// the file is written next to its destination first and renamed over it, so a crash
+    /// never leaves it half written. it is stored as-is, never compressed or encrypted,
+    /// and always under root even if a mount shadows it
+    fn write_file<P: AsRef<path::Path>>(&self, file_path: P, bytes: &[u8]) -> Result<(), GfsError> {
+        self.write_atomic(file_path.as_ref(), bytes, true).map(|_| ())
+    }
+    
+    /// delete the file under root and drop it from the cache
+    fn remove_file<P: AsRef<path::Path>>(&self, file_path: P) -> Result<(), GfsError> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        let absolute_path = self.writable_path(file_path)?;
+        let removed = fs::remove_file(&absolute_path);
+        self.cache_mut().remove_file(file_path);
+        match removed {
+            Ok(()) => Ok(()),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Err(self.not_found_error(file_path)),
+            Err(err) => Err(GfsError::Io(err)),
+        }
+    }
+    
+    /// move the file under root, along with its cache entry
+    fn rename<P: AsRef<path::Path>, Q: AsRef<path::Path>>(&self, from: P, to: Q) -> Result<(), GfsError> {
+        let from: &path::Path = &self.cache_key(from);
+        let to: &path::Path = &self.cache_key(to);
+        let absolute_from = self.writable_path(from)?;
+        let absolute_to = self.writable_path(to)?;
+        if !absolute_from.is_file() {
+            return Err(self.not_found_error(from));
+        }
+        if let Some(dir) = absolute_to.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::rename(&absolute_from, &absolute_to)?;
+        self.cache_mut().rename_file(from, to);
+        // keys may have resolved to either file as a variant, and assets depend on them by key
+        lock(&self.resolved).clear();
+        for key in &[from, to] {
+            self.invalidate_dependents(key);
+            let mut assets = lock(&self.assets);
+            assets.forget_parsed(key);
+            assets.set_dependencies(key, Vec::new());
+        }
+        Ok(())
+    }
+}
+
+/// make the entries of `dir`, like a file just renamed into it, durable. only unix can
+/// sync a directory
+fn sync_dir(dir: &path::Path) -> io::Result<()> {
+    #[cfg(unix)]
+    fs::File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// whether a file on disk still has the modification time and size it had when it was cached
+fn unchanged_since_read(meta: &FileMeta, disk_meta: &fs::Metadata) -> bool {
+    !meta.volatile
+        && meta.modified.is_some()
+        && meta.modified == disk_meta.modified().ok()
+        && meta.size == disk_meta.len()
+}
+
+#[cfg(any(feature = "json", feature = "toml", feature = "ron"))]
+fn parse_error<E: fmt::Display>(file_path: &path::Path, format: &'static str, err: E) -> GfsError {
+    GfsError::Parse {
+        path: file_path.to_path_buf(),
+        format,
+        message: err.to_string(),
+    }
+}
+
+/// lock a mutex guarding gfs bookkeeping, which a panicking thread can't leave half updated
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl PathMapper for GemFileSystem {
+    /// the file under the first root that has it, or under `root` if none does.
+    /// a path that would leave the root maps to the root itself
+    fn map<P: AsRef<path::Path>>(&self, file_path: P) -> Box<path::Path> {
+        let file_path: &path::Path = &self.cache_key(file_path);
+        match self.resolve(file_path) {
+            Some((absolute_path, _)) => absolute_path.into_boxed_path(),
+            None => {
+                let mut absolute_path = self.root.clone();
+                absolute_path.push(normalize(file_path).unwrap_or_default());
+                absolute_path.into_boxed_path()
+            }
+        }
+    }
+    
+}
+/// the resource directory of the app bundle `exe` is the executable of, if any
+#[cfg_attr(not(any(target_os = "macos", target_os = "ios")), allow(dead_code))]
+fn bundle_resource_dir(exe: &path::Path) -> Option<PathBuf> {
+    let app = OsStr::new("app");
+    let exe_dir = exe.parent()?;
+    // iOS bundles are flat, the executable sits right next to the resources
+    if exe_dir.extension() == Some(app) {
+        return Some(exe_dir.to_path_buf());
+    }
+    let contents = exe_dir.parent()?;
+    let is_macos_layout = exe_dir.file_name() == Some(OsStr::new("MacOS"))
+        && contents.file_name() == Some(OsStr::new("Contents"))
+        && contents.parent()?.extension() == Some(app);
+    match is_macos_layout {
+        true => Some(contents.join("Resources")),
+        false => None,
+    }
+}
+
+/// read a file with `load` until `modified` reports the same time before and after,
+/// giving up after MAX_READ_RETRIES extra attempts and marking the result volatile
+fn load_stable<T, M, L>(mut modified: M, mut load: L) -> (T, FileMeta)
+    where M: FnMut() -> Option<SystemTime>,
+          L: FnMut() -> T {
+    let mut retries = 0;
+    loop {
+        let before = modified();
+        let content = load();
+        let after = modified();
+        if before == after || retries == MAX_READ_RETRIES {
+            let meta = FileMeta {
+                modified: after,
+                volatile: before != after,
+                ..FileMeta::default()
+            };
+            return (content, meta);
+        }
+        retries += 1;
+    }
+}
+
+/// the paths of a `GemFileSystem::prefetch_manifest` manifest, in the order to load them in
+fn parse_prefetch_list(listed: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<(i32, PathBuf)> = listed.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let prioritized = line.split_once(' ')
+                .and_then(|(priority, file_path)| Some((priority.parse().ok()?, file_path.trim_start())));
+            match prioritized {
+                Some((priority, file_path)) => (priority, PathBuf::from(file_path)),
+                None => (0, PathBuf::from(line)),
+            }
+        })
+        .collect();
+    // stable, so equal priorities keep their order
+    paths.sort_by(|(a, _), (b, _)| b.cmp(a));
+    paths.into_iter().map(|(_, file_path)| file_path).collect()
+}
+
+/// the keys of the files of a pack or archive with `/` separators, like patterns have
+fn keys_of(files: Vec<(PathBuf, u64)>) -> Vec<String> {
+    files.into_iter()
+        .map(|(key, _)| {
+            let components: Vec<_> = key.components().map(|component| component.as_os_str().to_string_lossy()).collect();
+            components.join("/")
+        })
+        .collect()
+}
+
+/// the entries of `dir` in a pack or archive, given all of its files. directories
+/// only show up through the files in them
+fn list_files(files: Vec<(PathBuf, u64)>, dir: &path::Path) -> Vec<(String, bool, u64)> {
+    files.into_iter()
+        .filter_map(|(key, size)| {
+            let mut rest = key.strip_prefix(dir).ok()?.components();
+            let name = rest.next()?.as_os_str().to_string_lossy().into_owned();
+            match rest.next() {
+                Some(_) => Some((name, true, 0)),
+                None => Some((name, false, size)),
+            }
+        })
+        .collect()
+}
+
+/// resolve `.` and `..` in a path relative to a root, None if it would leave the root or
+/// isn't relative at all
+fn normalize(file_path: &path::Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in file_path.components() {
+        match component {
+            path::Component::Normal(name) => normalized.push(name),
+            path::Component::CurDir => {}
+            path::Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            path::Component::RootDir | path::Component::Prefix(_) => return None,
+        }
+    }
+    Some(normalized)
+}
+
+/// how a file under `dir` is actually spelled when its case doesn't matter, looking
+/// through one directory level at a time
+fn fold_in_dir(dir: &path::Path, file_path: &path::Path) -> Option<PathBuf> {
+    let mut found = PathBuf::new();
+    for component in file_path.components() {
+        let wanted = component.as_os_str().to_string_lossy().to_lowercase();
+        let here = dir.join(&found);
+        let exact = here.join(component);
+        match exact.exists() {
+            true => found.push(component),
+            false => {
+                let name = fs::read_dir(&here).ok()?
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.file_name())
+                    .find(|name| name.to_string_lossy().to_lowercase() == wanted)?;
+                found.push(name);
+            }
+        }
+    }
+    match dir.join(&found).is_file() {
+        true => Some(found),
+        false => None,
+    }
+}
+
+/// how a file of a pack or archive is actually spelled when its case doesn't matter
+fn fold_in_files(files: Vec<(PathBuf, u64)>, file_path: &path::Path) -> Option<PathBuf> {
+    let wanted = file_path.to_string_lossy().to_lowercase();
+    files.into_iter()
+        .map(|(key, _)| key)
+        .find(|key| key.to_string_lossy().to_lowercase() == wanted)
+}
+
+/// the error for a pack or archive entry that disappeared after it was looked up
+/// fails for a region of `len` bytes at `offset` that runs past the end of `size` bytes,
+/// before anything is allocated for it
+pub(crate) fn check_region(offset: u64, len: usize, size: u64) -> io::Result<()> {
+    match offset.checked_add(len as u64) {
+        Some(end) if end <= size => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("Region of {} bytes at {} runs past the end, at {}", len, offset, size),
+        )),
+    }
+}
+
+fn vanished(mount: &path::Path) -> io::Error {
+    let mut err = String::from("Entry is gone from: ");
+    err.push_str(&format!("{:#?}", mount));
+    io::Error::new(io::ErrorKind::NotFound, err)
+}
+
+/// map a file read-only. a mapping always shows the current content, so there is no torn
+/// read to retry
+#[cfg(feature = "mmap")]
+fn map_file(absolute_path: &path::Path) -> io::Result<(FileContent, FileMeta)> {
+    let file = fs::File::open(absolute_path)?;
+    let modified = file.metadata()?.modified().ok();
+    // sound only as long as nobody changes the file while it is mapped, which
+    // `GemFileSystem::mmap_extension` asks of the caller
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    let meta = FileMeta {
+        modified,
+        ..FileMeta::default()
+    };
+    Ok((FileContent::mapped(map), meta))
+}
+
+fn process_sha256<D: Digest + Default, R: Read + ?Sized>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut sh = D::default();
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    loop {
+        let n = match reader.read(&mut buffer)? {
+            0 => break,
+            n => n,
+        };
+        // a short read is not the end, buffered readers hand out what they hold first
+        sh.input(&buffer[..n]);
+    }
+    Ok(sh.result().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        env,
+        fs,
+        process,
+        sync::{
+            Arc,
+            Mutex,
+        },
+        thread,
+        time::Duration,
+    };
+    
+    /// a fresh, empty directory under the system temp dir for a single test
+    pub(crate) fn test_root(name: &str) -> PathBuf {
+        let mut root = env::temp_dir();
+        root.push(format!("gfs_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+    
+    #[test]
+    fn it_works() {
+        assert_eq!(2 + 2, 4);
+    }
+    
+    #[test]
+    fn read_aligned_satisfies_alignment() {
+        let root = test_root("read_aligned");
+        fs::write(root.join("mesh.bin"), b"vertex data").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        
+        for &align in &[16, 64, 4096] {
+            let bytes = gfs.read_aligned("mesh.bin", align).unwrap();
+            assert_eq!(bytes.as_ptr() as usize % align, 0);
+            assert_eq!(&bytes[..], b"vertex data");
+        }
+        assert!(gfs.read_aligned("mesh.bin", 3).is_err());
+    }
+    
+    #[test]
+    fn change_feed_invalidates_listed_paths() {
+        let root = test_root("change_feed");
+        for name in &["a.txt", "b.txt", "c.txt"] {
+            fs::write(root.join(name), name.as_bytes()).unwrap();
+        }
+        let gfs = GemFileSystem::new(&root);
+        for name in &["a.txt", "b.txt", "c.txt"] {
+            gfs.read_file(name).unwrap();
+        }
+        
+        let invalidated = gfs.apply_change_feed(vec!["a.txt", "b.txt", "not_cached.txt"]);
+        assert_eq!(invalidated, 2);
+        assert!(!gfs.cache().content_map.contains_key(path::Path::new("a.txt")));
+        assert!(!gfs.cache().sha2_map.contains_key(path::Path::new("b.txt")));
+        assert!(gfs.cache().content_map.contains_key(path::Path::new("c.txt")));
+    }
+    
+    #[test]
+    fn change_feed_reloads_when_enabled() {
+        let root = test_root("change_feed_reload");
+        fs::write(root.join("a.txt"), b"old").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.reload_on_change = true;
+        gfs.read_file("a.txt").unwrap();
+        
+        fs::write(root.join("a.txt"), b"new").unwrap();
+        assert_eq!(gfs.apply_change_feed(["a.txt"]), 1);
+        assert_eq!(&gfs.cache().content_map[path::Path::new("a.txt")][..], b"new");
+    }
+    
+    #[test]
+    fn read_regions_skips_gaps() {
+        let root = test_root("read_regions");
+        let mut content = Vec::new();
+        content.extend_from_slice(b"head");
+        content.extend_from_slice(&[0u8; 4096]);
+        content.extend_from_slice(b"tail");
+        fs::write(root.join("padded.pak"), &content).unwrap();
+        let gfs = GemFileSystem::new(&root);
+        
+        let regions = gfs.read_regions("padded.pak", &[(0, 4), (4100, 4)]).unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(&regions[0][..], b"head");
+        assert_eq!(&regions[1][..], b"tail");
+        assert!(!gfs.cache().content_map.contains_key(path::Path::new("padded.pak")));
+        
+        // a region running past the end of the file is an error, not a short read
+        assert!(gfs.read_regions("padded.pak", &[(4100, 8)]).is_err());
+    }
+    
+    #[test]
+    fn read_regions_follows_search_order() {
+        let root = test_root("read_regions_roots");
+        fs::create_dir_all(root.join("loose")).unwrap();
+        fs::create_dir_all(root.join("shared")).unwrap();
+        fs::create_dir_all(root.join("packed")).unwrap();
+        fs::write(root.join("shared/fallback.pak"), b"head....tail").unwrap();
+        fs::write(root.join("packed/packed.pak"), b"head....tail").unwrap();
+        let pack = root.join("assets.gfspack");
+        write_pack(&root.join("packed"), &mut fs::File::create(&pack).unwrap(), &Sha256Hasher).unwrap();
+        let memory = MemoryBackend::new();
+        memory.insert("memory.pak", b"head....tail".to_vec()).unwrap();
+        let mut gfs = GemFileSystem::new(root.join("loose"));
+        gfs.add_fallback_root(root.join("shared"), true);
+        gfs.mount_pack(&pack, 1).unwrap();
+        gfs.mount_backend("memory", memory, 2);
+        
+        for file in &["fallback.pak", "packed.pak", "memory.pak"] {
+            let regions = gfs.read_regions(file, &[(8, 4), (0, 4)]).unwrap();
+            assert_eq!(&regions[0][..], b"tail");
+            assert_eq!(&regions[1][..], b"head");
+            // the length is checked against the file before anything is allocated for it
+            let err = gfs.read_regions(file, &[(4, usize::MAX)]).unwrap_err();
+            assert!(matches!(err, GfsError::Io(ref err) if err.kind() == io::ErrorKind::UnexpectedEof));
+            assert!(gfs.read_regions(file, &[(12, 1)]).is_err());
+        }
+        assert!(matches!(gfs.read_regions("missing.pak", &[(0, 1)]), Err(GfsError::NotFound { .. })));
+        assert!(gfs.cache().content_map.is_empty());
+    }
+    
+    struct CapturingLogger {
+        records: Mutex<Vec<(log::Level, String)>>,
+    }
+    
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+        
+        fn log(&self, record: &log::Record) {
+            self.records.lock().unwrap().push((record.level(), format!("{}", record.args())));
+        }
+        
+        fn flush(&self) {}
+    }
+    
+    static LOGGER: CapturingLogger = CapturingLogger { records: Mutex::new(Vec::new()) };
+    
+    #[test]
+    fn miss_logging_is_sampled() {
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+        let root = test_root("miss_log_sample");
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.miss_log_level(log::Level::Warn).miss_log_sample(2);
+        
+        for i in 0..6 {
+            assert!(gfs.read_file(format!("sampled_miss_{}.png", i)).is_err());
+        }
+        let logged = LOGGER.records.lock().unwrap().iter()
+            .filter(|(level, message)| *level == log::Level::Warn && message.contains("sampled_miss_"))
+            .count();
+        assert_eq!(logged, 3);
+    }
+    
+    #[test]
+    fn swap_cache_is_atomic_for_readers() {
+        let paths = ["a.txt", "b.txt", "c.txt"];
+        let build_cache = |content: &[u8]| {
+            let mut cache = Cache::new();
+            for name in &paths {
+                cache.store_file(PathBuf::from(name), content.to_vec().into_boxed_slice());
+            }
+            cache
+        };
+        let shared = Arc::new(GemFileSystem::new(test_root("swap_cache")));
+        shared.swap_cache(build_cache(b"old"));
+        
+        let reader = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let mut saw_new = false;
+                while !saw_new {
+                    let snapshot: Vec<FileContent> = {
+                        let cache = shared.cache();
+                        paths.iter()
+                            .map(|name| cache.content_map[path::Path::new(name)].clone())
+                            .collect()
+                    };
+                    assert!(snapshot.iter().all(|content| *content == snapshot[0]));
+                    saw_new = &snapshot[0][..] == b"new";
+                }
+            })
+        };
+        let old = shared.swap_cache(build_cache(b"new"));
+        assert_eq!(&old.content_map[path::Path::new("a.txt")][..], b"old");
+        reader.join().unwrap();
+    
+        // a reader still on the old cache holds up neither the swap nor later reads
+        let held = shared.cache();
+        let swapper = {
+            let shared = shared.clone();
+            let newer = build_cache(b"newer");
+            thread::spawn(move || shared.swap_cache(newer))
+        };
+        while &shared.cache().content_map[path::Path::new("a.txt")][..] != b"newer" {
+            thread::yield_now();
+        }
+        assert_eq!(&held.content_map[path::Path::new("a.txt")][..], b"new");
+        drop(held);
+        assert_eq!(&swapper.join().unwrap().content_map[path::Path::new("a.txt")][..], b"new");
+    }
+    
+    #[test]
+    fn shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<GemFileSystem>();
+    
+        let root = test_root("shared_threads");
+        for i in 0..4 {
+            fs::write(root.join(format!("{}.ogg", i)), format!("track {}", i)).unwrap();
+        }
+        let gfs = Arc::new(GemFileSystem::new(&root));
+        let readers: Vec<_> = (0..4).map(|i| {
+            let gfs = gfs.clone();
+            thread::spawn(move || {
+                for _ in 0..10 {
+                    let content = gfs.read_file(format!("{}.ogg", i)).unwrap();
+                    assert_eq!(&content[..], format!("track {}", i).as_bytes());
+                }
+            })
+        }).collect();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(gfs.cache().content_map.len(), 4);
+    }
+    
+    #[test]
+    fn handles_outlive_the_cache_entry() {
+        let root = test_root("handles");
+        fs::write(root.join("a.txt"), b"first").unwrap();
+        fs::write(root.join("b.txt"), b"second").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        
+        let first = gfs.read_file("a.txt").unwrap();
+        let second = gfs.read_file("b.txt").unwrap();
+        // the handle is the cached content itself, not a copy of it
+        assert!(FileContent::ptr_eq(&first, &gfs.cache().content_map[path::Path::new("a.txt")]));
+        gfs.apply_change_feed(["a.txt", "b.txt"]);
+        assert!(gfs.cache().content_map.is_empty());
+        assert_eq!(&first[..], b"first");
+        assert_eq!(&second[..], b"second");
+    }
+    
+    #[test]
+    fn identical_files_share_one_copy() {
+        let root = test_root("deduplicate");
+        fs::create_dir_all(root.join("pack_a")).unwrap();
+        fs::create_dir_all(root.join("pack_b")).unwrap();
+        fs::write(root.join("pack_a/grass.tex"), [7u8; 64]).unwrap();
+        fs::write(root.join("pack_b/grass.tex"), [7u8; 64]).unwrap();
+        fs::write(root.join("pack_b/stone.tex"), [9u8; 64]).unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.deduplicate(true);
+        
+        let a = gfs.read_file("pack_a/grass.tex").unwrap();
+        let b = gfs.read_file("pack_b/grass.tex").unwrap();
+        let stone = gfs.read_file("pack_b/stone.tex").unwrap();
+        assert!(FileContent::ptr_eq(&a, &b));
+        assert!(!FileContent::ptr_eq(&a, &stone));
+        assert_eq!(gfs.cache().current_bytes(), 128);
+        // evicting one of them frees nothing while the other is still cached
+        gfs.evict("pack_a/grass.tex");
+        assert_eq!(gfs.cache().current_bytes(), 128);
+        drop((a, b));
+        gfs.evict("pack_b/grass.tex");
+        assert_eq!(gfs.cache().current_bytes(), 64);
+        assert_eq!(&gfs.read_file("pack_a/grass.tex").unwrap()[..], &[7u8; 64][..]);
+        assert_eq!(gfs.cache().current_bytes(), 128);
+    }
+    
+    #[test]
+    fn budget_evicts_least_recently_used() {
+        let root = test_root("lru_budget");
+        for name in &["a.bin", "b.bin", "c.bin"] {
+            fs::write(root.join(name), [0u8; 4]).unwrap();
+        }
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.cache_budget(Some(10));
+        
+        gfs.read_file("a.bin").unwrap();
+        gfs.read_file("b.bin").unwrap();
+        // reading a.bin again makes it the most recently used
+        gfs.read_file("a.bin").unwrap();
+        gfs.read_file("c.bin").unwrap();
+        assert!(gfs.cache().content_map.contains_key(path::Path::new("a.bin")));
+        assert!(!gfs.cache().content_map.contains_key(path::Path::new("b.bin")));
+        assert!(gfs.cache().content_map.contains_key(path::Path::new("c.bin")));
+        assert_eq!(gfs.cache().current_bytes(), 8);
+        
+        gfs.cache_budget(Some(4));
+        assert_eq!(gfs.cache().content_map.len(), 1);
+    }
+    
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn evicted_entries_are_unmapped_from_the_store() {
+        let root = test_root("mmap_store");
+        for i in 0..3u8 {
+            fs::write(root.join(format!("bank_{}.wem", i)), vec![i; 1 << 20]).unwrap();
+        }
+        let store = MmapStore::new();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.content_store(store.clone())
+            .cache_budget(Some(2 << 20));
+    
+        for i in 0..3u8 {
+            let bank = gfs.read_file(format!("bank_{}.wem", i)).unwrap();
+            assert!(!bank.is_heap());
+            assert_eq!(&bank[..], &vec![i; 1 << 20][..]);
+        }
+        // nothing holds bank_0 anymore, so its pages went back to the OS when it was evicted
+        assert!(!gfs.cache().content_map.contains_key(path::Path::new("bank_0.wem")));
+        assert_eq!(gfs.stats().evictions, 1);
+        assert_eq!(store.mappings(), 2);
+    
+        let held = gfs.read_file("bank_2.wem").unwrap();
+        gfs.read_file("bank_1.wem").unwrap();
+        gfs.cache_budget(Some(1 << 20));
+        // bank_2 is evicted, but still mapped while a caller holds it
+        assert!(!gfs.cache().content_map.contains_key(path::Path::new("bank_2.wem")));
+        assert_eq!(store.mappings(), 2);
+        drop(held);
+        assert_eq!(store.mappings(), 1);
+    }
+    
+    #[test]
+    fn evict_and_invalidate_report_freed_bytes() {
+        let root = test_root("evict");
+        fs::create_dir_all(root.join("level1")).unwrap();
+        fs::write(root.join("level1/map.bin"), [0u8; 8]).unwrap();
+        fs::write(root.join("level1/sky.png"), [0u8; 4]).unwrap();
+        fs::write(root.join("level10.bin"), [0u8; 2]).unwrap();
+        fs::write(root.join("ui.png"), [0u8; 1]).unwrap();
+        let gfs = GemFileSystem::new(&root);
+        for name in &["level1/map.bin", "level1/sky.png", "level10.bin", "ui.png"] {
+            gfs.read_file(name).unwrap();
+        }
+        
+        assert_eq!(gfs.evict("ui.png"), 1);
+        assert_eq!(gfs.evict("ui.png"), 0);
+        assert!(!gfs.cache().sha2_map.contains_key(path::Path::new("ui.png")));
+        // prefixes match whole path components only
+        assert_eq!(gfs.invalidate_prefix("level1"), 12);
+        assert!(gfs.cache().content_map.contains_key(path::Path::new("level10.bin")));
+        assert_eq!(gfs.clear(), 2);
+        assert!(gfs.cache().content_map.is_empty());
+        assert!(gfs.cache().sha2_map.is_empty());
+    }
+    
+    #[test]
+    fn stats_count_hits_misses_and_evictions() {
+        let root = test_root("stats");
+        fs::write(root.join("a.bin"), [0u8; 6]).unwrap();
+        fs::write(root.join("b.bin"), [0u8; 6]).unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.cache_budget(Some(8));
+        let cached_only = ReadOptions {
+            cache: false,
+            ..ReadOptions::default()
+        };
+        
+        gfs.read_file("a.bin").unwrap();
+        gfs.read_file_opts("a.bin", cached_only).unwrap();
+        gfs.read_file("b.bin").unwrap();
+        assert!(gfs.read_file("missing.bin").is_err());
+        assert_eq!(gfs.stats(), CacheStats {
+            hits: 1,
+            misses: 3,
+            evictions: 1,
+            total_bytes: 6,
+            entries: 1,
+            bytes_read_from_disk: 12,
+            download_bytes_per_sec: 0,
+        });
+        
+        gfs.reset_stats();
+        let stats = gfs.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions, stats.bytes_read_from_disk), (0, 0, 0, 0));
+        assert_eq!(stats.entries, 1);
+    }
+    
+    #[test]
+    fn read_file_synced_picks_up_changes() {
+        let root = test_root("read_synced");
+        fs::write(root.join("shader.glsl"), b"v1").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        assert_eq!(&gfs.read_file_synced("shader.glsl").unwrap()[..], b"v1");
+        let (_, token) = gfs.read_with_token("shader.glsl").unwrap();
+        
+        assert_eq!(&gfs.read_file_synced("shader.glsl").unwrap()[..], b"v1");
+        assert!(!gfs.has_changed("shader.glsl", token));
+        fs::write(root.join("shader.glsl"), b"v2").unwrap();
+        assert_eq!(&gfs.read_file_synced("shader.glsl").unwrap()[..], b"v2");
+        assert_eq!(&gfs.cache().content_map[path::Path::new("shader.glsl")][..], b"v2");
+        assert!(matches!(gfs.check_for_sync_file("shader.glsl").unwrap(), FileSyncState::HashMatch));
+    }
+    
+    #[test]
+    fn check_for_sync_all_reports_every_entry() {
+        let root = test_root("sync_all");
+        for name in &["same.txt", "changed.txt", "deleted.txt"] {
+            fs::write(root.join(name), b"v1").unwrap();
+        }
+        let gfs = GemFileSystem::new(&root);
+        for name in &["same.txt", "changed.txt", "deleted.txt"] {
+            gfs.read_file(name).unwrap();
+        }
+        fs::write(root.join("changed.txt"), b"v2").unwrap();
+        fs::remove_file(root.join("deleted.txt")).unwrap();
+        
+        let report = gfs.check_for_sync_all();
+        assert_eq!(report.len(), 3);
+        assert_eq!(report[path::Path::new("same.txt")], FileSyncState::HashMatch);
+        assert_eq!(report[path::Path::new("changed.txt")], FileSyncState::HashUnmatch);
+        assert_eq!(report[path::Path::new("deleted.txt")], FileSyncState::MissingOnDisk);
+    }
+    
+    #[test]
+    fn load_stable_retries_changing_file() {
+        // the modification time moves once, during the first read only
+        let mut ticks = vec![0, 1, 1, 1].into_iter();
+        let mut loads = 0;
+        let (content, meta) = load_stable(
+            || ticks.next().map(|tick| SystemTime::UNIX_EPOCH + Duration::from_secs(tick)),
+            || {
+                loads += 1;
+                vec![loads].into_boxed_slice()
+            },
+        );
+        assert_eq!(loads, 2);
+        assert_eq!(&content[..], &[2]);
+        assert!(!meta.volatile);
+    }
+    
+    #[test]
+    fn load_stable_marks_never_settling_file_volatile() {
+        let mut tick = 0;
+        let mut loads = 0;
+        let (_, meta) = load_stable(
+            || {
+                tick += 1;
+                Some(SystemTime::UNIX_EPOCH + Duration::from_secs(tick))
+            },
+            || {
+                loads += 1;
+                Box::<[u8]>::default()
+            },
+        );
+        assert_eq!(loads, MAX_READ_RETRIES + 1);
+        assert!(meta.volatile);
+    }
+    
+    #[test]
+    fn volatile_entry_is_reported_out_of_sync() {
+        let root = test_root("volatile_sync");
+        fs::write(root.join("log.txt"), b"line").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        gfs.read_file("log.txt").unwrap();
+        assert!(matches!(gfs.check_for_sync_file("log.txt").unwrap(), FileSyncState::HashMatch));
+        
+        gfs.cache_mut().meta_map.get_mut(path::Path::new("log.txt")).unwrap().volatile = true;
+        assert!(matches!(gfs.check_for_sync_file("log.txt").unwrap(), FileSyncState::HashUnmatch));
+    }
+    
+    #[test]
+    fn writes_are_atomic_and_cached() {
+        let root = test_root("write");
+        let gfs = GemFileSystem::new(&root);
+        gfs.write_file("saves/slot1.sav", b"level 1").unwrap();
+        assert_eq!(fs::read(root.join("saves/slot1.sav")).unwrap(), b"level 1");
+        assert_eq!(&gfs.read_file("saves/slot1.sav").unwrap()[..], b"level 1");
+        assert_eq!(gfs.check_for_sync_file("saves/slot1.sav").unwrap(), FileSyncState::HashMatch);
+    
+        gfs.write_file("saves/slot1.sav", b"level 2").unwrap();
+        assert_eq!(&gfs.read_file("saves/slot1.sav").unwrap()[..], b"level 2");
+        assert_eq!(gfs.check_for_sync_file("saves/slot1.sav").unwrap(), FileSyncState::HashMatch);
+        // no temporary file is left behind
+        assert_eq!(fs::read_dir(root.join("saves")).unwrap().count(), 1);
+        assert!(matches!(gfs.write_file("../escape.sav", b""), Err(GfsError::OutsideRoot { .. })));
+    
+        // concurrent writes of one file each go through a temp file of their own
+        let gfs = Arc::new(gfs);
+        let writers: Vec<_> = (0..4u8).map(|i| {
+            let gfs = gfs.clone();
+            thread::spawn(move || for _ in 0..25 {
+                gfs.write_file("saves/slot1.sav", &[i; 4096]).unwrap();
+            })
+        }).collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let saved = fs::read(root.join("saves/slot1.sav")).unwrap();
+        assert!(saved.len() == 4096 && saved.iter().all(|&byte| byte == saved[0]));
+        assert_eq!(fs::read_dir(root.join("saves")).unwrap().count(), 1);
+    }
+    
+    #[test]
+    fn flush_makes_deferred_writes_durable() {
+        let root = test_root("flush");
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.cache_budget(Some(25)).spill_dir(Some(root.join("spill")));
+        for slot in ["slot1", "slot2", "slot3"] {
+            gfs.write_file_deferred(format!("saves/{}.sav", slot), slot.repeat(2).as_bytes()).unwrap();
+        }
+        assert_eq!(&gfs.read_file("saves/slot3.sav").unwrap()[..], b"slot3slot3");
+        assert_eq!(lock(&gfs.unsynced).len(), 3);
+        assert_eq!(fs::read_dir(root.join("spill")).unwrap().count(), 1);
+        gfs.flush().unwrap();
+        assert!(lock(&gfs.unsynced).is_empty());
+        drop(gfs);
+        
+        let gfs = GemFileSystem::new(&root);
+        for slot in ["slot1", "slot2", "slot3"] {
+            assert_eq!(&gfs.read_file(format!("saves/{}.sav", slot)).unwrap()[..], slot.repeat(2).as_bytes());
+        }
+    }
+    
+    #[test]
+    fn removes_and_renames_move_the_cache() {
+        let root = test_root("rename");
+        let gfs = GemFileSystem::new(&root);
+        gfs.write_file("slot1.sav", b"level 1").unwrap();
+        gfs.write_file("slot2.sav", b"level 2").unwrap();
+        gfs.rename("slot1.sav", "backup/slot1.sav").unwrap();
+        assert!(!root.join("slot1.sav").exists());
+        assert!(!gfs.cache().content_map.contains_key(path::Path::new("slot1.sav")));
+        assert_eq!(&gfs.cache().content_map[path::Path::new("backup/slot1.sav")][..], b"level 1");
+        assert_eq!(gfs.check_for_sync_file("backup/slot1.sav").unwrap(), FileSyncState::HashMatch);
+        assert!(matches!(gfs.rename("slot1.sav", "slot3.sav"), Err(GfsError::NotFound { .. })));
+    
+        gfs.remove_file("slot2.sav").unwrap();
+        assert!(!root.join("slot2.sav").exists());
+        assert!(!gfs.cache().sha2_map.contains_key(path::Path::new("slot2.sav")));
+        assert!(matches!(gfs.read_file("slot2.sav"), Err(GfsError::NotFound { .. })));
+        assert!(matches!(gfs.remove_file("slot2.sav"), Err(GfsError::NotFound { .. })));
+    
+        // renaming a file onto itself keeps it cached
+        gfs.rename("backup/slot1.sav", "backup/./slot1.sav").unwrap();
+        assert_eq!(&gfs.cache().content_map[path::Path::new("backup/slot1.sav")][..], b"level 1");
+    }
+    
+    #[test]
+    fn renamed_files_resolve_under_their_new_name() {
+        let root = test_root("rename_variant");
+        fs::create_dir_all(root.join("textures")).unwrap();
+        fs::write(root.join("textures/wood.png"), b"wood").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.alias("tex", "textures")
+            .variant_extensions(&["ktx"]);
+        // remembered to resolve to itself while there is no variant
+        assert!(matches!(gfs.read_file("tex://stone.png"), Err(GfsError::NotFound { .. })));
+        gfs.read_file("tex://wood.png").unwrap();
+        gfs.add_dependency("tex://wood.png", "tex://grain.png");
+    
+        gfs.rename("tex://wood.png", "tex://stone.png.ktx").unwrap();
+        assert_eq!(&gfs.read_file("tex://stone.png").unwrap()[..], b"wood");
+        assert!(matches!(gfs.read_file("tex://wood.png"), Err(GfsError::NotFound { .. })));
+        assert!(gfs.dependencies("tex://wood.png").is_empty());
+    }
+    
+    #[test]
+    fn list_dir_merges_roots() {
+        let root = test_root("list_dir");
+        fs::create_dir_all(root.join("loose/models")).unwrap();
+        fs::create_dir_all(root.join("loose/saves")).unwrap();
+        fs::write(root.join("loose/models/chest.obj"), b"loose").unwrap();
+        fs::create_dir_all(root.join("packed/models/lids")).unwrap();
+        fs::write(root.join("packed/models/chest.obj"), b"packed chest").unwrap();
+        fs::write(root.join("packed/models/lids/lid.obj"), b"lid").unwrap();
+        let pack = root.join("models.gfspack");
+        write_pack(&root.join("packed"), &mut fs::File::create(&pack).unwrap(), &Sha256Hasher).unwrap();
+        let mut gfs = GemFileSystem::new(root.join("loose"));
+        gfs.mount_pack(&pack, 1).unwrap();
+    
+        let entry = |name: &str, is_dir, size| DirEntry { name: String::from(name), is_dir, size };
+        assert_eq!(gfs.list_dir("").unwrap(), [entry("models", true, 0), entry("saves", true, 0)]);
+        assert_eq!(gfs.list_dir("models").unwrap(), [entry("chest.obj", false, 12), entry("lids", true, 0)]);
+        assert_eq!(gfs.list_dir("models/lids").unwrap(), [entry("lid.obj", false, 3)]);
+        assert!(gfs.list_dir("saves").unwrap().is_empty());
+        assert!(matches!(gfs.list_dir("textures"), Err(GfsError::NotFound { .. })));
+    
+        let globbed: Vec<PathBuf> = gfs.glob("models/**/*.obj").collect();
+        assert_eq!(globbed, [PathBuf::from("models/chest.obj"), PathBuf::from("models/lids/lid.obj")]);
+        assert_eq!(gfs.glob("*.obj").count(), 0);
+    }
+    
+    #[test]
+    fn preload_dir_caches_the_subtree() {
+        let root = test_root("preload");
+        fs::create_dir_all(root.join("level1/props")).unwrap();
+        fs::write(root.join("level1/map.json"), b"{}").unwrap();
+        fs::write(root.join("level1/props/crate.obj"), b"crate").unwrap();
+        fs::write(root.join("level1/props/broken.zst"), b"not zstd").unwrap();
+        fs::write(root.join("menu.json"), b"{}").unwrap();
+        #[cfg_attr(not(feature = "zstd"), allow(unused_mut))]
+        let mut gfs = GemFileSystem::new(&root);
+        #[cfg(feature = "zstd")]
+        gfs.decompress_extension("zst", Codec::Zstd);
+    
+        let report = gfs.preload_dir("level1");
+        let cache = gfs.cache();
+        assert!(cache.content_map.contains_key(path::Path::new("level1/props/crate.obj")));
+        assert!(!cache.content_map.contains_key(path::Path::new("menu.json")));
+        match cfg!(feature = "zstd") {
+            true => {
+                assert_eq!((report.files, report.bytes), (2, 7));
+                assert_eq!(report.failures[0].0, PathBuf::from("level1/props/broken.zst"));
+            }
+            false => assert_eq!((report.files, report.bytes, report.failures.len()), (3, 15, 0)),
+        }
+    }
+    
+    #[test]
+    fn loads_report_their_progress() {
+        let root = test_root("progress");
+        fs::create_dir_all(root.join("level")).unwrap();
+        let len = BUFFER_SIZE as u64 * 3 + 1;
+        fs::write(root.join("level/terrain.bin"), vec![1u8; len as usize]).unwrap();
+        fs::write(root.join("level/sky.bin"), vec![2u8; 500]).unwrap();
+        let gfs = GemFileSystem::new(&root);
+        let mut reported = Vec::new();
+        gfs.read_file_with_progress("level/terrain.bin", |done, total| reported.push((done, total))).unwrap();
+        assert_eq!(reported[0], (BUFFER_SIZE as u64, len));
+        assert_eq!(reported.last(), Some(&(len, len)));
+        reported.clear();
+        gfs.read_file_with_progress("level/terrain.bin", |done, total| reported.push((done, total))).unwrap();
+        assert_eq!(reported, [(len, len)]);
+        
+        let mut last = (0, 0);
+        let report = gfs.preload_dir_with_progress("level", |done, total| {
+            assert!(done >= last.0 && done <= total);
+            last = (done, total);
+        });
+        assert_eq!(report.bytes, len + 500);
+        assert_eq!(last, (len + 500, len + 500));
+    }
+    
+    /// a backend taking its time to answer, like a server that stopped responding
+    struct Stalled(Duration);
+    
+    impl FileBackend for Stalled {
+        fn open(&self, file_path: &path::Path) -> io::Result<Box<dyn Read + Send + '_>> {
+            self.metadata(file_path)?;
+            thread::sleep(self.0);
+            Ok(Box::new(&b"late"[..]))
+        }
+        
+        fn metadata(&self, file_path: &path::Path) -> io::Result<BackendMeta> {
+            match file_path == path::Path::new("slow.bin") {
+                true => Ok(BackendMeta::default()),
+                false => Err(io::Error::new(io::ErrorKind::NotFound, "Not on the server")),
+            }
+        }
+        
+        fn list(&self) -> io::Result<Vec<(PathBuf, u64)>> {
+            Ok(vec![(PathBuf::from("slow.bin"), 4)])
+        }
+    }
+    
+    #[test]
+    fn stalled_reads_time_out() {
+        let root = test_root("timeout");
+        fs::write(root.join("a.bin"), b"abc").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.read_timeout(Some(Duration::ZERO));
+        assert!(matches!(gfs.read_file("a.bin"), Err(GfsError::Timeout { .. })));
+        assert!(!gfs.is_cached("a.bin"));
+        gfs.read_timeout(None);
+        gfs.read_file("a.bin").unwrap();
+        // served from memory, there is nothing to wait for
+        gfs.read_timeout(Some(Duration::ZERO));
+        assert_eq!(&gfs.read_file("a.bin").unwrap()[..], b"abc");
+        
+        gfs.read_timeout(None)
+            .mount_backend("cdn", Stalled(Duration::from_millis(200)), 10);
+        let gfs = Arc::new(gfs);
+        let read = gfs.read_file_with_timeout("slow.bin", Duration::from_millis(10));
+        assert!(matches!(read, Err(GfsError::Timeout { .. })));
+        thread::sleep(Duration::from_millis(400));
+        assert!(gfs.is_cached("slow.bin"));
+        assert_eq!(&gfs.read_file_with_timeout("slow.bin", Duration::ZERO).unwrap()[..], b"late");
+    }
+    
+    #[test]
+    fn prefetch_manifest_loads_by_priority() {
+        let root = test_root("prefetch");
+        fs::write(root.join("hero.png"), b"hero").unwrap();
+        fs::write(root.join("sky box.png"), b"sky").unwrap();
+        fs::write(root.join("music.ogg"), b"music").unwrap();
+        fs::write(root.join("level1.prefetch"), "# level 1\nmusic.ogg\n10 hero.png\n\n5 sky box.png\nmissing.png\n").unwrap();
+        let gfs = GemFileSystem::new(&root);
+    
+        let mut loaded = Vec::new();
+        let report = gfs.prefetch_manifest("level1.prefetch", |done, total, file_path| {
+            loaded.push((done, total, file_path.to_path_buf()));
+        }).unwrap();
+        let order: Vec<_> = loaded.iter().map(|(_, _, file_path)| file_path.to_str().unwrap()).collect();
+        assert_eq!(order, ["hero.png", "sky box.png", "music.ogg", "missing.png"]);
+        assert_eq!(loaded[3].0, 4);
+        assert_eq!(loaded[3].1, 4);
+        assert_eq!((report.files, report.bytes), (3, 12));
+        assert!(matches!(report.failures[0], (_, GfsError::NotFound { .. })));
+        assert!(!gfs.cache().content_map.contains_key(path::Path::new("level1.prefetch")));
+        assert!(gfs.prefetch_manifest("level2.prefetch", |_, _, _| {}).is_err());
+    }
+    
+    #[test]
+    fn read_files_keeps_the_order() {
+        let root = test_root("read_files");
+        let file_paths: Vec<String> = (0..64).map(|i| format!("tile{}.bin", i)).collect();
+        for (i, file_path) in file_paths.iter().enumerate() {
+            fs::write(root.join(file_path), i.to_string()).unwrap();
+        }
+        let mut requested = file_paths.clone();
+        requested.push(String::from("missing.bin"));
+        let gfs = GemFileSystem::new(&root);
+    
+        let results = gfs.read_files(&requested);
+        assert_eq!(results.len(), 65);
+        for (i, result) in results[..64].iter().enumerate() {
+            assert_eq!(&result.as_ref().unwrap()[..], i.to_string().as_bytes());
+        }
+        assert!(matches!(results[64], Err(GfsError::NotFound { .. })));
+        assert_eq!(gfs.cache().content_map.len(), 64);
+        assert!(gfs.read_files::<&str>(&[]).is_empty());
+    }
+    
+    #[test]
+    fn concurrent_reads_share_one_disk_read() {
+        let root = test_root("coalesce");
+        fs::write(root.join("level.bin"), b"from disk").unwrap();
+        let gfs = Arc::new(GemFileSystem::new(&root));
+        // pretend another thread is in the middle of reading the file
+        let flight = Arc::new(InFlight::default());
+        lock(&gfs.in_flight).insert(PathBuf::from("level.bin"), Arc::clone(&flight));
+        let waiter = {
+            let gfs = Arc::clone(&gfs);
+            thread::spawn(move || gfs.read_file("level.bin").unwrap())
+        };
+        while Arc::strong_count(&flight) < 3 {
+            thread::yield_now();
+        }
+        drop(Landing {
+            gfs: &gfs,
+            file_path: path::Path::new("level.bin"),
+            flight,
+            content: Some(FileContent::from(&b"from the other read"[..])),
+        });
+        assert_eq!(&waiter.join().unwrap()[..], b"from the other read");
+        assert_eq!(gfs.stats().bytes_read_from_disk, 0);
+        assert!(lock(&gfs.in_flight).is_empty());
+        // caching is up to the read that went to disk, which this one pretended to be
+        assert_eq!(&gfs.read_file("level.bin").unwrap()[..], b"from disk");
+    }
+    
+    #[test]
+    fn cancelled_reads_stop_waiting_on_other_reads() {
+        let root = test_root("coalesce_cancel");
+        fs::write(root.join("level.bin"), b"from disk").unwrap();
+        let gfs = Arc::new(GemFileSystem::new(&root));
+        // another thread stuck reading the file
+        let flight = Arc::new(InFlight::default());
+        lock(&gfs.in_flight).insert(PathBuf::from("level.bin"), Arc::clone(&flight));
+        let cancel = CancellationToken::new();
+        let waiter = {
+            let (gfs, cancel) = (Arc::clone(&gfs), cancel.clone());
+            thread::spawn(move || gfs.read_file_cancellable("level.bin", &cancel))
+        };
+        while Arc::strong_count(&flight) < 3 {
+            thread::yield_now();
+        }
+        cancel.cancel();
+        assert!(matches!(waiter.join().unwrap(), Err(GfsError::Cancelled { .. })));
+        assert!(!gfs.is_cached("level.bin"));
+        lock(&gfs.in_flight).clear();
+    }
+    
+    #[test]
+    fn lookups_never_leave_the_root() {
+        let root = test_root("sandbox");
+        fs::create_dir_all(root.join("game/models")).unwrap();
+        fs::write(root.join("secret.txt"), b"secret").unwrap();
+        fs::write(root.join("game/models/chest.obj"), b"chest").unwrap();
+        let gfs = GemFileSystem::new(root.join("game"));
+    
+        assert_eq!(normalize(path::Path::new("./models/../models/chest.obj")), Some(PathBuf::from("models/chest.obj")));
+        assert_eq!(normalize(path::Path::new("models/../../secret.txt")), None);
+        assert_eq!(normalize(path::Path::new("/etc/passwd")), None);
+        assert_eq!(&gfs.read_file("models/./../models/chest.obj").unwrap()[..], b"chest");
+        for escaping in &["../secret.txt", "models/../../secret.txt", "/etc/passwd"] {
+            assert!(matches!(gfs.read_file(escaping), Err(GfsError::OutsideRoot { .. })));
+            assert!(gfs.fetch_and_cache_file(escaping).is_none());
+        }
+        assert_eq!(&*gfs.map("../secret.txt"), root.join("game").as_path());
+        assert!(matches!(gfs.write_file("saves/../../escape.sav", b""), Err(GfsError::OutsideRoot { .. })));
+        gfs.write_file("saves/../save.sav", b"saved").unwrap();
+        assert!(root.join("game/save.sav").is_file());
+    }
+    
+    #[test]
+    fn spellings_of_a_path_share_one_entry() {
+        let root = test_root("cache_key");
+        fs::create_dir_all(root.join("models")).unwrap();
+        fs::write(root.join("models/chest.obj"), b"chest").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+    
+        for spelling in &["models/chest.obj", "models\\chest.obj", "./models//chest.obj", "models/../models/chest.obj"] {
+            assert_eq!(&gfs.read_file(spelling).unwrap()[..], b"chest");
+        }
+        assert_eq!(gfs.cache().content_map.len(), 1);
+        assert!(gfs.cache().content_map.contains_key(path::Path::new("models/chest.obj")));
+        assert_eq!(gfs.check_for_sync_file("models\\chest.obj").unwrap(), FileSyncState::HashMatch);
+        assert_eq!(gfs.evict("./models/chest.obj"), 5);
+    
+        gfs.lowercase_keys(true);
+        assert_eq!(gfs.cache_key("Models\\Chest.OBJ"), PathBuf::from("models/chest.obj"));
+        assert_eq!(&gfs.read_file("MODELS/chest.obj").unwrap()[..], b"chest");
+        assert_eq!(gfs.cache_key("../Secret.txt"), PathBuf::from("../Secret.txt"));
+    }
+    
+    #[test]
+    fn case_insensitive_lookups_find_any_spelling() {
+        let root = test_root("case_insensitive");
+        fs::create_dir_all(root.join("textures")).unwrap();
+        fs::write(root.join("textures/wood.png"), b"wood").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        assert!(gfs.read_file("Textures/Wood.PNG").is_err());
+    
+        gfs.case_insensitive(true);
+        assert_eq!(&gfs.read_file("Textures/Wood.PNG").unwrap()[..], b"wood");
+        assert_eq!(gfs.check_for_sync_file("Textures/Wood.PNG").unwrap(), FileSyncState::HashMatch);
+        assert_eq!(&*gfs.map("TEXTURES/wood.png"), root.join("textures/wood.png").as_path());
+        assert!(gfs.read_file("Textures/Stone.PNG").is_err());
+        gfs.lowercase_keys(true);
+        assert_eq!(&gfs.read_file("TEXTURES/WOOD.png").unwrap()[..], b"wood");
+        assert!(gfs.cache().content_map.contains_key(path::Path::new("textures/wood.png")));
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn symlink_policy_refuses_links() {
+        use std::os::unix::fs::symlink;
+    
+        let root = test_root("symlink_policy");
+        fs::create_dir_all(root.join("game/textures")).unwrap();
+        fs::write(root.join("secret.txt"), b"secret").unwrap();
+        fs::write(root.join("game/textures/wood.png"), b"wood").unwrap();
+        symlink(root.join("secret.txt"), root.join("game/escape.txt")).unwrap();
+        symlink(root.join("game/textures/wood.png"), root.join("game/alias.png")).unwrap();
+        let mut gfs = GemFileSystem::new(root.join("game"));
+        assert_eq!(&gfs.read_file("escape.txt").unwrap()[..], b"secret");
+        gfs.clear();
+    
+        gfs.symlink_policy(SymlinkPolicy::DenyOutsideRoot);
+        assert!(matches!(gfs.read_file("escape.txt"), Err(GfsError::OutsideRoot { .. })));
+        assert!(gfs.open("escape.txt").is_err());
+        assert_eq!(&gfs.read_file("alias.png").unwrap()[..], b"wood");
+        gfs.clear();
+    
+        gfs.symlink_policy(SymlinkPolicy::Deny);
+        assert!(matches!(gfs.read_file("alias.png"), Err(GfsError::OutsideRoot { .. })));
+        assert_eq!(&gfs.read_file("textures/wood.png").unwrap()[..], b"wood");
+    }
+    
+    #[test]
+    fn aliases_stand_for_directories() {
+        let root = test_root("alias");
+        fs::create_dir_all(root.join("assets/textures")).unwrap();
+        fs::write(root.join("assets/textures/wood.png"), b"wood").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.alias("textures", "assets/textures");
+    
+        assert_eq!(&gfs.read_file("textures://wood.png").unwrap()[..], b"wood");
+        assert_eq!(&*gfs.map("textures://wood.png"), root.join("assets/textures/wood.png").as_path());
+        assert!(gfs.cache().content_map.contains_key(path::Path::new("assets/textures/wood.png")));
+        assert_eq!(gfs.cache_key("shaders://wood.png"), PathBuf::from("shaders:/wood.png"));
+        assert!(matches!(gfs.read_file("textures://../../../secret.txt"), Err(GfsError::OutsideRoot { .. })));
+    
+        // setting it again replaces the old directory
+        gfs.alias("textures", "textures");
+        assert!(gfs.read_file("textures://wood.png").is_err());
+    }
+    
+    #[test]
+    fn saved_indexes_spare_rehashing() {
+        struct Counting(Arc<AtomicUsize>);
+    
+        impl ContentHasher for Counting {
+            fn name(&self) -> &'static str {
+                "counting"
+            }
+    
+            fn hash(&self, reader: &mut dyn Read) -> io::Result<Vec<u8>> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Sha256Hasher.hash(reader)
+            }
+        }
+    
+        let root = test_root("index");
+        fs::write(root.join("level.bin"), b"level 1").unwrap();
+        fs::write(root.join("intro.txt"), b"intro").unwrap();
+        let index_dir = root.join(".index");
+        let hashed = Arc::new(AtomicUsize::new(0));
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.set_hasher(Counting(Arc::clone(&hashed)));
+        gfs.read_file("level.bin").unwrap();
+        gfs.save_index(&index_dir, true).unwrap();
+        gfs.read_file("intro.txt").unwrap();
+        gfs.save_index(&index_dir, false).unwrap();
+    
+        assert!(matches!(GemFileSystem::new(&root).load_index(&index_dir), Err(GfsError::HashAlgorithmMismatch { .. })));
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.set_hasher(Counting(Arc::clone(&hashed)));
+        hashed.store(0, Ordering::SeqCst);
+        assert_eq!(gfs.load_index(&index_dir).unwrap(), 1);
+        assert!(gfs.cache().content_map.contains_key(path::Path::new("level.bin")));
+        assert_eq!(gfs.check_for_sync_file("level.bin").unwrap(), FileSyncState::HashMatch);
+        assert_eq!(hashed.load(Ordering::SeqCst), 0);
+        fs::write(root.join("level.bin"), b"level 2").unwrap();
+        assert_eq!(gfs.check_for_sync_file("level.bin").unwrap(), FileSyncState::HashUnmatch);
+        assert_eq!(hashed.load(Ordering::SeqCst), 1);
+    }
+    
+    #[test]
+    fn file_systems_are_built_around_a_backend() {
+        let root = test_root("with_backend");
+        fs::write(root.join("settings.ini"), b"volume=3").unwrap();
+        fs::write(root.join("intro.txt"), b"old intro").unwrap();
+        let assets = MemoryBackend::new();
+        assets.insert("intro.txt", b"intro".to_vec()).unwrap();
+        let gfs = GemFileSystem::with_backend(&root, assets);
+    
+        assert_eq!(&gfs.read_file("intro.txt").unwrap()[..], b"intro");
+        assert_eq!(&gfs.read_file("settings.ini").unwrap()[..], b"volume=3");
+        gfs.write_file("save.dat", b"level 2").unwrap();
+        assert_eq!(fs::read(root.join("save.dat")).unwrap(), b"level 2");
+    }
+    
+    #[test]
+    fn uris_route_to_one_mount() {
+        let root = test_root("route");
+        fs::create_dir_all(root.join("base/models")).unwrap();
+        fs::create_dir_all(root.join("bundle1/models")).unwrap();
+        fs::write(root.join("base/models/chest.obj"), b"base").unwrap();
+        fs::write(root.join("bundle1/models/chest.obj"), b"bundled").unwrap();
+        let bundle = root.join("bundle1.gfspack");
+        write_pack(&root.join("bundle1"), &mut fs::File::create(&bundle).unwrap(), &Sha256Hasher).unwrap();
+        let mut gfs = GemFileSystem::new(root.join("base"));
+        gfs.mount_pack(&bundle, -1).unwrap().route("pack://bundle1", &bundle);
+    
+        assert_eq!(&gfs.read_file("file://models/chest.obj").unwrap()[..], b"base");
+        assert_eq!(&gfs.read_file("pack://bundle1/models/chest.obj").unwrap()[..], b"bundled");
+        assert_eq!(gfs.check_for_sync_file("pack://bundle1/models/chest.obj").unwrap(), FileSyncState::HashMatch);
+        assert_eq!(gfs.cache().content_map.len(), 2);
+        assert!(gfs.read_file("pack://bundle2/models/chest.obj").is_err());
+        gfs.route("pack://bundle1", "elsewhere.gfspack");
+        gfs.clear();
+        assert!(gfs.read_file("pack://bundle1/models/chest.obj").is_err());
+    }
+    
+    #[test]
+    fn change_token_detects_reload() {
+        let root = test_root("change_token");
+        fs::write(root.join("wood.png"), b"v1").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        let (content, token) = gfs.read_with_token("wood.png").unwrap();
+        assert_eq!(&content[..], b"v1");
+        
+        // reloading identical content keeps the token valid
+        gfs.fetch_and_cache_file("wood.png").unwrap();
+        assert!(!gfs.has_changed("wood.png", token));
+        
+        fs::write(root.join("wood.png"), b"v2").unwrap();
+        gfs.fetch_and_cache_file("wood.png").unwrap();
+        assert!(gfs.has_changed("wood.png", token));
+        let (_, new_token) = gfs.read_with_token("wood.png").unwrap();
+        assert!(!gfs.has_changed("wood.png", new_token));
+        assert!(gfs.has_changed("not_cached.png", new_token));
+    }
+    
+    #[test]
+    fn read_opts_can_skip_hashing() {
+        let root = test_root("read_opts_skip_hash");
+        fs::write(root.join("tmp.bin"), b"throwaway").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        let opts = ReadOptions {
+            hash: HashMode::Skip,
+            ..ReadOptions::default()
+        };
+        
+        assert_eq!(&gfs.read_file_opts("tmp.bin", opts).unwrap()[..], b"throwaway");
+        assert!(gfs.cache().content_map.contains_key(path::Path::new("tmp.bin")));
+        assert!(!gfs.cache().sha2_map.contains_key(path::Path::new("tmp.bin")));
+        // the sync check hashes on demand
+        assert!(matches!(gfs.check_for_sync_file("tmp.bin").unwrap(), FileSyncState::HashMatch));
+        assert!(gfs.cache().sha2_map.contains_key(path::Path::new("tmp.bin")));
+    }
+    
+    #[test]
+    fn read_opts_can_skip_caching() {
+        let root = test_root("read_opts_skip_cache");
+        fs::write(root.join("tmp.bin"), b"throwaway").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        let opts = ReadOptions {
+            cache: false,
+            ..ReadOptions::default()
+        };
+        
+        assert_eq!(&gfs.read_file_opts("tmp.bin", opts).unwrap()[..], b"throwaway");
+        assert!(!gfs.cache().content_map.contains_key(path::Path::new("tmp.bin")));
+        assert!(!gfs.cache().sha2_map.contains_key(path::Path::new("tmp.bin")));
+    }
+    
+    fn cache_with(entries: &[(&str, &[u8], u64)]) -> Cache {
+        let mut cache = Cache::new();
+        for &(name, content, modified) in entries {
+            let meta = FileMeta {
+                modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(modified)),
+                ..FileMeta::default()
+            };
+            cache.store_file_with_meta(PathBuf::from(name), content.to_vec().into_boxed_slice(), meta);
+        }
+        cache
+    }
+    
+    #[test]
+    fn merge_resolves_conflicts_by_policy() {
+        let merged_content = |policy| {
+            let mut base = cache_with(&[("shared.png", b"base", 20), ("base.png", b"base", 0)]);
+            let delta = cache_with(&[("shared.png", b"delta", 10), ("delta.png", b"delta", 0)]);
+            base.merge(delta, policy).map(|_| {
+                assert_eq!(base.content_map.len(), 3);
+                assert_eq!(base.content_map.len(), base.sha2_map.len());
+                assert_eq!(base.content_map.len(), base.meta_map.len());
+                base.content_map[path::Path::new("shared.png")].clone()
+            })
+        };
+        
+        assert_eq!(&merged_content(MergePolicy::PreferNewer).unwrap()[..], b"base");
+        assert_eq!(&merged_content(MergePolicy::PreferOther).unwrap()[..], b"delta");
+        match merged_content(MergePolicy::Error) {
+            Err(GfsError::MergeConflict { path }) => assert_eq!(path, PathBuf::from("shared.png")),
+            other => panic!("expected a merge conflict, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn merge_prefers_newer_entry() {
+        let mut base = cache_with(&[("shared.png", b"base", 10)]);
+        base.merge(cache_with(&[("shared.png", b"delta", 20)]), MergePolicy::PreferNewer).unwrap();
+        assert_eq!(&base.content_map[path::Path::new("shared.png")][..], b"delta");
+    }
+    
+    #[test]
+    fn bundle_resource_dir_follows_platform_layout() {
+        assert_eq!(bundle_resource_dir(path::Path::new("/Applications/Gem.app/Contents/MacOS/gem")),
+                   Some(PathBuf::from("/Applications/Gem.app/Contents/Resources")));
+        assert_eq!(bundle_resource_dir(path::Path::new("/var/containers/Bundle/Gem.app/gem")),
+                   Some(PathBuf::from("/var/containers/Bundle/Gem.app")));
+        assert_eq!(bundle_resource_dir(path::Path::new("/usr/local/bin/gem")), None);
+        assert_eq!(bundle_resource_dir(path::Path::new("/opt/Contents/MacOS/gem")), None);
+    }
+    
+    #[derive(Clone)]
+    struct ManualClock(Arc<Mutex<Instant>>);
+    
+    impl ManualClock {
+        fn advance(&self, by: Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+    
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+    
+    #[test]
+    fn reloads_are_rate_limited() {
+        let root = test_root("min_reload_interval");
+        fs::write(root.join("level.map"), b"v1").unwrap();
+        let clock = ManualClock(Arc::new(Mutex::new(Instant::now())));
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.set_clock(clock.clone()).min_reload_interval(Duration::from_secs(1));
+        gfs.read_file("level.map").unwrap();
+        assert!(!gfs.reload_if_changed("level.map").unwrap());
+        
+        fs::write(root.join("level.map"), b"v2").unwrap();
+        assert!(gfs.reload_if_changed("level.map").unwrap());
+        fs::write(root.join("level.map"), b"v3").unwrap();
+        assert!(!gfs.reload_if_changed("level.map").unwrap());
+        assert_eq!(&gfs.cache().content_map[path::Path::new("level.map")][..], b"v2");
+        
+        clock.advance(Duration::from_millis(1500));
+        assert!(gfs.reload_if_changed("level.map").unwrap());
+        assert_eq!(&gfs.cache().content_map[path::Path::new("level.map")][..], b"v3");
+    }
+    
+    #[test]
+    fn change_callbacks_wait_for_writes_to_settle() {
+        let root = test_root("on_change");
+        fs::create_dir_all(root.join("shaders")).unwrap();
+        fs::write(root.join("shaders/water.glsl"), b"v1").unwrap();
+        let clock = ManualClock(Arc::new(Mutex::new(Instant::now())));
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.set_clock(clock.clone()).change_debounce(Duration::from_millis(100));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&seen);
+        gfs.on_change("shaders/*.glsl", move |file_path, content| {
+            recorded.lock().unwrap().push((file_path.to_path_buf(), content.to_vec()));
+        });
+        
+        fs::write(root.join("shaders/water.glsl"), b"v2").unwrap();
+        gfs.apply_change_feed(["shaders/water.glsl", "config.toml"]);
+        clock.advance(Duration::from_millis(60));
+        fs::write(root.join("shaders/water.glsl"), b"v3").unwrap();
+        gfs.apply_change_feed(["shaders/water.glsl"]);
+        clock.advance(Duration::from_millis(60));
+        assert_eq!(gfs.dispatch_changes(), 0);
+        clock.advance(Duration::from_millis(60));
+        assert_eq!(gfs.dispatch_changes(), 1);
+        assert_eq!(*seen.lock().unwrap(), vec![(PathBuf::from("shaders/water.glsl"), b"v3".to_vec())]);
+        assert_eq!(gfs.dispatch_changes(), 0);
+    }
+    
+    #[test]
+    fn missing_files_get_placeholders() {
+        let root = test_root("placeholders");
+        fs::create_dir_all(root.join("textures")).unwrap();
+        fs::write(root.join("textures/wood.png"), b"wood").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.extension_placeholder(".png", &b"magenta checker"[..])
+            .prefix_placeholder("sounds", &b"silence"[..])
+            .prefix_placeholder("sounds/music", &b"quiet music"[..]);
+        
+        assert_eq!(&gfs.read_file("textures/wood.png").unwrap()[..], b"wood");
+        assert_eq!(&gfs.read_file("textures/stone.png").unwrap()[..], b"magenta checker");
+        assert_eq!(&gfs.read_file("sounds/step.ogg").unwrap()[..], b"silence");
+        assert_eq!(&gfs.read_file("sounds/music/theme.ogg").unwrap()[..], b"quiet music");
+        assert_eq!(&gfs.read_file("sounds/cover.png").unwrap()[..], b"silence");
+        assert!(matches!(gfs.read_file("levels/one.map"), Err(GfsError::NotFound { .. })));
+        assert!(matches!(gfs.read_file("../secret.png"), Err(GfsError::OutsideRoot { .. })));
+        // the real file is read once it shows up
+        fs::write(root.join("textures/stone.png"), b"stone").unwrap();
+        assert_eq!(&gfs.read_file("textures/stone.png").unwrap()[..], b"stone");
+    }
+    
+    #[test]
+    fn miss_handler_fills_in_missing_files() {
+        let root = test_root("miss_handler");
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let mut gfs = GemFileSystem::new(&root);
+        let log = Arc::clone(&asked);
+        gfs.extension_placeholder("png", &b"magenta checker"[..])
+            .set_miss_handler(move |file_path| {
+                lock(&log).push(file_path.to_path_buf());
+                match file_path.starts_with("noise") {
+                    true => Some(Box::from(&b"generated"[..])),
+                    false => None,
+                }
+            });
+        
+        assert_eq!(&gfs.read_file("noise/perlin.png").unwrap()[..], b"generated");
+        assert_eq!(&gfs.read_file("textures/stone.png").unwrap()[..], b"magenta checker");
+        assert!(matches!(gfs.read_file("levels/one.map"), Err(GfsError::NotFound { .. })));
+        assert_eq!(lock(&asked).len(), 3);
+        assert!(!gfs.cache().content_map.contains_key(path::Path::new("noise/perlin.png")));
+    }
+    
+    #[test]
+    fn text_is_checked_for_utf8() {
+        let root = test_root("text");
+        fs::write(root.join("shader.glsl"), b"\xEF\xBB\xBFvoid main() {\r\n}\n").unwrap();
+        fs::write(root.join("broken.cfg"), b"name = caf\xC3").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        assert_eq!(gfs.read_to_string("shader.glsl").unwrap(), "void main() {\r\n}\n");
+        assert_eq!(gfs.read_lines("shader.glsl").unwrap(), ["void main() {", "}"]);
+        assert!(matches!(gfs.read_to_string("broken.cfg"), Err(GfsError::InvalidUtf8 { offset: 10, .. })));
+        
+        gfs.strip_bom(false);
+        assert_eq!(gfs.read_to_string("shader.glsl").unwrap(), "\u{feff}void main() {\r\n}\n");
+    }
+    
+    #[cfg(all(feature = "json", feature = "toml", feature = "ron"))]
+    #[test]
+    fn configs_deserialize_in_every_format() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Config {
+            name: String,
+            volume: f32,
+        }
+        
+        let root = test_root("formats");
+        fs::write(root.join("config.json"), r#"{ "name": "gem", "volume": 0.5 }"#).unwrap();
+        fs::write(root.join("config.toml"), "name = \"gem\"\nvolume = 0.5\n").unwrap();
+        fs::write(root.join("config.ron"), r#"(name: "gem", volume: 0.5)"#).unwrap();
+        fs::write(root.join("broken.json"), r#"{ "name": "#).unwrap();
+        let gfs = GemFileSystem::new(&root);
+        let expected = Config { name: String::from("gem"), volume: 0.5 };
+        assert_eq!(gfs.read_json::<Config, _>("config.json").unwrap(), expected);
+        assert_eq!(gfs.read_toml::<Config, _>("config.toml").unwrap(), expected);
+        assert_eq!(gfs.read_ron::<Config, _>("config.ron").unwrap(), expected);
+        assert!(matches!(gfs.read_json::<Config, _>("broken.json"), Err(GfsError::Parse { format: "JSON", .. })));
+        assert!(gfs.cache().content_map.contains_key(path::Path::new("config.json")));
+    }
+    
+    #[test]
+    fn metadata_leaves_files_unread() {
+        let root = test_root("metadata");
+        fs::create_dir_all(root.join("levels")).unwrap();
+        fs::write(root.join("levels/one.map"), vec![0u8; 4096]).unwrap();
+        let backend = MemoryBackend::new();
+        backend.insert("music/theme.ogg", vec![0u8; 100]).unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.mount_backend("memory", backend, 0);
+        
+        let meta = gfs.metadata("levels/one.map").unwrap();
+        assert_eq!((meta.size, meta.is_dir, meta.readonly), (4096, false, false));
+        assert_eq!(meta.modified, fs::metadata(root.join("levels/one.map")).unwrap().modified().ok());
+        assert!(gfs.metadata("levels").unwrap().is_dir);
+        let meta = gfs.metadata("music/theme.ogg").unwrap();
+        assert_eq!((meta.size, meta.is_dir, meta.readonly), (100, false, true));
+        assert!(gfs.metadata("music").unwrap().is_dir);
+        assert!(matches!(gfs.metadata("levels/two.map"), Err(GfsError::NotFound { .. })));
+        assert_eq!(gfs.cache().content_map.len(), 0);
+    }
+    
+    #[test]
+    fn existence_is_told_without_reading() {
+        let root = test_root("exists");
+        fs::write(root.join("a.txt"), b"hello").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        assert!(gfs.exists("a.txt"));
+        assert!(!gfs.exists("b.txt"));
+        assert!(!gfs.exists("../a.txt"));
+        assert!(!gfs.is_cached("a.txt"));
+        assert_eq!(gfs.cached_size("a.txt"), None);
+        
+        gfs.read_file("./a.txt").unwrap();
+        assert!(gfs.is_cached("a.txt"));
+        assert_eq!(gfs.cached_size("a.txt"), Some(5));
+        fs::remove_file(root.join("a.txt")).unwrap();
+        // still cached, so still there for `read_file`
+        assert!(gfs.exists("a.txt"));
+    }
+    
+    #[test]
+    fn small_buffers_read_whole_files() {
+        struct Trickle<'a>(&'a [u8]);
+        
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let n = 3.min(buf.len()).min(self.0.len());
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+        
+        let bytes: Vec<u8> = (0..100u8).collect();
+        assert_eq!(Sha256Hasher.hash(&mut Trickle(&bytes)).unwrap(), Sha256Hasher.hash_bytes(&bytes));
+        let root = test_root("buffer_size");
+        fs::write(root.join("a.bin"), &bytes).unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.buffer_size(7);
+        let mut sink = Vec::new();
+        assert_eq!(gfs.write_to("a.bin", &mut sink).unwrap(), 100);
+        assert_eq!(sink, bytes);
+        let mut streamed = Vec::new();
+        gfs.open("a.bin").unwrap().read_to_end(&mut streamed).unwrap();
+        assert_eq!(streamed, bytes);
+    }
+    
+    #[test]
+    fn evicted_files_spill_to_disk() {
+        let root = test_root("spill");
+        let spill = root.join("spill");
+        for name in ["a.bin", "b.bin", "c.bin"] {
+            fs::write(root.join(name), name.repeat(10)).unwrap();
+        }
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.cache_budget(Some(120)).spill_dir(Some(&spill));
+        let (_, token) = gfs.read_with_token("a.bin").unwrap();
+        gfs.read_file("b.bin").unwrap();
+        gfs.read_file("c.bin").unwrap();
+        assert!(!gfs.is_cached("a.bin"));
+        assert_eq!(fs::read_dir(&spill).unwrap().count(), 1);
+        
+        // gone at the source, but still there to take back
+        fs::remove_file(root.join("a.bin")).unwrap();
+        let disk_bytes = gfs.stats().bytes_read_from_disk;
+        assert_eq!(&gfs.read_file("a.bin").unwrap()[..], "a.bin".repeat(10).as_bytes());
+        assert_eq!(gfs.stats().bytes_read_from_disk, disk_bytes);
+        assert!(!gfs.has_changed("a.bin", token));
+        // b.bin went to make room
+        assert_eq!(fs::read_dir(&spill).unwrap().count(), 1);
+        gfs.evict("b.bin");
+        assert_eq!(fs::read_dir(&spill).unwrap().count(), 0);
+        
+        gfs.read_file("b.bin").unwrap();
+        assert_eq!(fs::read_dir(&spill).unwrap().count(), 1);
+        drop(gfs);
+        assert_eq!(fs::read_dir(&spill).unwrap().count(), 0);
+    }
+    
+    #[test]
+    fn file_systems_mix_behind_trait_objects() {
+        let root = test_root("dyn");
+        fs::write(root.join("a.txt"), b"on disk").unwrap();
+        let memory = MemoryBackend::new();
+        memory.insert("a.txt", b"in memory".to_vec()).unwrap();
+        let readers: Vec<Box<dyn DynReadFile>> = vec![Box::new(GemFileSystem::new(&root)), Box::new(memory)];
+        let read: Vec<_> = readers.iter().map(|reader| reader.read_path(path::Path::new("a.txt")).unwrap()).collect();
+        assert_eq!(&read[0][..], b"on disk");
+        assert_eq!(&read[1][..], b"in memory");
+        
+        let gfs: Box<dyn DynWriteFile> = Box::new(GemFileSystem::new(&root));
+        gfs.write_path(path::Path::new("b.txt"), b"written").unwrap();
+        gfs.rename_path(path::Path::new("b.txt"), path::Path::new("c.txt")).unwrap();
+        assert_eq!(fs::read(root.join("c.txt")).unwrap(), b"written");
+        let mapper: &dyn DynPathMapper = &GemFileSystem::new(&root);
+        assert_eq!(&*mapper.map_path(path::Path::new("c.txt")), root.join("c.txt"));
+    }
+    
+    #[test]
+    fn file_hashes_carry_their_digest() {
+        let root = test_root("file_hash");
+        fs::write(root.join("a.txt"), b"abc").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        assert_eq!(gfs.file_hash("a.txt"), None);
+        gfs.read_file("a.txt").unwrap();
+        let hash = gfs.file_hash("a.txt").unwrap();
+        assert_eq!(hash.algorithm(), "sha256");
+        assert_eq!(hash, Sha256Hasher.file_hash(&mut &b"abc"[..]).unwrap());
+        assert_eq!(hash.to_hex().len(), 64);
+        assert_eq!(hash.to_string(), format!("sha256:{}", hash.to_hex()));
+        assert_ne!(hash, FileHash::new("xxh64", hash.as_bytes()));
+    }
+    
+    #[test]
+    fn disabled_sync_hashes_nothing() {
+        let root = test_root("sync_disabled");
+        fs::write(root.join("a.txt"), b"shipped").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.sync_policy(SyncPolicy::Disabled);
+        assert_eq!(&gfs.read_file("a.txt").unwrap()[..], b"shipped");
+        gfs.write_file("b.txt", b"saved").unwrap();
+        assert!(gfs.cache().sha2_map.is_empty());
+        assert!(matches!(gfs.check_for_sync_file("a.txt"), Err(GfsError::SyncDisabled { .. })));
+        assert!(matches!(gfs.reload_if_changed("a.txt"), Err(GfsError::SyncDisabled { .. })));
+        assert!(gfs.check_for_sync_all().is_empty());
+        fs::write(root.join("a.txt"), b"patched").unwrap();
+        assert_eq!(&gfs.read_file_synced("a.txt").unwrap()[..], b"shipped");
+    }
+    
+    #[test]
+    fn unchanged_files_are_not_hashed_again() {
+        let root = test_root("mtime");
+        fs::write(root.join("a.txt"), b"first").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.read_file("a.txt").unwrap();
+        let modified = fs::metadata(root.join("a.txt")).unwrap().modified().unwrap();
+        // same size and modification time, as a tool restoring timestamps would leave it
+        fs::write(root.join("a.txt"), b"other").unwrap();
+        fs::File::options().write(true).open(root.join("a.txt")).unwrap().set_modified(modified).unwrap();
+        assert_eq!(gfs.check_for_sync_file("a.txt").unwrap(), FileSyncState::HashMatch);
+        gfs.paranoid_sync(true);
+        assert_eq!(gfs.check_for_sync_file("a.txt").unwrap(), FileSyncState::HashUnmatch);
+        
+        gfs.paranoid_sync(false);
+        fs::write(root.join("a.txt"), b"longer now").unwrap();
+        assert_eq!(gfs.check_for_sync_file("a.txt").unwrap(), FileSyncState::HashUnmatch);
+    }
+    
+    #[test]
+    fn write_to_copies_into_sink() {
+        let root = test_root("write_to");
+        fs::write(root.join("cached.bin"), b"from cache").unwrap();
+        fs::write(root.join("streamed.bin"), b"from disk").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        gfs.read_file("cached.bin").unwrap();
+        
+        let mut sink = Vec::new();
+        assert_eq!(gfs.write_to("cached.bin", &mut sink).unwrap(), 10);
+        assert_eq!(gfs.write_to("streamed.bin", &mut sink).unwrap(), 9);
+        assert_eq!(&sink[..], b"from cachefrom disk");
+        assert!(!gfs.cache().content_map.contains_key(path::Path::new("streamed.bin")));
+        assert!(gfs.write_to("missing.bin", &mut sink).is_err());
+    }
+    
+    #[test]
+    fn foreign_hash_algorithm_is_detected() {
+        let gfs = GemFileSystem::new(test_root("hash_algorithm"));
+        assert!(gfs.check_hash_algorithm().is_ok());
+        
+        let mut foreign = cache_with(&[("wood.png", b"wood", 0)]);
+        foreign.hash_algorithm = String::from("blake3");
+        gfs.merge_cache(cache_with(&[]), MergePolicy::Error).unwrap();
+        assert!(matches!(gfs.merge_cache(foreign, MergePolicy::PreferOther),
+                         Err(GfsError::HashAlgorithmMismatch { .. })));
+        
+        let mut foreign = Cache::new();
+        foreign.hash_algorithm = String::from("blake3");
+        gfs.swap_cache(foreign);
+        match gfs.check_hash_algorithm() {
+            Err(GfsError::HashAlgorithmMismatch { expected, found }) => {
+                assert_eq!(expected, "sha256");
+                assert_eq!(found, "blake3");
+            }
+            other => panic!("expected a hash algorithm mismatch, got {:?}", other),
+        }
+    }
+    
+    /// a deliberately weak digest, only the length of the content
+    struct LengthHasher;
+    
+    impl ContentHasher for LengthHasher {
+        fn name(&self) -> &'static str {
+            "length"
+        }
+        
+        fn hash(&self, reader: &mut dyn Read) -> io::Result<Vec<u8>> {
+            let mut content = Vec::new();
+            reader.read_to_end(&mut content)?;
+            Ok((content.len() as u64).to_le_bytes().to_vec())
+        }
+    }
+    
+    #[test]
+    fn hasher_is_pluggable() {
+        let root = test_root("hasher");
+        fs::write(root.join("mesh.bin"), b"abc").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.read_file("mesh.bin").unwrap();
+        gfs.set_hasher(LengthHasher);
+        assert_eq!(gfs.cache().hash_algorithm, "length");
+        assert!(gfs.cache().sha2_map.is_empty());
+        assert!(gfs.check_hash_algorithm().is_ok());
+        
+        assert_eq!(gfs.check_for_sync_file("mesh.bin").unwrap(), FileSyncState::HashMatch);
+        assert_eq!(&gfs.cache().sha2_map[path::Path::new("mesh.bin")][..], &3u64.to_le_bytes()[..]);
+        // same length, so this digest can't tell
+        fs::write(root.join("mesh.bin"), b"xyz").unwrap();
+        assert_eq!(gfs.check_for_sync_file("mesh.bin").unwrap(), FileSyncState::HashMatch);
+        fs::write(root.join("mesh.bin"), b"longer").unwrap();
+        assert_eq!(gfs.check_for_sync_file("mesh.bin").unwrap(), FileSyncState::HashUnmatch);
+    }
+    
+    #[test]
+    fn hashing_can_be_deferred() {
+        let root = test_root("hash_timing");
+        fs::write(root.join("lazy.bin"), b"lazy").unwrap();
+        fs::write(root.join("background.bin"), b"background").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        
+        gfs.hash_timing(HashTiming::Lazy);
+        gfs.read_file("lazy.bin").unwrap();
+        assert!(!gfs.cache().sha2_map.contains_key(path::Path::new("lazy.bin")));
+        assert_eq!(gfs.check_for_sync_file("lazy.bin").unwrap(), FileSyncState::HashMatch);
+        assert!(gfs.cache().sha2_map.contains_key(path::Path::new("lazy.bin")));
+        
+        gfs.hash_timing(HashTiming::Background);
+        gfs.read_file("background.bin").unwrap();
+        gfs.flush().unwrap();
+        assert_eq!(&gfs.cache().sha2_map[path::Path::new("background.bin")][..],
+                   &Sha256Hasher.hash_bytes(b"background")[..]);
+    }
+    
+    /// counts the bytes it hashes, to tell how often content went through it
+    #[derive(Clone, Default)]
+    struct CountingHasher(Arc<AtomicU64>);
+    
+    impl ContentHasher for CountingHasher {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+        
+        fn hash(&self, reader: &mut dyn Read) -> io::Result<Vec<u8>> {
+            let mut content = Vec::new();
+            reader.read_to_end(&mut content)?;
+            self.0.fetch_add(content.len() as u64, Ordering::Relaxed);
+            Ok(content.iter().map(|&byte| byte as u64).sum::<u64>().to_le_bytes().to_vec())
+        }
+    }
+    
+    #[test]
+    fn files_are_hashed_as_they_are_read() {
+        let root = test_root("stream_hash");
+        fs::write(root.join("level.bin"), vec![7u8; 10_000]).unwrap();
+        let hasher = CountingHasher::default();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.set_hasher(hasher.clone());
+        gfs.buffer_size(256);
+        assert_eq!(gfs.read_file("level.bin").unwrap().len(), 10_000);
+        assert_eq!(hasher.0.load(Ordering::Relaxed), 10_000);
+        assert_eq!(gfs.file_hash("level.bin").unwrap().as_bytes(), &70_000u64.to_le_bytes()[..]);
+        
+        gfs.paranoid_sync(true);
+        assert_eq!(gfs.check_for_sync_file("level.bin").unwrap(), FileSyncState::HashMatch);
+        assert_eq!(hasher.0.load(Ordering::Relaxed), 20_000);
+        fs::write(root.join("level.bin"), vec![8u8; 10_000]).unwrap();
+        assert_eq!(gfs.check_for_sync_file("level.bin").unwrap(), FileSyncState::HashUnmatch);
+        fs::remove_file(root.join("level.bin")).unwrap();
+        assert!(gfs.check_for_sync_file("level.bin").is_err());
+    }
+    
+    #[test]
+    fn cache_hits_stay_in_memory() {
+        let root = test_root("cache_hit");
+        fs::write(root.join("mesh.bin"), b"v1").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        gfs.read_file("mesh.bin").unwrap();
+        
+        fs::write(root.join("mesh.bin"), b"v2").unwrap();
+        assert_eq!(&gfs.read_file("mesh.bin").unwrap()[..], b"v1");
+        assert_eq!(gfs.stats().bytes_read_from_disk, 2);
+        assert_eq!(&gfs.force_refresh("mesh.bin").unwrap()[..], b"v2");
+        assert_eq!(&gfs.read_file("mesh.bin").unwrap()[..], b"v2");
+        
+        fs::remove_file(root.join("mesh.bin")).unwrap();
+        assert!(matches!(gfs.force_refresh("mesh.bin"), Err(GfsError::NotFound { .. })));
+    }
+    
+    #[test]
+    fn fallback_roots_are_tried_last() {
+        let primary = test_root("fallback_primary");
+        let shared = test_root("fallback_shared");
+        let uncached = test_root("fallback_uncached");
+        fs::write(primary.join("rock.png"), b"local").unwrap();
+        fs::write(shared.join("rock.png"), b"shared").unwrap();
+        fs::write(shared.join("tree.png"), b"shared").unwrap();
+        fs::write(uncached.join("bush.png"), b"remote").unwrap();
+        let mut gfs = GemFileSystem::new(&primary);
+        gfs.add_fallback_root(&shared, true);
+        gfs.add_fallback_root(&uncached, false);
+        
+        assert_eq!(&gfs.read_file("rock.png").unwrap()[..], b"local");
+        assert_eq!(&gfs.read_file("tree.png").unwrap()[..], b"shared");
+        assert!(gfs.cache().content_map.contains_key(path::Path::new("tree.png")));
+        assert_eq!(&gfs.read_file("bush.png").unwrap()[..], b"remote");
+        assert!(!gfs.cache().content_map.contains_key(path::Path::new("bush.png")));
+        assert_eq!(&*gfs.map("bush.png"), &*uncached.join("bush.png"));
+        assert!(gfs.read_file("missing.png").is_err());
+    }
+    
+    #[test]
+    fn mounts_shadow_by_priority() {
+        let base = test_root("mount_base");
+        let dlc = test_root("mount_dlc");
+        let mods = test_root("mount_mods");
+        let shared = test_root("mount_shared");
+        fs::write(base.join("sword.obj"), b"base").unwrap();
+        fs::write(base.join("shield.obj"), b"base").unwrap();
+        fs::write(dlc.join("sword.obj"), b"dlc").unwrap();
+        fs::write(dlc.join("horse.obj"), b"dlc").unwrap();
+        fs::write(mods.join("sword.obj"), b"mod").unwrap();
+        fs::write(mods.join("horse.obj"), b"mod").unwrap();
+        fs::write(shared.join("shield.obj"), b"shared").unwrap();
+        let mut gfs = GemFileSystem::new(&base);
+        gfs.mount(&shared, -1).mount(&dlc, 0).mount(&mods, 0);
+        
+        // the later mount of the same priority wins, root loses to both
+        assert_eq!(&gfs.read_file("sword.obj").unwrap()[..], b"mod");
+        assert_eq!(&gfs.read_file("horse.obj").unwrap()[..], b"mod");
+        assert_eq!(&gfs.read_file("shield.obj").unwrap()[..], b"base");
+        
+        assert!(gfs.unmount(&mods));
+        assert!(!gfs.unmount(&mods));
+        gfs.clear();
+        assert_eq!(&gfs.read_file("sword.obj").unwrap()[..], b"dlc");
+        gfs.mount(&mods, -2);
+        assert_eq!(&*gfs.map("horse.obj"), &*dlc.join("horse.obj"));
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn dangling_symlink_is_reported() {
+        let root = test_root("broken_symlink");
+        std::os::unix::fs::symlink("textures/deleted.png", root.join("wood.png")).unwrap();
+        let gfs = GemFileSystem::new(&root);
+        
+        match gfs.read_file("wood.png") {
+            Err(GfsError::BrokenSymlink { link, target }) => {
+                assert_eq!(link, root.join("wood.png"));
+                assert_eq!(target, path::Path::new("textures/deleted.png"));
+            }
+            other => panic!("expected a broken symlink error, got {:?}", other),
+        }
+        assert!(matches!(gfs.read_file("missing.png"), Err(GfsError::NotFound { .. })));
+        // still usable by code built around io::Error
+        let err = io::Error::from(gfs.read_file("missing.png").unwrap_err());
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(matches!(err.get_ref().and_then(|err| err.downcast_ref::<GfsError>()),
+                         Some(GfsError::NotFound { .. })));
+    }
+    
+    #[test]
+    fn backing_status_follows_disk() {
+        let root = test_root("backing_status");
+        fs::write(root.join("level.map"), b"v1").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        assert_eq!(gfs.backing_status("level.map"), None);
+        gfs.read_file("level.map").unwrap();
+        
+        let status = gfs.backing_status("level.map").unwrap();
+        assert_eq!(status.resolved, Some(root.join("level.map")));
+        assert!(!status.stale);
+        
+        fs::write(root.join("level.map"), b"v2, longer").unwrap();
+        assert!(gfs.backing_status("level.map").unwrap().stale);
+        
+        fs::remove_dir_all(&root).unwrap();
+        let status = gfs.backing_status("level.map").unwrap();
+        assert_eq!(status.resolved, None);
+        assert!(status.stale);
+    }
+    
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn compressed_cache_round_trips() {
+        let root = test_root("compress_cache");
+        // a flat-colored texture compresses very well
+        let content = vec![0x7fu8; 64 * 1024];
+        fs::write(root.join("flat.tex"), &content).unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.compress_cache(true);
+        
+        assert_eq!(&gfs.read_file("flat.tex").unwrap()[..], &content[..]);
+        assert!(gfs.cache().current_bytes() < content.len());
+        assert!(gfs.cache().compression_ratio() > 1.0);
+        assert!(matches!(gfs.check_for_sync_file("flat.tex").unwrap(), FileSyncState::HashMatch));
+    }
+    
+    #[cfg(feature = "diagnostics")]
+    #[test]
+    fn recent_errors_keeps_failures_in_order() {
+        let root = test_root("recent_errors");
+        let gfs = GemFileSystem::new(&root);
+        assert!(gfs.read_file("missing_a.png").is_err());
+        assert!(gfs.read_file("missing_b.png").is_err());
+        
+        let failed: Vec<PathBuf> = gfs.recent_errors().into_iter().map(|(path, _)| path).collect();
+        assert_eq!(failed, vec![PathBuf::from("missing_a.png"), PathBuf::from("missing_b.png")]);
+    }
+    
+    #[cfg(feature = "serde")]
+    #[test]
+    fn state_can_be_dumped_and_read_back() {
+        fn dumpable<T: serde::Serialize + serde::de::DeserializeOwned>() {}
+        dumpable::<HashMap<PathBuf, FileMeta>>();
+        dumpable::<CacheStats>();
+        dumpable::<Vec<DirEntry>>();
+        dumpable::<HashMap<PathBuf, FileSyncState>>();
+        dumpable::<BackingStatus>();
+        dumpable::<Manifest>();
+        dumpable::<ChangeSet>();
+        dumpable::<HashMap<PathBuf, ManifestCheck>>();
+        dumpable::<BackendMeta>();
     }
 }
@@ -0,0 +1,329 @@
+use std::{
+    cmp::Ordering,
+    collections::{
+        BinaryHeap,
+        HashMap,
+    },
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering as AtomicOrdering,
+        },
+        Arc,
+        Condvar,
+        Mutex,
+        Weak,
+    },
+    thread,
+};
+
+use crate::{
+    lock,
+    FileContent,
+    GemFileSystem,
+    GfsError,
+    ReadFile,
+};
+
+type OnDone = Box<dyn FnOnce(Result<FileContent, GfsError>) + Send>;
+
+/// how urgently `Loader::request` needs a file. higher priorities are loaded first,
+/// requests of the same priority in the order they were made
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+}
+
+/// hands out the result of a `Loader::request` through `Loader::poll`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LoadTicket(u64);
+
+/// gives up on the loads it is handed to, whether they are still queued or already being
+/// read, see `Loader::request_cancellable` and `GemFileSystem::read_file_cancellable`. clones
+/// share one state, so e.g. every request of a level is cancelled at once when it is left
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+    
+    pub fn cancel(&self) {
+        self.0.store(true, AtomicOrdering::Relaxed);
+    }
+    
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(AtomicOrdering::Relaxed)
+    }
+}
+
+/// loads requested files into the cache on worker threads of its own, see
+/// `GemFileSystem::loader`. requests that haven't started yet are dropped with it
+pub struct Loader {
+    shared: Arc<Shared>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+}
+
+struct Queue {
+    jobs: BinaryHeap<Job>,
+    done: HashMap<LoadTicket, Result<FileContent, GfsError>>,
+    next_ticket: u64,
+    stopped: bool,
+}
+
+struct Job {
+    priority: Priority,
+    ticket: LoadTicket,
+    file_path: PathBuf,
+    on_done: Option<OnDone>,
+    cancel: Option<CancellationToken>,
+}
+
+impl GemFileSystem {
+    /// start a `Loader` with `threads` workers. like a watcher it only holds a weak
+    /// reference, so it never keeps the filesystem alive
+    pub fn loader(self: &Arc<GemFileSystem>, threads: usize) -> io::Result<Loader> {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                jobs: BinaryHeap::new(),
+                done: HashMap::new(),
+                next_ticket: 0,
+                stopped: false,
+            }),
+            ready: Condvar::new(),
+        });
+        let mut loader = Loader {
+            shared,
+            workers: Vec::new(),
+        };
+        for _ in 0..threads.max(1) {
+            let shared = Arc::clone(&loader.shared);
+            let gfs = Arc::downgrade(self);
+            // pushed one by one, so the workers already running are stopped if this fails
+            let worker = thread::Builder::new()
+                .name(String::from("gfs-loader"))
+                .spawn(move || work(&shared, &gfs))?;
+            loader.workers.push(worker);
+        }
+        Ok(loader)
+    }
+}
+
+impl Loader {
+    /// queue `file_path` to be read into the cache, its result kept for `poll`
+    pub fn request<P: AsRef<Path>>(&self, file_path: P, priority: Priority) -> LoadTicket {
+        self.queue(file_path.as_ref(), priority, None, None)
+    }
+    
+    /// same as `request`, but the load is given up on once `cancel` is cancelled, see
+    /// `GemFileSystem::read_file_cancellable`. `poll` then hands out `GfsError::Cancelled`
+    pub fn request_cancellable<P: AsRef<Path>>(&self, file_path: P, priority: Priority, cancel: &CancellationToken)
+        -> LoadTicket {
+        self.queue(file_path.as_ref(), priority, None, Some(cancel.clone()))
+    }
+    
+    /// same as `request`, but hands the result to `on_done` on the worker thread instead of
+    /// keeping it for `poll`
+    pub fn request_with<P, F>(&self, file_path: P, priority: Priority, on_done: F) -> LoadTicket
+        where P: AsRef<Path>,
+              F: FnOnce(Result<FileContent, GfsError>) + Send + 'static {
+        self.queue(file_path.as_ref(), priority, Some(Box::new(on_done)), None)
+    }
+    
+    /// the result of a `request` once it is loaded, handed out only once
+    pub fn poll(&self, ticket: LoadTicket) -> Option<Result<FileContent, GfsError>> {
+        lock(&self.shared.queue).done.remove(&ticket)
+    }
+    
+    /// how many requests haven't been picked up by a worker yet
+    pub fn pending(&self) -> usize {
+        lock(&self.shared.queue).jobs.len()
+    }
+    
+    fn queue(&self, file_path: &Path, priority: Priority, on_done: Option<OnDone>, cancel: Option<CancellationToken>)
+        -> LoadTicket {
+        let mut queue = lock(&self.shared.queue);
+        let ticket = LoadTicket(queue.next_ticket);
+        queue.next_ticket += 1;
+        queue.jobs.push(Job {
+            priority,
+            ticket,
+            file_path: file_path.to_path_buf(),
+            on_done,
+            cancel,
+        });
+        self.shared.ready.notify_one();
+        ticket
+    }
+}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        lock(&self.shared.queue).stopped = true;
+        self.shared.ready.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work(shared: &Shared, gfs: &Weak<GemFileSystem>) {
+    loop {
+        let job = {
+            let mut queue = lock(&shared.queue);
+            loop {
+                if queue.stopped {
+                    return;
+                }
+                if let Some(job) = queue.jobs.pop() {
+                    break job;
+                }
+                queue = shared.ready.wait(queue).unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+        };
+        let result = match gfs.upgrade() {
+            Some(gfs) => match &job.cancel {
+                Some(cancel) => gfs.read_file_cancellable(&job.file_path, cancel),
+                None => gfs.read_file(&job.file_path),
+            },
+            None => return,
+        };
+        match job.on_done {
+            Some(on_done) => on_done(result),
+            None => {
+                lock(&shared.queue).done.insert(job.ticket, result);
+            }
+        }
+    }
+}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Job) -> Ordering {
+        // the heap pops the greatest job, so earlier tickets must compare greater
+        self.priority.cmp(&other.priority).then_with(|| other.ticket.0.cmp(&self.ticket.0))
+    }
+}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Job) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Job) -> bool {
+        self.ticket == other.ticket
+    }
+}
+
+impl Eq for Job {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_root;
+    use std::{
+        fs,
+        sync::mpsc,
+        time::Duration,
+    };
+    
+    #[test]
+    fn urgent_requests_jump_the_queue() {
+        let root = test_root("loader");
+        for name in &["first", "near", "far", "next", "polled"] {
+            fs::write(root.join(name), name).unwrap();
+        }
+        let gfs = Arc::new(GemFileSystem::new(&root));
+        let loader = gfs.loader(1).unwrap();
+    
+        // keep the only worker busy until everything else is queued
+        let (release, released) = mpsc::channel::<()>();
+        let (busy, started) = mpsc::channel::<()>();
+        let (loaded, order) = mpsc::channel::<String>();
+        let first = loaded.clone();
+        loader.request_with("first", Priority::Normal, move |_| {
+            busy.send(()).unwrap();
+            released.recv().unwrap();
+            first.send(String::from("first")).unwrap();
+        });
+        started.recv().unwrap();
+        for (name, priority) in &[("far", Priority::Low), ("next", Priority::Normal), ("near", Priority::High)] {
+            let loaded = loaded.clone();
+            loader.request_with(*name, *priority, move |content| {
+                loaded.send(String::from_utf8(content.unwrap().to_vec()).unwrap()).unwrap();
+            });
+        }
+        release.send(()).unwrap();
+        let order: Vec<String> = order.iter().take(4).collect();
+        assert_eq!(order, ["first", "near", "next", "far"]);
+    
+        let ticket = loader.request("polled", Priority::Normal);
+        let mut polled = None;
+        while polled.is_none() {
+            thread::sleep(Duration::from_millis(1));
+            polled = loader.poll(ticket);
+        }
+        assert_eq!(&polled.unwrap().unwrap()[..], b"polled");
+        assert!(loader.poll(ticket).is_none());
+        drop(loader);
+        assert!(gfs.cache().content_map.contains_key(Path::new("far")));
+    }
+    
+    #[test]
+    fn cancelled_loads_are_dropped() {
+        let root = test_root("cancel");
+        for name in &["busy", "kept", "left"] {
+            fs::write(root.join(name), name).unwrap();
+        }
+        fs::write(root.join("streamed.bin"), vec![0u8; crate::BUFFER_SIZE * 4]).unwrap();
+        let gfs = Arc::new(GemFileSystem::new(&root));
+        let loader = gfs.loader(1).unwrap();
+        
+        let (release, released) = mpsc::channel::<()>();
+        let (busy, started) = mpsc::channel::<()>();
+        loader.request_with("busy", Priority::High, move |_| {
+            busy.send(()).unwrap();
+            released.recv().unwrap();
+        });
+        started.recv().unwrap();
+        let level = CancellationToken::new();
+        let left = loader.request_cancellable("left", Priority::Normal, &level);
+        let kept = loader.request_cancellable("kept", Priority::Normal, &CancellationToken::new());
+        level.cancel();
+        release.send(()).unwrap();
+        let mut results = (None, None);
+        while results.0.is_none() || results.1.is_none() {
+            thread::sleep(Duration::from_millis(1));
+            results.0 = results.0.or_else(|| loader.poll(left));
+            results.1 = results.1.or_else(|| loader.poll(kept));
+        }
+        assert!(matches!(results.0, Some(Err(GfsError::Cancelled { .. }))));
+        assert_eq!(&results.1.unwrap().unwrap()[..], b"kept");
+        assert!(!gfs.cache().content_map.contains_key(Path::new("left")));
+        
+        // given up on in between two buffers
+        let streamed = CancellationToken::new();
+        let tracking = crate::Tracking {
+            progress: Some(&|done, _| if done > 0 { streamed.cancel() }),
+            cancel: Some(&streamed),
+            ..crate::Tracking::default()
+        };
+        let read = gfs.read_reported(Path::new("streamed.bin"), crate::ReadOptions::default(), tracking);
+        assert!(matches!(read, Err(GfsError::Cancelled { .. })));
+        assert!(!gfs.cache().content_map.contains_key(Path::new("streamed.bin")));
+    }
+}
@@ -0,0 +1,355 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs,
+    io::{
+        self,
+        Read,
+    },
+    path::PathBuf,
+};
+use std::path::Path;
+
+#[cfg(feature = "signing")]
+use ed25519_dalek::{
+    Signature,
+    Signer,
+    SigningKey,
+    Verifier,
+    VerifyingKey,
+};
+
+use crate::{
+    ContentHasher,
+    GfsError,
+    Sha256Hasher,
+};
+
+const HEADER: &str = "gfs-manifest 1";
+const VERSION_PREFIX: &str = "version ";
+#[cfg(feature = "signing")]
+const SIGNATURE_PREFIX: &str = "signature ";
+
+/// the SHA-256 and size of every file of an installation, keyed like the cache.
+/// written out as text, a `version <version>` line followed by one
+/// `<sha256 in hex> <size> <path>` line per file
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Manifest {
+    /// of the content, e.g. a build number, bumped by whoever publishes it.
+    /// 0 for manifests written before they had one
+    pub version: u64,
+    pub entries: BTreeMap<PathBuf, ManifestEntry>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManifestEntry {
+    pub sha256: [u8; 32],
+    pub size: u64,
+}
+
+/// how a file compares to its manifest entry, see `GemFileSystem::verify_manifest`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ManifestCheck {
+    Match,
+    /// the file exists, but its hash or size differs, or it can't be read
+    Mismatch,
+    Missing,
+}
+
+/// which files differ between two manifests, see `diff_manifests`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChangeSet {
+    /// only in the new manifest
+    pub added: Vec<PathBuf>,
+    /// only in the old manifest
+    pub removed: Vec<PathBuf>,
+    /// in both, with a different hash or size
+    pub modified: Vec<PathBuf>,
+}
+
+impl ChangeSet {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+    
+    /// the files an update from the old manifest to the new one has to download
+    pub fn to_download(&self) -> impl Iterator<Item = &PathBuf> {
+        self.added.iter().chain(&self.modified)
+    }
+}
+
+/// what changed from `old` to `new`, in path order. the versions of the manifests
+/// are not compared, only their entries
+pub fn diff_manifests(old: &Manifest, new: &Manifest) -> ChangeSet {
+    let mut changes = ChangeSet::default();
+    for (path, entry) in &new.entries {
+        match old.entries.get(path) {
+            None => changes.added.push(path.clone()),
+            Some(old_entry) if old_entry != entry => changes.modified.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+    changes.removed = old.entries.keys()
+        .filter(|path| !new.entries.contains_key(*path))
+        .cloned()
+        .collect();
+    changes
+}
+
+impl ManifestEntry {
+    /// hash and measure a file on disk
+    pub(crate) fn of_file(absolute_path: &Path) -> io::Result<ManifestEntry> {
+        let mut counted = Counted {
+            inner: fs::File::open(absolute_path)?,
+            count: 0,
+        };
+        let mut sha256 = [0u8; 32];
+        sha256.copy_from_slice(&Sha256Hasher.hash(&mut counted)?);
+        Ok(ManifestEntry {
+            sha256,
+            size: counted.count,
+        })
+    }
+}
+
+impl Manifest {
+    pub fn new() -> Manifest {
+        Manifest::default()
+    }
+    
+    /// read a manifest back from what its `Display` impl wrote
+    pub fn parse(text: &str) -> Result<Manifest, GfsError> {
+        let mut lines = text.lines().peekable();
+        if lines.next() != Some(HEADER) {
+            return Err(invalid_manifest(String::from("Not a gfs manifest")));
+        }
+        let version = match lines.peek().and_then(|line| line.strip_prefix(VERSION_PREFIX)) {
+            Some(version) => {
+                let version = version.parse().map_err(|_| {
+                    let mut err = String::from("Malformed manifest version: ");
+                    err.push_str(&format!("{:#?}", version));
+                    invalid_manifest(err)
+                })?;
+                lines.next();
+                version
+            }
+            None => 0,
+        };
+        let mut entries = BTreeMap::new();
+        for line in lines.filter(|line| !line.is_empty()) {
+            let mut fields = line.splitn(3, ' ');
+            let entry = match (fields.next(), fields.next(), fields.next()) {
+                (Some(sha256), Some(size), Some(path)) => from_hex::<32>(sha256)
+                    .and_then(|sha256| Some((sha256, size.parse().ok()?, path))),
+                _ => None,
+            };
+            match entry {
+                Some((sha256, size, path)) => {
+                    entries.insert(PathBuf::from(path), ManifestEntry { sha256, size });
+                }
+                None => {
+                    let mut err = String::from("Malformed manifest line: ");
+                    err.push_str(&format!("{:#?}", line));
+                    return Err(invalid_manifest(err));
+                }
+            }
+        }
+        Ok(Manifest {
+            version,
+            entries,
+        })
+    }
+    
+    /// the manifest as text followed by a line with its ed25519 signature made with
+    /// `secret_key`, as `GemFileSystem::mount_verified` expects it
+    #[cfg(feature = "signing")]
+    pub fn sign(&self, secret_key: &[u8; 32]) -> String {
+        let text = self.to_string();
+        let signature = SigningKey::from_bytes(secret_key).sign(text.as_bytes());
+        format!("{}{}{}\n", text, SIGNATURE_PREFIX, to_hex(&signature.to_bytes()))
+    }
+    
+    /// parse a manifest written by `sign`, failing unless it was signed with the secret key
+    /// belonging to `public_key`. `path` is only used to report a bad signature
+    #[cfg(feature = "signing")]
+    pub fn verify(signed: &str, public_key: &[u8; 32], path: &Path) -> Result<Manifest, GfsError> {
+        let invalid = || GfsError::InvalidSignature { path: path.to_path_buf() };
+        // the signature covers everything up to its own line
+        let at = signed.trim_end_matches('\n').rfind('\n').map_or(0, |at| at + 1);
+        let (text, signature) = signed.split_at(at);
+        let signature = signature.trim_end()
+            .strip_prefix(SIGNATURE_PREFIX)
+            .and_then(from_hex::<64>)
+            .ok_or_else(invalid)?;
+        let public_key = VerifyingKey::from_bytes(public_key).map_err(|_| invalid())?;
+        public_key.verify(text.as_bytes(), &Signature::from_bytes(&signature)).map_err(|_| invalid())?;
+        Manifest::parse(text)
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "{}{}", VERSION_PREFIX, self.version)?;
+        for (path, entry) in &self.entries {
+            // keys always use `/`, whatever the platform
+            let path: Vec<_> = path.components().map(|component| component.as_os_str().to_string_lossy()).collect();
+            writeln!(f, "{} {} {}", to_hex(&entry.sha256), entry.size, path.join("/"))?;
+        }
+        Ok(())
+    }
+}
+
+/// counts what is read through it, so the size matches exactly the bytes that were hashed
+struct Counted<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+        Ok(read)
+    }
+}
+
+fn invalid_manifest(err: String) -> GfsError {
+    GfsError::Io(io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 {
+        return None;
+    }
+    let mut bytes = [0u8; N];
+    for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn entry(content: &[u8]) -> ManifestEntry {
+        let mut sha256 = [0u8; 32];
+        sha256.copy_from_slice(&Sha256Hasher.hash_bytes(content));
+        ManifestEntry {
+            sha256,
+            size: content.len() as u64,
+        }
+    }
+    
+    #[test]
+    fn manifests_round_trip() {
+        let mut manifest = Manifest::new();
+        manifest.entries.insert(PathBuf::from("models/chest.obj"), entry(b"chest"));
+        manifest.entries.insert(PathBuf::from("music/main theme.ogg"), entry(b""));
+        let text = manifest.to_string();
+        assert!(text.starts_with("gfs-manifest 1\n"));
+        assert_eq!(Manifest::parse(&text).unwrap(), manifest);
+        assert!(Manifest::parse("gfs-manifest 1\nabc 1 x\n").is_err());
+        assert!(Manifest::parse("").is_err());
+    }
+    
+    #[test]
+    fn updates_download_only_what_changed() {
+        let mut old = Manifest::new();
+        old.version = 41;
+        old.entries.insert(PathBuf::from("models/chest.obj"), entry(b"chest"));
+        old.entries.insert(PathBuf::from("models/lid.obj"), entry(b"lid"));
+        old.entries.insert(PathBuf::from("music/old theme.ogg"), entry(b"old"));
+        let mut new = old.clone();
+        new.version = 42;
+        new.entries.insert(PathBuf::from("models/lid.obj"), entry(b"bigger lid"));
+        new.entries.insert(PathBuf::from("music/new theme.ogg"), entry(b"new"));
+        new.entries.remove(Path::new("music/old theme.ogg"));
+        let parsed = Manifest::parse(&new.to_string()).unwrap();
+        assert_eq!(parsed.version, 42);
+        assert_eq!(Manifest::parse("gfs-manifest 1\n").unwrap().version, 0);
+        assert!(Manifest::parse("gfs-manifest 1\nversion next\n").is_err());
+    
+        let changes = diff_manifests(&old, &parsed);
+        assert_eq!(changes.added, vec![PathBuf::from("music/new theme.ogg")]);
+        assert_eq!(changes.removed, vec![PathBuf::from("music/old theme.ogg")]);
+        assert_eq!(changes.modified, vec![PathBuf::from("models/lid.obj")]);
+        assert_eq!(changes.to_download().count(), 2);
+        assert!(diff_manifests(&old, &old).is_empty());
+    }
+    
+    #[test]
+    fn exported_manifests_verify() {
+        use crate::{
+            tests::test_root,
+            GemFileSystem,
+        };
+    
+        let root = test_root("export");
+        fs::create_dir_all(root.join("models")).unwrap();
+        fs::write(root.join("models/chest.obj"), b"chest").unwrap();
+        fs::write(root.join("save.dat"), b"level 1").unwrap();
+        fs::write(root.join("gone.dat"), b"gone").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        let manifest = gfs.export_manifest().unwrap();
+        assert_eq!(manifest.entries[Path::new("models/chest.obj")], entry(b"chest"));
+        assert_eq!(manifest.entries.len(), 3);
+    
+        fs::write(root.join("save.dat"), b"level 2").unwrap();
+        fs::remove_file(root.join("gone.dat")).unwrap();
+        let checks = gfs.verify_manifest(&Manifest::parse(&manifest.to_string()).unwrap());
+        assert_eq!(checks[Path::new("models/chest.obj")], ManifestCheck::Match);
+        assert_eq!(checks[Path::new("save.dat")], ManifestCheck::Mismatch);
+        assert_eq!(checks[Path::new("gone.dat")], ManifestCheck::Missing);
+    }
+    
+    #[cfg(feature = "signing")]
+    #[test]
+    fn signed_manifests_verify() {
+        use crate::{
+            tests::test_root,
+            FileSyncState,
+            GemFileSystem,
+            ReadFile,
+        };
+        use std::fs;
+    
+        let secret_key = [3u8; 32];
+        let public_key = SigningKey::from_bytes(&secret_key).verifying_key().to_bytes();
+        let root = test_root("manifest");
+        let build = root.join("build");
+        fs::create_dir_all(&build).unwrap();
+        fs::write(build.join("game.dat"), b"original").unwrap();
+        fs::write(build.join("tampered.dat"), b"cheats").unwrap();
+        fs::write(build.join("unlisted.dat"), b"extra").unwrap();
+        let mut manifest = Manifest::new();
+        manifest.entries.insert(PathBuf::from("game.dat"), entry(b"original"));
+        manifest.entries.insert(PathBuf::from("tampered.dat"), entry(b"honest"));
+        let signed = manifest.sign(&secret_key);
+        fs::write(root.join("manifest.sig"), &signed).unwrap();
+        fs::write(root.join("forged.sig"), signed.replace("game.dat", "gamf.dat")).unwrap();
+    
+        assert_eq!(Manifest::verify(&signed, &public_key, Path::new("manifest.sig")).unwrap(), manifest);
+        let mut gfs = GemFileSystem::new(root.join("empty"));
+        assert!(matches!(gfs.mount_verified(&build, root.join("forged.sig"), &public_key, 0),
+                         Err(GfsError::InvalidSignature { .. })));
+        assert!(matches!(gfs.mount_verified(&build, root.join("manifest.sig"), &[0; 32], 0),
+                         Err(GfsError::InvalidSignature { .. })));
+        gfs.mount_verified(&build, root.join("manifest.sig"), &public_key, 0).unwrap();
+    
+        assert_eq!(&gfs.read_file("game.dat").unwrap()[..], b"original");
+        assert_eq!(gfs.check_for_sync_file("game.dat").unwrap(), FileSyncState::HashMatch);
+        assert!(matches!(gfs.read_file("tampered.dat"), Err(GfsError::HashMismatch { .. })));
+        assert!(matches!(gfs.read_file("unlisted.dat"), Err(GfsError::HashMismatch { .. })));
+        assert!(!gfs.cache().content_map.contains_key(Path::new("tampered.dat")));
+    }
+}
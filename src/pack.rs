@@ -0,0 +1,385 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{
+        self,
+        BufReader,
+        Read,
+        Seek,
+        SeekFrom,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        Mutex,
+        MutexGuard,
+    },
+    time::SystemTime,
+};
+
+use crate::{
+    lock,
+    ContentHasher,
+    FileContent,
+    FileMeta,
+    GfsError,
+};
+
+const MAGIC: &[u8; 8] = b"GFSPACK\0";
+const VERSION: u32 = 1;
+/// the most index entries reserved for before they are read
+const MAX_RESERVED: usize = 4096;
+
+/// where a file sits inside a pack, see `write_pack`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackEntry {
+    /// from the start of the pack file
+    pub offset: u64,
+    pub len: u64,
+    /// computed with `PackIndex::hash_algorithm`
+    pub hash: Box<[u8]>,
+}
+
+/// the index at the start of a pack, read in one go when the pack is mounted
+#[derive(Clone, Debug, Default)]
+pub struct PackIndex {
+    /// the `ContentHasher::name` the hashes were computed with
+    pub hash_algorithm: String,
+    pub entries: HashMap<PathBuf, PackEntry>,
+}
+
+impl PackIndex {
+    pub fn read<R: Read>(reader: &mut R) -> Result<PackIndex, GfsError> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_pack(String::from("Not a gfs pack")));
+        }
+        let version = read_u32(reader)?;
+        if version != VERSION {
+            let mut err = String::from("Unsupported gfs pack version: ");
+            err.push_str(&format!("{}", version));
+            return Err(invalid_pack(err));
+        }
+        let name_len = read_u8(reader)? as usize;
+        let hash_algorithm = read_string(reader, name_len)?;
+        let count = read_u32(reader)?;
+        // the count comes from the file, so it only hints at how much to reserve
+        let mut entries = HashMap::with_capacity((count as usize).min(MAX_RESERVED));
+        for _ in 0..count {
+            let path_len = read_u16(reader)? as usize;
+            let key = PathBuf::from(read_string(reader, path_len)?);
+            let offset = read_u64(reader)?;
+            let len = read_u64(reader)?;
+            let mut hash = vec![0u8; read_u8(reader)? as usize].into_boxed_slice();
+            reader.read_exact(&mut hash)?;
+            entries.insert(key, PackEntry { offset, len, hash });
+        }
+        Ok(PackIndex {
+            hash_algorithm,
+            entries,
+        })
+    }
+}
+
+/// pack every file under `dir` into `out`: an index of path, offset, length and hash, followed
+/// by the content of every file back to back. paths are stored relative to `dir` with `/`
+/// separators, the same keys `read_file` takes. returns how many files were packed
+pub fn write_pack<W: Write>(dir: &Path, out: &mut W, hasher: &dyn ContentHasher) -> Result<usize, GfsError> {
+    let mut files = Vec::new();
+    collect_files(dir, dir, &mut files)?;
+    files.sort();
+    
+    let mut hashed = Vec::with_capacity(files.len());
+    for (key, absolute_path) in files {
+        let len = fs::metadata(&absolute_path)?.len();
+        let hash = hasher.hash(&mut fs::File::open(&absolute_path)?)?;
+        hashed.push((key, absolute_path, len, hash));
+    }
+    let index_len = 8 + 4 + 1 + hasher.name().len() + 4 + hashed.iter()
+        .map(|(key, _, _, hash)| 2 + key.len() + 8 + 8 + 1 + hash.len())
+        .sum::<usize>();
+    
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&[hasher.name().len() as u8])?;
+    out.write_all(hasher.name().as_bytes())?;
+    out.write_all(&(hashed.len() as u32).to_le_bytes())?;
+    let mut offset = index_len as u64;
+    for (key, _, len, hash) in &hashed {
+        out.write_all(&(key.len() as u16).to_le_bytes())?;
+        out.write_all(key.as_bytes())?;
+        out.write_all(&offset.to_le_bytes())?;
+        out.write_all(&len.to_le_bytes())?;
+        out.write_all(&[hash.len() as u8])?;
+        out.write_all(hash)?;
+        offset += len;
+    }
+    for (_, absolute_path, len, _) in &hashed {
+        // the file may have changed since it was hashed, so copy exactly what the index says
+        let copied = io::copy(&mut fs::File::open(absolute_path)?.take(*len), out)?;
+        if copied != *len {
+            let mut err = String::from("File shrank while being packed: ");
+            err.push_str(&format!("{:#?}", absolute_path));
+            return Err(GfsError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, err)));
+        }
+    }
+    out.flush()?;
+    Ok(hashed.len())
+}
+
+pub(crate) fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> Result<(), GfsError> {
+    for entry in fs::read_dir(dir)? {
+        let absolute_path = entry?.path();
+        if absolute_path.is_dir() {
+            collect_files(root, &absolute_path, files)?;
+            continue;
+        }
+        let relative = match absolute_path.strip_prefix(root) {
+            Ok(relative) => relative,
+            Err(_) => continue,
+        };
+        let components: Option<Vec<&str>> = relative.components()
+            .map(|component| component.as_os_str().to_str())
+            .collect();
+        match components {
+            Some(components) => files.push((components.join("/"), absolute_path)),
+            None => {
+                let mut err = String::from("Path is not valid UTF-8, can't be packed: ");
+                err.push_str(&format!("{:#?}", absolute_path));
+                return Err(GfsError::Io(io::Error::new(io::ErrorKind::InvalidData, err)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// a pack mounted with `GemFileSystem::mount_pack`. like a zip mount it is opened again
+/// whenever its modification time changes
+pub(crate) struct PackMount {
+    pub(crate) path: PathBuf,
+    opened: Mutex<Opened>,
+}
+
+struct Opened {
+    file: fs::File,
+    modified: Option<SystemTime>,
+    index: PackIndex,
+}
+
+impl PackMount {
+    pub(crate) fn open(path: &Path) -> Result<PackMount, GfsError> {
+        Ok(PackMount {
+            path: path.to_path_buf(),
+            opened: Mutex::new(Opened::open(path)?),
+        })
+    }
+    
+    pub(crate) fn contains(&self, key: &Path) -> bool {
+        self.opened().index.entries.contains_key(key)
+    }
+    
+    /// every file in the pack with its size
+    pub(crate) fn files(&self) -> Vec<(PathBuf, u64)> {
+        self.opened().index.entries.iter().map(|(key, entry)| (key.clone(), entry.len)).collect()
+    }
+    
+    /// the hash the index has for an entry if it was computed with `hasher`, otherwise the
+    /// entry is read and hashed. None if there is no such entry
+    pub(crate) fn hash(&self, key: &Path, hasher: &dyn ContentHasher) -> Option<io::Result<Vec<u8>>> {
+        let mut opened = self.opened();
+        let entry = opened.index.entries.get(key)?.clone();
+        if opened.index.hash_algorithm == hasher.name() {
+            return Some(Ok(entry.hash.to_vec()));
+        }
+        let hash = opened.file.seek(SeekFrom::Start(entry.offset))
+            .and_then(|_| hasher.hash(&mut (&mut opened.file).take(entry.len)));
+        Some(hash)
+    }
+    
+    /// read the `(offset, len)` regions of an entry, None if there is no such entry.
+    /// a region running past the end of the entry fails before anything is allocated for it
+    pub(crate) fn read_regions(&self, key: &Path, regions: &[(u64, usize)]) -> Option<io::Result<Vec<Box<[u8]>>>> {
+        let mut opened = self.opened();
+        let entry = opened.index.entries.get(key)?.clone();
+        let read = regions.iter().map(|&(offset, len)| {
+            crate::check_region(offset, len, entry.len)?;
+            let mut region = vec![0u8; len].into_boxed_slice();
+            opened.file.seek(SeekFrom::Start(entry.offset + offset))?;
+            opened.file.read_exact(&mut region)?;
+            Ok(region)
+        }).collect();
+        Some(read)
+    }
+    
+    /// read an entry, None if there is no such entry
+    pub(crate) fn read(&self, key: &Path) -> Option<io::Result<(FileContent, FileMeta)>> {
+        let mut opened = self.opened();
+        let entry = opened.index.entries.get(key)?.clone();
+        let modified = opened.modified;
+        let mut content = vec![0u8; entry.len as usize];
+        let read = opened.file.seek(SeekFrom::Start(entry.offset))
+            .and_then(|_| opened.file.read_exact(&mut content))
+            .map(|_| {
+                let meta = FileMeta {
+                    modified,
+                    ..FileMeta::default()
+                };
+                (FileContent::from(content), meta)
+            });
+        Some(read)
+    }
+    
+    fn opened(&self) -> MutexGuard<'_, Opened> {
+        let mut opened = lock(&self.opened);
+        if modified(&self.path) != opened.modified {
+            match Opened::open(&self.path) {
+                Ok(reopened) => *opened = reopened,
+                Err(err) => warn!("failed to reopen {}: {}", self.path.display(), err),
+            }
+        }
+        opened
+    }
+}
+
+impl Opened {
+    fn open(path: &Path) -> Result<Opened, GfsError> {
+        let modified = modified(path);
+        let mut file = fs::File::open(path)?;
+        let size = file.metadata()?.len();
+        let index = PackIndex::read(&mut BufReader::new(&mut file))?;
+        // entries are read by allocating their length up front, so they have to fit in the pack
+        let outside = index.entries.iter()
+            .find(|(_, entry)| !matches!(entry.offset.checked_add(entry.len), Some(end) if end <= size));
+        if let Some((key, entry)) = outside {
+            let mut err = format!("Entry {} runs past the end of the pack: ", key.display());
+            err.push_str(&format!("{} bytes at {} of {}", entry.len, entry.offset, size));
+            return Err(invalid_pack(err));
+        }
+        Ok(Opened {
+            file,
+            modified,
+            index,
+        })
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn invalid_pack(err: String) -> GfsError {
+    GfsError::Io(io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub(crate) fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut bytes = [0u8; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0u8; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_string<R: Read>(reader: &mut R, len: usize) -> Result<String, GfsError> {
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| invalid_pack(String::from("Pack path is not valid UTF-8")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        FileSyncState,
+        GemFileSystem,
+        ReadFile,
+        Sha256Hasher,
+    };
+    use std::{
+        thread,
+        time::Duration,
+    };
+    
+    #[test]
+    fn packs_round_trip_and_sync() {
+        let root = test_root("pack");
+        let tree = root.join("tree");
+        fs::create_dir_all(tree.join("models")).unwrap();
+        fs::write(tree.join("models/chest.obj"), b"chest").unwrap();
+        fs::write(tree.join("models/lid.obj"), b"").unwrap();
+        fs::write(tree.join("readme.txt"), b"hello").unwrap();
+        let pack = root.join("assets.gfspack");
+        let count = write_pack(&tree, &mut fs::File::create(&pack).unwrap(), &Sha256Hasher).unwrap();
+        assert_eq!(count, 3);
+    
+        let index = PackIndex::read(&mut fs::File::open(&pack).unwrap()).unwrap();
+        assert_eq!(index.hash_algorithm, "sha256");
+        assert_eq!(index.entries[Path::new("models/chest.obj")].len, 5);
+        assert!(PackIndex::read(&mut &b"GFSPACK"[..]).is_err());
+    
+        let mut gfs = GemFileSystem::new(root.join("empty"));
+        gfs.mount_pack(&pack, 0).unwrap();
+        assert_eq!(&gfs.read_file("models/chest.obj").unwrap()[..], b"chest");
+        assert_eq!(&gfs.read_file("models/lid.obj").unwrap()[..], b"");
+        assert_eq!(&gfs.read_file("readme.txt").unwrap()[..], b"hello");
+        assert!(gfs.read_file("models").is_err());
+        assert_eq!(gfs.check_for_sync_file("models/chest.obj").unwrap(), FileSyncState::HashMatch);
+    
+        thread::sleep(Duration::from_millis(20));
+        fs::write(tree.join("models/chest.obj"), b"bigger chest").unwrap();
+        write_pack(&tree, &mut fs::File::create(&pack).unwrap(), &Sha256Hasher).unwrap();
+        assert_eq!(gfs.check_for_sync_file("models/chest.obj").unwrap(), FileSyncState::HashUnmatch);
+        assert_eq!(gfs.check_for_sync_file("readme.txt").unwrap(), FileSyncState::HashMatch);
+        assert!(gfs.reload_if_changed("models/chest.obj").unwrap());
+        assert_eq!(&gfs.read_file("models/chest.obj").unwrap()[..], b"bigger chest");
+    }
+    
+    #[test]
+    fn entries_past_the_end_are_refused() {
+        let root = test_root("pack_corrupt");
+        let tree = root.join("tree");
+        fs::create_dir_all(&tree).unwrap();
+        fs::write(tree.join("chest.obj"), b"chest").unwrap();
+        let pack = root.join("assets.gfspack");
+        write_pack(&tree, &mut fs::File::create(&pack).unwrap(), &Sha256Hasher).unwrap();
+        let written = fs::read(&pack).unwrap();
+        // magic, version, hash name, count, then the key, offset and length of the only entry
+        let len_at = 8 + 4 + 1 + 6 + 4 + 2 + 9 + 8;
+        assert_eq!(&written[len_at..len_at + 8], &5u64.to_le_bytes());
+        
+        for len in &[6, 1 << 40, u64::MAX] {
+            let mut corrupt = written.clone();
+            corrupt[len_at..len_at + 8].copy_from_slice(&len.to_le_bytes());
+            fs::write(&pack, &corrupt).unwrap();
+            let mut gfs = GemFileSystem::new(root.join("empty"));
+            match gfs.mount_pack(&pack, 0) {
+                Err(GfsError::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::InvalidData),
+                _ => panic!("mounted a pack with an entry of {} bytes", len),
+            }
+        }
+        fs::write(&pack, &written).unwrap();
+        let mut gfs = GemFileSystem::new(root.join("empty"));
+        gfs.mount_pack(&pack, 0).unwrap();
+        assert_eq!(&gfs.read_file("chest.obj").unwrap()[..], b"chest");
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    collections::HashMap,
+    io::{
+        self,
+        Read,
+        Write,
+    },
+};
+
+use crate::{
+    pack::{
+        read_u32,
+        read_u64,
+        read_u8,
+    },
+    ContentHasher,
+    GfsError,
+};
+
+const MAGIC: &[u8; 8] = b"GFSDELTA";
+const VERSION: u32 = 1;
+/// runs shared with the old version shorter than this are sent as they are
+const BLOCK: usize = 32;
+/// of the rolling hash over a block
+const BASE: u64 = 0x0000_0100_0000_01b3;
+
+const COPY: u8 = 0;
+const INSERT: u8 = 1;
+
+/// a binary diff from one version of a file, or of a whole pack, to the next, made with
+/// `Patch::diff`. it carries the hashes of both versions, so it is only applied to the content
+/// it was made from and what it produces is checked before it is used.
+/// as written out, the hash algorithm and both hashes, followed by the bytes to copy from
+/// the old version and the bytes to insert in between
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Patch {
+    /// the `ContentHasher::name` both hashes were computed with
+    pub hash_algorithm: String,
+    pub source_hash: Box<[u8]>,
+    pub target_hash: Box<[u8]>,
+    pub target_len: u64,
+    ops: Vec<Op>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Op {
+    /// a run of the old version
+    Copy {
+        offset: u64,
+        len: u64,
+    },
+    /// bytes the old version doesn't have
+    Insert(Vec<u8>),
+}
+
+impl Patch {
+    /// what turns `old` into `new`, reusing every run of at least 32 bytes they share,
+    /// wherever it is in `old`
+    pub fn diff(old: &[u8], new: &[u8], hasher: &dyn ContentHasher) -> Patch {
+        let mut blocks = HashMap::new();
+        for (index, block) in old.chunks_exact(BLOCK).enumerate() {
+            blocks.entry(rolling_hash(block)).or_insert(index * BLOCK);
+        }
+        let top = (1..BLOCK).fold(1u64, |power, _| power.wrapping_mul(BASE));
+        let mut ops = Vec::new();
+        let mut literal_start = 0;
+        let mut pos = 0;
+        let mut window = None;
+        while pos + BLOCK <= new.len() {
+            let hash = window.unwrap_or_else(|| rolling_hash(&new[pos..pos + BLOCK]));
+            let found = blocks.get(&hash).copied()
+                .filter(|&offset| old[offset..offset + BLOCK] == new[pos..pos + BLOCK]);
+            let offset = match found {
+                Some(offset) => offset,
+                None => {
+                    window = match pos + BLOCK < new.len() {
+                        true => Some(roll(hash, top, new[pos], new[pos + BLOCK])),
+                        false => None,
+                    };
+                    pos += 1;
+                    continue;
+                }
+            };
+            // grow the match both ways, backwards into the bytes not matched so far
+            let (mut start, mut source) = (pos, offset);
+            while start > literal_start && source > 0 && old[source - 1] == new[start - 1] {
+                start -= 1;
+                source -= 1;
+            }
+            let (mut end, mut source_end) = (pos + BLOCK, offset + BLOCK);
+            while end < new.len() && source_end < old.len() && old[source_end] == new[end] {
+                end += 1;
+                source_end += 1;
+            }
+            if start > literal_start {
+                ops.push(Op::Insert(new[literal_start..start].to_vec()));
+            }
+            ops.push(Op::Copy {
+                offset: source as u64,
+                len: (end - start) as u64,
+            });
+            pos = end;
+            literal_start = end;
+            window = None;
+        }
+        if literal_start < new.len() {
+            ops.push(Op::Insert(new[literal_start..].to_vec()));
+        }
+        Patch {
+            hash_algorithm: String::from(hasher.name()),
+            source_hash: hasher.hash_bytes(old).into_boxed_slice(),
+            target_hash: hasher.hash_bytes(new).into_boxed_slice(),
+            target_len: new.len() as u64,
+            ops,
+        }
+    }
+    
+    /// whether the patch was made from `old`, `GfsError::HashAlgorithmMismatch` if
+    /// it was hashed with another digest than `hasher`
+    pub fn applies_to(&self, old: &[u8], hasher: &dyn ContentHasher) -> Result<bool, GfsError> {
+        if self.hash_algorithm != hasher.name() {
+            return Err(GfsError::HashAlgorithmMismatch {
+                expected: String::from(hasher.name()),
+                found: self.hash_algorithm.clone(),
+            });
+        }
+        Ok(hasher.hash_bytes(old)[..] == self.source_hash[..])
+    }
+    
+    /// the new version of `old`, only if `old` is what the patch was made from
+    /// and the result hashes to what it should
+    pub fn apply(&self, old: &[u8], hasher: &dyn ContentHasher) -> Result<Vec<u8>, GfsError> {
+        if !self.applies_to(old, hasher)? {
+            return Err(invalid_patch(String::from("Patch was made from different content")));
+        }
+        let mut len = 0u64;
+        for op in &self.ops {
+            len += match op {
+                Op::Copy { offset, len } => match offset.checked_add(*len) {
+                    Some(end) if end <= old.len() as u64 => *len,
+                    _ => return Err(invalid_patch(String::from("Patch copies past the end of the content"))),
+                },
+                Op::Insert(bytes) => bytes.len() as u64,
+            };
+        }
+        if len != self.target_len {
+            return Err(invalid_patch(String::from("Patch does not add up to the patched length")));
+        }
+        let mut new = Vec::with_capacity(len as usize);
+        for op in &self.ops {
+            match op {
+                Op::Copy { offset, len } => new.extend_from_slice(&old[*offset as usize..(offset + len) as usize]),
+                Op::Insert(bytes) => new.extend_from_slice(bytes),
+            }
+        }
+        if hasher.hash_bytes(&new)[..] != self.target_hash[..] {
+            return Err(invalid_patch(String::from("Patched content does not match its hash")));
+        }
+        Ok(new)
+    }
+    
+    pub fn write<W: Write>(&self, out: &mut W) -> Result<(), GfsError> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        for field in &[self.hash_algorithm.as_bytes(), &self.source_hash, &self.target_hash] {
+            out.write_all(&[field.len() as u8])?;
+            out.write_all(field)?;
+        }
+        out.write_all(&self.target_len.to_le_bytes())?;
+        out.write_all(&(self.ops.len() as u32).to_le_bytes())?;
+        for op in &self.ops {
+            match op {
+                Op::Copy { offset, len } => {
+                    out.write_all(&[COPY])?;
+                    out.write_all(&offset.to_le_bytes())?;
+                    out.write_all(&len.to_le_bytes())?;
+                }
+                Op::Insert(bytes) => {
+                    out.write_all(&[INSERT])?;
+                    out.write_all(&(bytes.len() as u64).to_le_bytes())?;
+                    out.write_all(bytes)?;
+                }
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+    
+    pub fn read<R: Read>(reader: &mut R) -> Result<Patch, GfsError> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_patch(String::from("Not a gfs patch")));
+        }
+        let version = read_u32(reader)?;
+        if version != VERSION {
+            let mut err = String::from("Unsupported gfs patch version: ");
+            err.push_str(&format!("{}", version));
+            return Err(invalid_patch(err));
+        }
+        let hash_algorithm = String::from_utf8(read_field(reader)?.into_vec())
+            .map_err(|_| invalid_patch(String::from("Patch hash algorithm is not valid UTF-8")))?;
+        let source_hash = read_field(reader)?;
+        let target_hash = read_field(reader)?;
+        let target_len = read_u64(reader)?;
+        let count = read_u32(reader)?;
+        let mut ops = Vec::new();
+        for _ in 0..count {
+            let op = match read_u8(reader)? {
+                COPY => Op::Copy {
+                    offset: read_u64(reader)?,
+                    len: read_u64(reader)?,
+                },
+                INSERT => {
+                    // never allocate more than the patched file can take, whatever the patch says
+                    let len = read_u64(reader)?;
+                    if len > target_len {
+                        return Err(invalid_patch(String::from("Patch inserts more than the patched length")));
+                    }
+                    let mut bytes = vec![0u8; len as usize];
+                    reader.read_exact(&mut bytes)?;
+                    Op::Insert(bytes)
+                }
+                tag => {
+                    let mut err = String::from("Unknown patch operation: ");
+                    err.push_str(&format!("{}", tag));
+                    return Err(invalid_patch(err));
+                }
+            };
+            ops.push(op);
+        }
+        Ok(Patch {
+            hash_algorithm,
+            source_hash,
+            target_hash,
+            target_len,
+            ops,
+        })
+    }
+}
+
+fn rolling_hash(block: &[u8]) -> u64 {
+    block.iter().fold(0u64, |hash, &byte| hash.wrapping_mul(BASE).wrapping_add(byte as u64))
+}
+
+/// the hash of the window one byte further, `top` being BASE to the power of BLOCK - 1
+fn roll(hash: u64, top: u64, out: u8, byte: u8) -> u64 {
+    hash.wrapping_sub((out as u64).wrapping_mul(top)).wrapping_mul(BASE).wrapping_add(byte as u64)
+}
+
+fn read_field<R: Read>(reader: &mut R) -> io::Result<Box<[u8]>> {
+    let mut field = vec![0u8; read_u8(reader)? as usize].into_boxed_slice();
+    reader.read_exact(&mut field)?;
+    Ok(field)
+}
+
+fn invalid_patch(err: String) -> GfsError {
+    GfsError::Io(io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        GemFileSystem,
+        ReadFile,
+        Sha256Hasher,
+    };
+    use std::fs;
+    
+    /// bytes that don't repeat, like compressed texture data
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len).map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 56) as u8
+        }).collect()
+    }
+    
+    #[test]
+    fn patches_carry_only_what_changed() {
+        let old = noise(64 * 1024, 1);
+        let mut new = old.clone();
+        new[1000..1100].copy_from_slice(&noise(100, 2));
+        new.splice(40_000..40_000, noise(500, 3));
+        new.truncate(60_000);
+        let patch = Patch::diff(&old, &new, &Sha256Hasher);
+        let mut written = Vec::new();
+        patch.write(&mut written).unwrap();
+        assert!(written.len() < 1000);
+        let patch = Patch::read(&mut &written[..]).unwrap();
+        assert_eq!(patch.apply(&old, &Sha256Hasher).unwrap(), new);
+        assert!(patch.apply(&new, &Sha256Hasher).is_err());
+        assert!(Patch::read(&mut &written[..20]).is_err());
+    
+        let root = test_root("patch");
+        fs::write(root.join("world.pak"), &old).unwrap();
+        let gfs = GemFileSystem::new(&root);
+        assert_eq!(gfs.read_file("world.pak").unwrap().len(), old.len());
+        gfs.apply_patch("world.pak", &patch).unwrap();
+        assert_eq!(&gfs.read_file("world.pak").unwrap()[..], &new[..]);
+        assert_eq!(fs::read(root.join("world.pak")).unwrap(), new);
+        assert!(matches!(gfs.apply_patch("world.pak", &patch), Err(GfsError::HashMismatch { .. })));
+        assert_eq!(Patch::diff(&new, &new, &Sha256Hasher).apply(&new, &Sha256Hasher).unwrap(), new);
+        assert_eq!(Patch::diff(b"", b"tiny", &Sha256Hasher).apply(b"", &Sha256Hasher).unwrap(), b"tiny");
+    }
+}
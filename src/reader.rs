@@ -0,0 +1,185 @@
+use std::{
+    fs,
+    io::{
+        self,
+        Cursor,
+        Read,
+        Seek,
+        SeekFrom,
+    },
+    path::Path,
+};
+
+use crate::{
+    retry::Retrying,
+    FileContent,
+    GemFileSystem,
+    GfsError,
+    Tracking,
+};
+
+/// a file opened with `GemFileSystem::open`, read straight from disk unless it was cached
+pub struct GfsReader {
+    source: Source,
+}
+
+enum Source {
+    Cached(Cursor<FileContent>),
+    Disk(io::BufReader<Retrying<fs::File>>),
+}
+
+impl GfsReader {
+    /// the content comes from the cache rather than from disk
+    pub fn is_cached(&self) -> bool {
+        match self.source {
+            Source::Cached(_) => true,
+            Source::Disk(_) => false,
+        }
+    }
+}
+
+impl Read for GfsReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.source {
+            Source::Cached(content) => content.read(buf),
+            Source::Disk(file) => file.read(buf),
+        }
+    }
+}
+
+impl Seek for GfsReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match &mut self.source {
+            Source::Cached(content) => content.seek(pos),
+            Source::Disk(file) => file.seek(pos),
+        }
+    }
+}
+
+/// a reader telling its `Tracking` how many of the `len` bytes it is expected to yield it did,
+/// failing once the load is cancelled or its deadline passed
+pub(crate) struct Reported<'a, R> {
+    reader: R,
+    done: u64,
+    len: u64,
+    tracking: Tracking<'a>,
+}
+
+impl<'a, R: Read> Reported<'a, R> {
+    pub(crate) fn new(reader: R, len: u64, tracking: Tracking<'a>) -> Reported<'a, R> {
+        Reported {
+            reader,
+            done: 0,
+            len,
+            tracking,
+        }
+    }
+}
+
+impl<R: Read> Read for Reported<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.tracking.cancelled() {
+            // not `Interrupted`, which readers take as a cue to try again. `fetch` sees the
+            // load was cancelled and hands out `GfsError::Cancelled` instead
+            return Err(io::Error::other("load cancelled"));
+        }
+        if self.tracking.timed_out() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "read timed out"));
+        }
+        let n = self.reader.read(buf)?;
+        if n > 0 {
+            self.done += n as u64;
+            self.tracking.report(self.done, self.len.max(self.done));
+        }
+        Ok(n)
+    }
+}
+
+impl GemFileSystem {
+    /// open a file for streaming, e.g. a video or an audio bank too big to hold in memory.
+    /// a cached file is read from the cache, anything else from disk without being cached
+    pub fn open<P: AsRef<Path>>(&self, file_path: P) -> Result<GfsReader, GfsError> {
+        let file_path: &Path = &self.cache_key(file_path);
+        if let Some(content) = self.cached_content(file_path) {
+            self.cache().record_hit();
+            return Ok(GfsReader {
+                source: Source::Cached(Cursor::new(content)),
+            });
+        }
+        self.cache().record_miss();
+        match self.resolve(file_path) {
+            Some((absolute_path, _)) => {
+                debug!("{}", absolute_path.display());
+                Ok(GfsReader {
+                    source: Source::Disk(self.buffered(fs::File::open(absolute_path)?)),
+                })
+            }
+            None => Err(self.not_found_error(file_path)),
+        }
+    }
+    
+    /// only the `len` bytes at `offset` of a file, e.g. the header of a texture, copied from the
+    /// cache if it is cached and read from disk otherwise, like `open`. fewer bytes if the file
+    /// ends before, none if it ends before `offset`
+    pub fn read_range<P: AsRef<Path>>(&self, file_path: P, offset: u64, len: usize) -> Result<Box<[u8]>, GfsError> {
+        let mut reader = self.open(file_path)?;
+        let cached = reader.is_cached();
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut range = Vec::with_capacity(len);
+        reader.take(len as u64).read_to_end(&mut range)?;
+        if !cached {
+            self.cache().record_disk_read(range.len() as u64);
+        }
+        Ok(range.into_boxed_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        ReadFile,
+    };
+    
+    #[test]
+    fn streams_from_disk_or_cache() {
+        let root = test_root("open");
+        fs::write(root.join("bank.bin"), b"0123456789").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.stream_threshold(Some(4));
+    
+        let mut tail = String::new();
+        let mut reader = gfs.open("bank.bin").unwrap();
+        assert!(!reader.is_cached());
+        reader.seek(SeekFrom::Start(6)).unwrap();
+        reader.read_to_string(&mut tail).unwrap();
+        assert_eq!(tail, "6789");
+    
+        // too big to be kept by read_file
+        assert_eq!(&gfs.read_file("bank.bin").unwrap()[..], b"0123456789");
+        assert!(gfs.cache().content_map.is_empty());
+    
+        gfs.stream_threshold(None);
+        gfs.read_file("bank.bin").unwrap();
+        let mut reader = gfs.open("bank.bin").unwrap();
+        assert!(reader.is_cached());
+        assert_eq!(reader.seek(SeekFrom::End(-2)).unwrap(), 8);
+        assert!(matches!(gfs.open("missing.bin"), Err(GfsError::NotFound { .. })));
+    }
+    
+    #[test]
+    fn ranges_read_only_what_is_asked() {
+        let root = test_root("range");
+        fs::write(root.join("texture.ktx2"), b"HEADERpixels").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        assert_eq!(&gfs.read_range("texture.ktx2", 0, 6).unwrap()[..], b"HEADER");
+        assert_eq!(gfs.stats().bytes_read_from_disk, 6);
+        assert!(gfs.cache().content_map.is_empty());
+        
+        gfs.read_file("texture.ktx2").unwrap();
+        assert_eq!(&gfs.read_range("texture.ktx2", 6, 100).unwrap()[..], b"pixels");
+        assert!(gfs.read_range("texture.ktx2", 100, 4).unwrap().is_empty());
+        assert!(matches!(gfs.read_range("missing.ktx2", 0, 4), Err(GfsError::NotFound { .. })));
+    }
+}
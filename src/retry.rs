@@ -0,0 +1,233 @@
+use std::{
+    io::{
+        self,
+        Read,
+        Seek,
+        SeekFrom,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::GfsError;
+
+/// how often a read failing with `ErrorKind::Interrupted` or `ErrorKind::WouldBlock` is tried
+/// before it fails for good with `ErrorKind::TimedOut`, see `GemFileSystem::retry_policy`.
+/// every other error is handed on right away
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// tries in all, the first one included. 1 never retries
+    pub attempts: u32,
+    /// the wait before the first retry, doubled for every one after it
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 3,
+            backoff: Duration::from_millis(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// try every read once
+    pub fn never() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 1,
+            backoff: Duration::ZERO,
+        }
+    }
+    
+    /// `op`, tried again while it fails with a transient error and attempts are left. once
+    /// they run out the error is made terminal, `read_to_end` and `io::copy` would otherwise
+    /// keep trying an `Interrupted` read forever
+    pub(crate) fn run<T, E: Transient, F: FnMut() -> Result<T, E>>(&self, mut op: F) -> Result<T, E> {
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match op() {
+                Err(err) if err.is_transient() && attempt < self.attempts => {
+                    debug!("transient read error, trying again in {:?}", backoff);
+                    thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                    attempt += 1;
+                }
+                Err(err) if err.is_transient() => return Err(err.exhausted(self.attempts)),
+                result => return result,
+            }
+        }
+    }
+    
+    pub(crate) fn reader<R>(&self, reader: R) -> Retrying<R> {
+        Retrying {
+            reader,
+            policy: *self,
+        }
+    }
+}
+
+/// errors worth another try
+pub(crate) trait Transient: Sized {
+    fn is_transient(&self) -> bool;
+    
+    /// the error to fail with once it happened on all of `attempts`
+    fn exhausted(self, attempts: u32) -> Self;
+}
+
+impl Transient for io::Error {
+    fn is_transient(&self) -> bool {
+        matches!(self.kind(), io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock)
+    }
+    
+    fn exhausted(self, attempts: u32) -> io::Error {
+        io::Error::new(io::ErrorKind::TimedOut, format!("still failing after {} attempts: {}", attempts, self))
+    }
+}
+
+impl Transient for GfsError {
+    fn is_transient(&self) -> bool {
+        match self {
+            GfsError::Io(err) => err.is_transient(),
+            _ => false,
+        }
+    }
+    
+    fn exhausted(self, attempts: u32) -> GfsError {
+        match self {
+            GfsError::Io(err) => GfsError::Io(err.exhausted(attempts)),
+            err => err,
+        }
+    }
+}
+
+/// a reader retrying every read as its `RetryPolicy` says
+pub(crate) struct Retrying<R> {
+    reader: R,
+    policy: RetryPolicy,
+}
+
+impl<R: Read> Read for Retrying<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let reader = &mut self.reader;
+        self.policy.run(|| reader.read(buf))
+    }
+}
+
+impl<R: Seek> Seek for Retrying<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let reader = &mut self.reader;
+        self.policy.run(|| reader.seek(pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        BackendMeta,
+        ContentHasher,
+        FileBackend,
+        GemFileSystem,
+        ReadFile,
+        Sha256Hasher,
+    };
+    use std::{
+        path::{
+            Path,
+            PathBuf,
+        },
+        sync::{
+            atomic::{
+                AtomicU32,
+                Ordering,
+            },
+            Arc,
+        },
+    };
+    
+    /// fails with `kind` the first `failures` reads, then hands out `content`
+    struct Flaky {
+        content: &'static [u8],
+        failures: u32,
+        kind: io::ErrorKind,
+    }
+    
+    impl Read for Flaky {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(io::Error::new(self.kind, "flaky"));
+            }
+            self.content.read(buf)
+        }
+    }
+    
+    fn flaky(failures: u32, kind: io::ErrorKind) -> Flaky {
+        Flaky {
+            content: b"tile data",
+            failures,
+            kind,
+        }
+    }
+    
+    #[test]
+    fn transient_errors_are_retried() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            backoff: Duration::ZERO,
+        };
+        let expected = Sha256Hasher.hash_bytes(b"tile data");
+        let hash = Sha256Hasher.hash(&mut policy.reader(flaky(2, io::ErrorKind::WouldBlock)));
+        assert_eq!(hash.unwrap(), expected);
+        let hash = Sha256Hasher.hash(&mut policy.reader(flaky(3, io::ErrorKind::Interrupted)));
+        assert_eq!(hash.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        let hash = Sha256Hasher.hash(&mut policy.reader(flaky(1, io::ErrorKind::PermissionDenied)));
+        assert_eq!(hash.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        let hash = Sha256Hasher.hash(&mut RetryPolicy::never().reader(flaky(1, io::ErrorKind::WouldBlock)));
+        assert!(hash.is_err());
+    }
+    
+    /// a backend every read of which is interrupted, counting the reads
+    struct Interrupted(Arc<AtomicU32>);
+    
+    impl FileBackend for Interrupted {
+        fn open(&self, _: &Path) -> io::Result<Box<dyn Read + Send + '_>> {
+            Ok(Box::new(flaky(u32::MAX, io::ErrorKind::Interrupted)))
+        }
+    
+        fn read_all(&self, _: &Path) -> io::Result<Vec<u8>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Err(io::Error::new(io::ErrorKind::Interrupted, "flaky"))
+        }
+    
+        fn metadata(&self, _: &Path) -> io::Result<BackendMeta> {
+            Ok(BackendMeta::default())
+        }
+    
+        fn list(&self) -> io::Result<Vec<(PathBuf, u64)>> {
+            Ok(vec![(PathBuf::from("tile.bin"), 9)])
+        }
+    }
+    
+    #[test]
+    fn reads_give_up_once_attempts_run_out() {
+        let reads = Arc::new(AtomicU32::new(0));
+        let mut gfs = GemFileSystem::new(test_root("retry"));
+        gfs.retry_policy(RetryPolicy {
+            attempts: 3,
+            backoff: Duration::ZERO,
+        }).mount_backend("cdn", Interrupted(Arc::clone(&reads)), 0);
+        match gfs.read_file("tile.bin") {
+            Err(GfsError::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::TimedOut),
+            other => panic!("expected a terminal error, got {:?}", other.map(|content| content.len())),
+        }
+        assert_eq!(reads.load(Ordering::SeqCst), 3);
+    
+        // read_to_end and io::copy would retry an interrupted read forever
+        let mut buffered = io::BufReader::new(RetryPolicy::default().reader(flaky(u32::MAX, io::ErrorKind::Interrupted)));
+        assert_eq!(io::copy(&mut buffered, &mut io::sink()).unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+}
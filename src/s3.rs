@@ -0,0 +1,333 @@
+use std::{
+    io::{
+        self,
+        Read,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::{
+        Duration,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+use crate::{
+    backend::percent_encode,
+    manifest::to_hex,
+    BackendMeta,
+    ContentHasher,
+    throttle::throttled,
+    FileBackend,
+    Sha256Hasher,
+    Throttle,
+};
+
+/// how long a request may stall before it fails, unless `timeout` says otherwise
+const TIMEOUT: Duration = Duration::from_secs(30);
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+/// the objects under a prefix of a bucket of an S3-compatible object store, like AWS S3 or
+/// MinIO, mounted with `mount_backend`. a file "models/chest.obj" is the object
+/// "<prefix>models/chest.obj". requests are signed with AWS signature version 4, and sync
+/// checks compare the ETag of an object instead of downloading and hashing it
+pub struct S3Backend {
+    endpoint: String,
+    host: String,
+    region: String,
+    bucket: String,
+    prefix: String,
+    access_key: String,
+    secret_key: String,
+    agent: ureq::Agent,
+    throttle: Option<Throttle>,
+}
+
+impl S3Backend {
+    /// `endpoint` like "https://s3.eu-west-1.amazonaws.com", addressed path-style as
+    /// "<endpoint>/<bucket>/<key>". `prefix` like "game/v1/", or "" for the whole bucket
+    pub fn new(endpoint: &str, region: &str, bucket: &str, prefix: &str, access_key: &str, secret_key: &str)
+        -> S3Backend {
+        let endpoint = endpoint.trim_end_matches('/');
+        S3Backend {
+            endpoint: endpoint.to_string(),
+            host: endpoint.split("://").last().unwrap_or(endpoint).to_string(),
+            region: region.to_string(),
+            bucket: bucket.to_string(),
+            prefix: prefix.to_string(),
+            access_key: access_key.to_string(),
+            secret_key: secret_key.to_string(),
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+            throttle: None,
+        }
+    }
+    
+    /// how long a request may stall before it fails, 30 seconds by default
+    pub fn timeout(mut self, timeout: Duration) -> S3Backend {
+        self.agent = ureq::AgentBuilder::new().timeout(timeout).build();
+        self
+    }
+    
+    /// download objects no faster than `throttle` lets through, together with everything
+    /// else it was handed to. listings and HEAD requests aren't held back
+    pub fn throttle(mut self, throttle: &Throttle) -> S3Backend {
+        self.throttle = Some(throttle.clone());
+        self
+    }
+    
+    fn key(&self, file_path: &Path) -> String {
+        let components: Vec<_> = file_path.components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect();
+        format!("{}{}", self.prefix, components.join("/"))
+    }
+    
+    /// send a signed request for an object, or for the bucket itself with an empty `key`
+    fn request(&self, method: &str, key: &str, query: &[(&str, &str)]) -> io::Result<ureq::Response> {
+        let segments: Vec<_> = key.split('/').map(percent_encode).collect();
+        let uri = match key.is_empty() {
+            true => format!("/{}", percent_encode(&self.bucket)),
+            false => format!("/{}/{}", percent_encode(&self.bucket), segments.join("/")),
+        };
+        let mut query: Vec<_> = query.iter()
+            .map(|(name, value)| format!("{}={}", percent_encode(name), percent_encode(value)))
+            .collect();
+        query.sort();
+        let query = query.join("&");
+        let amz_date = amz_date(SystemTime::now());
+        let date = &amz_date[..8];
+        let payload_hash = to_hex(&sha256(b""));
+        let canonical_request = format!("{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+                                        method, uri, query, self.host, payload_hash, amz_date,
+                                        SIGNED_HEADERS, payload_hash);
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}",
+                                     amz_date, scope, to_hex(&sha256(canonical_request.as_bytes())));
+        let signing_key = [date, &self.region, "s3", "aws4_request"].iter()
+            .fold(format!("AWS4{}", self.secret_key).into_bytes(), |key, part| hmac(&key, part.as_bytes()));
+        let authorization = format!("AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                                    self.access_key, scope, SIGNED_HEADERS,
+                                    to_hex(&hmac(&signing_key, string_to_sign.as_bytes())));
+        let url = match query.is_empty() {
+            true => format!("{}{}", self.endpoint, uri),
+            false => format!("{}{}?{}", self.endpoint, uri, query),
+        };
+        let request = match method {
+            "HEAD" => self.agent.head(&url),
+            _ => self.agent.get(&url),
+        };
+        let answered = request.set("x-amz-date", &amz_date)
+            .set("x-amz-content-sha256", &payload_hash)
+            .set("Authorization", &authorization)
+            .call();
+        match answered {
+            Ok(response) => Ok(response),
+            Err(ureq::Error::Status(404, _)) => {
+                let mut err = String::from("No such object: ");
+                err.push_str(&format!("{:#?}", key));
+                Err(io::Error::new(io::ErrorKind::NotFound, err))
+            }
+            Err(ureq::Error::Status(status, _)) => {
+                let mut err = String::from("Object store request failed: ");
+                err.push_str(&format!("{} {:#?} answered {}", method, key, status));
+                Err(io::Error::other(err))
+            }
+            Err(err) => Err(io::Error::other(err.to_string())),
+        }
+    }
+}
+
+impl FileBackend for S3Backend {
+    fn open(&self, file_path: &Path) -> io::Result<Box<dyn Read + Send + '_>> {
+        Ok(Box::new(throttled(&self.throttle, self.request("GET", &self.key(file_path), &[])?.into_reader())))
+    }
+    
+    fn metadata(&self, file_path: &Path) -> io::Result<BackendMeta> {
+        let response = self.request("HEAD", &self.key(file_path), &[])?;
+        Ok(BackendMeta {
+            size: response.header("Content-Length").and_then(|size| size.parse().ok()).unwrap_or(0),
+            modified: None,
+            etag: response.header("ETag").map(String::from),
+        })
+    }
+    
+    fn list(&self) -> io::Result<Vec<(PathBuf, u64)>> {
+        let mut files = Vec::new();
+        let mut continuation: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", &self.prefix[..])];
+            if let Some(token) = &continuation {
+                query.push(("continuation-token", &token[..]));
+            }
+            let mut listing = String::new();
+            self.request("GET", "", &query)?.into_reader().read_to_string(&mut listing)?;
+            for object in elements(&listing, "Contents") {
+                let key = elements(object, "Key").first().map(|key| unescape(key));
+                let size = elements(object, "Size").first().and_then(|size| size.parse().ok());
+                if let (Some(key), Some(size)) = (key, size) {
+                    // keys ending in `/` stand for folders in most consoles
+                    match key.strip_prefix(&self.prefix[..]) {
+                        Some(relative) if !relative.is_empty() && !relative.ends_with('/') => {
+                            files.push((PathBuf::from(relative), size));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            continuation = match elements(&listing, "IsTruncated").first() {
+                Some(&"true") => elements(&listing, "NextContinuationToken").first().map(|token| unescape(token)),
+                _ => None,
+            };
+            if continuation.is_none() {
+                return Ok(files);
+            }
+        }
+    }
+    
+    fn throttle(&self) -> Option<&Throttle> {
+        self.throttle.as_ref()
+    }
+}
+
+/// what is between every `<tag>` and the `</tag>` after it
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        match rest.find(&close) {
+            Some(end) => {
+                found.push(&rest[..end]);
+                rest = &rest[end + close.len()..];
+            }
+            None => break,
+        }
+    }
+    found
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn sha256(bytes: &[u8]) -> Vec<u8> {
+    Sha256Hasher.hash_bytes(bytes)
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut block = [0u8; 64];
+    match key.len() > block.len() {
+        true => block[..32].copy_from_slice(&sha256(key)),
+        false => block[..key.len()].copy_from_slice(key),
+    }
+    let padded = |pad: u8| block.iter().map(|byte| byte ^ pad).collect::<Vec<u8>>();
+    let mut inner = padded(0x36);
+    inner.extend_from_slice(message);
+    let mut outer = padded(0x5c);
+    outer.extend(sha256(&inner));
+    sha256(&outer)
+}
+
+/// `now` as "20240131T235959Z", in UTC
+fn amz_date(now: SystemTime) -> String {
+    let seconds = now.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+    let (days, time) = ((seconds / 86400) as i64, seconds % 86400);
+    // days since 1970-01-01 to a date, as in Howard Hinnant's `civil_from_days`
+    let shifted = days + 719468;
+    let era = shifted.div_euclid(146097);
+    let day_of_era = shifted.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = match month_from_march < 10 {
+        true => month_from_march + 3,
+        false => month_from_march - 9,
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", year, month, day, time / 3600, time % 3600 / 60, time % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        FileSyncState,
+        GemFileSystem,
+        ReadFile,
+    };
+    use std::{
+        io::Write,
+        net::TcpListener,
+        sync::{
+            Arc,
+            Mutex,
+        },
+        thread,
+    };
+    
+    /// a bucket "assets" with the object "game/v1/levels/level1.txt", whose ETag is `etag`
+    fn serve(etag: Arc<Mutex<String>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = Vec::new();
+                let mut byte = [0u8; 1];
+                while !request.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+                    request.push(byte[0]);
+                }
+                let request = String::from_utf8_lossy(&request);
+                let etag = etag.lock().unwrap().clone();
+                let line = request.lines().next().unwrap_or("");
+                let response = match line.split(' ').take(2).collect::<Vec<_>>()[..] {
+                    _ if !request.contains("Authorization: AWS4-HMAC-SHA256 Credential=key/") => {
+                        String::from("HTTP/1.0 403 Forbidden\r\n\r\n")
+                    }
+                    ["GET", "/assets?list-type=2&prefix=game%2Fv1%2F"] => String::from(
+                        "HTTP/1.0 200 OK\r\n\r\n<ListBucketResult><IsTruncated>false</IsTruncated>\
+                         <Contents><Key>game/v1/levels/</Key><Size>0</Size></Contents>\
+                         <Contents><Key>game/v1/levels/level1.txt</Key><Size>7</Size></Contents>\
+                         </ListBucketResult>"),
+                    ["HEAD", "/assets/game/v1/levels/level1.txt"] => {
+                        format!("HTTP/1.0 200 OK\r\nETag: {}\r\nContent-Length: 7\r\n\r\n", etag)
+                    }
+                    ["GET", "/assets/game/v1/levels/level1.txt"] => {
+                        format!("HTTP/1.0 200 OK\r\nETag: {}\r\n\r\nlevel 1", etag)
+                    }
+                    _ => String::from("HTTP/1.0 404 Not Found\r\n\r\n"),
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        format!("http://{}", address)
+    }
+    
+    #[test]
+    fn objects_sync_by_etag() {
+        assert_eq!(amz_date(UNIX_EPOCH), "19700101T000000Z");
+        assert_eq!(amz_date(UNIX_EPOCH + Duration::from_secs(1_709_251_199)), "20240229T235959Z");
+    
+        let etag = Arc::new(Mutex::new(String::from("\"v1\"")));
+        let endpoint = serve(Arc::clone(&etag));
+        let mut gfs = GemFileSystem::new(test_root("s3"));
+        gfs.mount_backend("bucket", S3Backend::new(&endpoint, "us-east-1", "assets", "game/v1/", "key", "secret"), 0);
+    
+        assert_eq!(&gfs.read_file("levels/level1.txt").unwrap()[..], b"level 1");
+        assert_eq!(gfs.cache().meta_map[Path::new("levels/level1.txt")].etag.as_deref(), Some("\"v1\""));
+        assert!(gfs.read_file("levels/level2.txt").is_err());
+        assert_eq!(gfs.glob("**").collect::<Vec<_>>(), vec![PathBuf::from("levels/level1.txt")]);
+        assert_eq!(gfs.check_for_sync_file("levels/level1.txt").unwrap(), FileSyncState::HashMatch);
+        *etag.lock().unwrap() = String::from("\"v2\"");
+        assert_eq!(gfs.check_for_sync_file("levels/level1.txt").unwrap(), FileSyncState::HashUnmatch);
+    }
+}
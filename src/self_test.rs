@@ -0,0 +1,115 @@
+use std::{
+    env,
+    fs,
+    path::Path,
+    process,
+    time::{
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+use crate::{
+    FileSyncState,
+    GemFileSystem,
+    GfsError,
+    ReadFile,
+};
+
+const TEST_FILE: &str = "self_test.txt";
+
+/// the steps `GemFileSystem::self_test` goes through, in order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelfTestStage {
+    WriteFile,
+    ReadFile,
+    SyncMatch,
+    ModifyFile,
+    SyncUnmatch,
+}
+
+/// outcome of `GemFileSystem::self_test`, one entry per stage that was run.
+/// stages after the first failing one are not run
+#[derive(Debug)]
+pub struct SelfTestReport {
+    pub stages: Vec<(SelfTestStage, Result<(), String>)>,
+}
+
+impl SelfTestReport {
+    /// every stage ran and passed
+    pub fn passed(&self) -> bool {
+        let finished = self.stages.last().map(|(stage, _)| *stage) == Some(SelfTestStage::SyncUnmatch);
+        finished && self.stages.iter().all(|(_, result)| result.is_ok())
+    }
+    
+    fn record(&mut self, stage: SelfTestStage, result: Result<(), String>) -> bool {
+        let passed = result.is_ok();
+        self.stages.push((stage, result));
+        passed
+    }
+}
+
+impl GemFileSystem {
+    /// check that gfs works in this environment: write a file into a fresh temp directory,
+    /// read it, check it is in sync, modify it and check it is out of sync.
+    /// only failing to create the temp directory is an error, everything else is reported
+    pub fn self_test() -> Result<SelfTestReport, GfsError> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.subsec_nanos()).unwrap_or(0);
+        let mut root = env::temp_dir();
+        root.push(format!("gfs_self_test_{}_{}", process::id(), nanos));
+        fs::create_dir_all(&root).map_err(GfsError::Io)?;
+        
+        let report = run_stages(&root);
+        if let Err(err) = fs::remove_dir_all(&root) {
+            warn!("failed to clean up self test directory {}: {}", root.display(), err);
+        }
+        Ok(report)
+    }
+}
+
+fn run_stages(root: &Path) -> SelfTestReport {
+    let gfs = GemFileSystem::new(root);
+    let mut report = SelfTestReport {
+        stages: Vec::new(),
+    };
+    let test_file = root.join(TEST_FILE);
+    // each stage only runs if the one before passed
+    let passed = report.record(SelfTestStage::WriteFile, write(&test_file, b"gfs self test"))
+        && report.record(SelfTestStage::ReadFile, expect_content(&gfs, b"gfs self test"))
+        && report.record(SelfTestStage::SyncMatch, expect_sync(&gfs, FileSyncState::HashMatch))
+        && report.record(SelfTestStage::ModifyFile, write(&test_file, b"gfs self test, modified"))
+        && report.record(SelfTestStage::SyncUnmatch, expect_sync(&gfs, FileSyncState::HashUnmatch));
+    debug!("self test in {} passed: {}", root.display(), passed);
+    report
+}
+
+fn write(file: &Path, content: &[u8]) -> Result<(), String> {
+    fs::write(file, content).map_err(|err| err.to_string())
+}
+
+fn expect_content(gfs: &GemFileSystem, expected: &[u8]) -> Result<(), String> {
+    match gfs.read_file(TEST_FILE) {
+        Ok(content) if &content[..] == expected => Ok(()),
+        Ok(_) => Err(String::from("read back different content")),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn expect_sync(gfs: &GemFileSystem, expected: FileSyncState) -> Result<(), String> {
+    match gfs.check_for_sync_file(TEST_FILE) {
+        Ok(state) if state == expected => Ok(()),
+        Ok(state) => Err(format!("expected {:?}, got {:?}", expected, state)),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn self_test_passes() {
+        let report = GemFileSystem::self_test().unwrap();
+        assert!(report.passed(), "{:?}", report);
+    }
+}
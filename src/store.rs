@@ -0,0 +1,142 @@
+use std::io;
+#[cfg(feature = "mmap")]
+use std::sync::{
+    atomic::{
+        AtomicUsize,
+        Ordering,
+    },
+    Arc,
+};
+
+use crate::FileContent;
+
+/// where a `Cache` keeps the content it stores, see `Cache::content_store`, e.g. somewhere
+/// other than the regular heap for a cache that should not live there
+pub trait ContentStore: Send + Sync {
+    /// the handle the cache keeps instead of `content`, a copy of it or `content` itself
+    fn store(&self, content: FileContent) -> io::Result<FileContent>;
+}
+
+/// keeps content on the regular heap, as it was read. what a `Cache` uses by default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapStore;
+
+impl HeapStore {
+    pub fn new() -> HeapStore {
+        HeapStore
+    }
+}
+
+impl ContentStore for HeapStore {
+    fn store(&self, content: FileContent) -> io::Result<FileContent> {
+        Ok(content)
+    }
+}
+
+/// keeps every entry in its own anonymous memory map instead of on the heap, so large
+/// entries don't fragment the allocator and their pages go straight back to the OS once
+/// they are evicted and no caller holds them anymore. files mapped because of
+/// `GemFileSystem::mmap_extension` are off the heap already and are kept as they are.
+/// clones share their count of `mappings`
+#[cfg(feature = "mmap")]
+#[derive(Clone, Default)]
+pub struct MmapStore {
+    live: Arc<AtomicUsize>,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapStore {
+    pub fn new() -> MmapStore {
+        MmapStore::default()
+    }
+    
+    /// how many mappings made by this store are currently alive
+    pub fn mappings(&self) -> usize {
+        self.live.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl ContentStore for MmapStore {
+    fn store(&self, content: FileContent) -> io::Result<FileContent> {
+        if !content.is_heap() {
+            return Ok(content);
+        }
+        // empty entries still need a one byte mapping
+        let mut map = memmap2::MmapMut::map_anon(content.len().max(1))?;
+        map[..content.len()].copy_from_slice(&content);
+        self.live.fetch_add(1, Ordering::Relaxed);
+        Ok(FileContent::anonymous(AnonMap {
+            map,
+            len: content.len(),
+            live: self.live.clone(),
+        }))
+    }
+}
+
+/// a mapping made by `MmapStore`, unmapped when dropped
+#[cfg(feature = "mmap")]
+pub(crate) struct AnonMap {
+    map: memmap2::MmapMut,
+    len: usize,
+    live: Arc<AtomicUsize>,
+}
+
+#[cfg(feature = "mmap")]
+impl AnonMap {
+    pub(crate) fn bytes(&self) -> &[u8] {
+        &self.map[..self.len]
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl Drop for AnonMap {
+    fn drop(&mut self) {
+        self.live.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn exercise<S: ContentStore>(store: &S) -> Vec<FileContent> {
+        let entries: Vec<Vec<u8>> = (0..4u8)
+            .map(|i| vec![i; (1 << 20) + i as usize])
+            .chain(Some(Vec::new()))
+            .collect();
+        let stored: Vec<FileContent> = entries.iter()
+            .map(|content| store.store(FileContent::from(&content[..])).unwrap())
+            .collect();
+    
+        for (content, stored) in entries.iter().zip(&stored) {
+            assert_eq!(&stored[..], &content[..]);
+        }
+        stored
+    }
+    
+    #[test]
+    fn heap_store_keeps_content_as_is() {
+        let content = FileContent::from(vec![7u8; 64]);
+        assert!(FileContent::ptr_eq(&HeapStore::new().store(content.clone()).unwrap(), &content));
+        exercise(&HeapStore::new());
+    }
+    
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_store_round_trips_and_unmaps() {
+        let store = MmapStore::new();
+        let mut stored = exercise(&store);
+        assert!(stored.iter().all(|content| !content.is_heap()));
+        assert_eq!(store.mappings(), 5);
+        // storing a mapping again doesn't copy it
+        let again = store.store(stored[0].clone()).unwrap();
+        assert!(FileContent::ptr_eq(&again, &stored[0]));
+        drop(again);
+    
+        stored.truncate(1);
+        assert_eq!(store.mappings(), 1);
+        drop(stored);
+        assert_eq!(store.mappings(), 0);
+    }
+}
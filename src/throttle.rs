@@ -0,0 +1,213 @@
+use std::{
+    collections::VecDeque,
+    io::{
+        self,
+        Read,
+    },
+    sync::{
+        Arc,
+        Mutex,
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use crate::lock;
+
+/// how far back `Throttle::throughput` looks
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// a cap on how fast backends download, e.g. `Throttle::new(2 << 20, 256 << 10)` for 2 MiB
+/// a second in bursts of up to 256 KiB, so background downloads leave the game's own
+/// traffic room. clones share the cap: mounts handed the same throttle, see
+/// `HttpBackend::throttle`, download that fast together
+#[derive(Clone)]
+pub struct Throttle {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+struct Bucket {
+    /// bytes a second and the most bytes read at once, None for no cap
+    limit: Option<(f64, f64)>,
+    /// bytes to read right away, below 0 while readers wait for bytes they took
+    tokens: f64,
+    refilled: Instant,
+    /// bytes read within the last `WINDOW`, with when they were
+    recent: VecDeque<(Instant, u64)>,
+}
+
+impl Throttle {
+    pub fn new(bytes_per_sec: u64, burst: u64) -> Throttle {
+        let burst = burst.max(1) as f64;
+        Throttle::with_limit(Some((bytes_per_sec.max(1) as f64, burst)), burst)
+    }
+    
+    /// no cap, only counting what is downloaded for `throughput`
+    pub fn unlimited() -> Throttle {
+        Throttle::with_limit(None, 0.0)
+    }
+    
+    fn with_limit(limit: Option<(f64, f64)>, tokens: f64) -> Throttle {
+        Throttle {
+            bucket: Arc::new(Mutex::new(Bucket {
+                limit,
+                tokens,
+                refilled: Instant::now(),
+                recent: VecDeque::new(),
+            })),
+        }
+    }
+    
+    /// bytes a second read through the throttle, over the last second
+    pub fn throughput(&self) -> u64 {
+        let mut bucket = lock(&self.bucket);
+        bucket.forget_old(Instant::now());
+        bucket.recent.iter().map(|(_, bytes)| bytes).sum()
+    }
+    
+    /// whether `other` is a clone of this throttle
+    pub(crate) fn same(&self, other: &Throttle) -> bool {
+        Arc::ptr_eq(&self.bucket, &other.bucket)
+    }
+    
+    /// take up to `wanted` bytes out of the bucket, waiting until they are there
+    fn take(&self, wanted: usize) -> usize {
+        let (taken, wait) = {
+            let mut bucket = lock(&self.bucket);
+            let (rate, burst) = match bucket.limit {
+                Some(limit) => limit,
+                None => return wanted,
+            };
+            let now = Instant::now();
+            let refill = now.duration_since(bucket.refilled).as_secs_f64() * rate;
+            bucket.tokens = (bucket.tokens + refill).min(burst);
+            bucket.refilled = now;
+            let taken = (wanted as f64).min(burst);
+            bucket.tokens -= taken;
+            (taken as usize, Duration::from_secs_f64((-bucket.tokens).max(0.0) / rate))
+        };
+        // sleep without the lock, the bytes are taken already and later readers queue up behind
+        thread::sleep(wait);
+        taken
+    }
+    
+    /// `read` of the `taken` bytes were read, the rest go back to the bucket
+    fn record(&self, taken: usize, read: usize) {
+        let mut bucket = lock(&self.bucket);
+        if bucket.limit.is_some() {
+            bucket.tokens += (taken - read) as f64;
+        }
+        let now = Instant::now();
+        bucket.forget_old(now);
+        if read > 0 {
+            bucket.recent.push_back((now, read as u64));
+        }
+    }
+}
+
+impl Bucket {
+    fn forget_old(&mut self, now: Instant) {
+        while let Some(&(at, _)) = self.recent.front() {
+            match now.duration_since(at) > WINDOW {
+                true => self.recent.pop_front(),
+                false => break,
+            };
+        }
+    }
+}
+
+/// `reader`, read through `throttle` if there is one
+#[cfg_attr(not(any(feature = "http", feature = "s3")), allow(dead_code))]
+pub(crate) fn throttled<R>(throttle: &Option<Throttle>, reader: R) -> Throttled<R> {
+    Throttled {
+        reader,
+        throttle: throttle.clone(),
+    }
+}
+
+pub(crate) struct Throttled<R> {
+    reader: R,
+    throttle: Option<Throttle>,
+}
+
+impl<R: Read> Read for Throttled<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let throttle = match &self.throttle {
+            Some(throttle) => throttle,
+            None => return self.reader.read(buf),
+        };
+        let taken = throttle.take(buf.len());
+        let read = self.reader.read(&mut buf[..taken]);
+        throttle.record(taken, *read.as_ref().unwrap_or(&0));
+        read
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        BackendMeta,
+        FileBackend,
+        GemFileSystem,
+        ReadFile,
+    };
+    use std::{
+        path::{
+            Path,
+            PathBuf,
+        },
+    };
+    
+    /// serves "tile.bin" through a throttle, like a network backend would
+    struct Remote(Throttle);
+    
+    impl FileBackend for Remote {
+        fn open(&self, file_path: &Path) -> io::Result<Box<dyn Read + Send + '_>> {
+            match file_path == Path::new("tile.bin") {
+                true => Ok(Box::new(throttled(&Some(self.0.clone()), &[7u8; 30_000][..]))),
+                false => Err(io::Error::new(io::ErrorKind::NotFound, "no such file")),
+            }
+        }
+    
+        fn metadata(&self, file_path: &Path) -> io::Result<BackendMeta> {
+            self.open(file_path).map(|_| BackendMeta::default())
+        }
+    
+        fn list(&self) -> io::Result<Vec<(PathBuf, u64)>> {
+            Ok(vec![(PathBuf::from("tile.bin"), 30_000)])
+        }
+    
+        fn throttle(&self) -> Option<&Throttle> {
+            Some(&self.0)
+        }
+    }
+    
+    #[test]
+    fn downloads_keep_to_their_bandwidth() {
+        let root = test_root("throttle");
+        let throttle = Throttle::new(100_000, 10_000);
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.mount_backend("cdn", Remote(throttle.clone()), 1)
+            .mount_backend("mirror", Remote(throttle.clone()), 0);
+        let started = Instant::now();
+        assert_eq!(gfs.read_file("tile.bin").unwrap().len(), 30_000);
+        // the first 10 KB are a burst, the other 20 KB take at least 200 ms at 100 KB a second
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(190), "read 30 KB in {:?}", elapsed);
+        // both mounts share the throttle, so it is counted once
+        let (stats, throughput) = (gfs.stats().download_bytes_per_sec, throttle.throughput());
+        // unless the machine is so slow the first bytes dropped out of the window already
+        if started.elapsed() <= WINDOW {
+            assert_eq!(stats, 30_000);
+            assert_eq!(throughput, 30_000);
+        }
+    
+        let mut unlimited = throttled(&Some(Throttle::unlimited()), &[0u8; 64][..]);
+        assert_eq!(io::copy(&mut unlimited, &mut io::sink()).unwrap(), 64);
+    }
+}
@@ -0,0 +1,148 @@
+use std::path::Path;
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+/// what a `Step` is doing, the name of its span
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Stage {
+    /// reading a file that isn't cached from wherever it is mounted
+    Fetch,
+    Hash,
+    #[cfg_attr(not(any(feature = "zstd", feature = "lz4")), allow(dead_code))]
+    Decompress,
+    /// storing loaded content in the cache
+    Cache,
+}
+
+/// one step of a load, traced with the "tracing" feature as a span from `step` until it is
+/// dropped. the span has the path, the bytes the step handled once `bytes` is called and
+/// how long it took in microseconds. without the feature it is nothing at all
+pub(crate) struct Step {
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+    #[cfg(feature = "tracing")]
+    started: Instant,
+}
+
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn step(stage: Stage, path: &Path) -> Step {
+    #[cfg(feature = "tracing")]
+    {
+        use tracing::field::Empty;
+        let path = path.display();
+        // span names have to be literals
+        let span = match stage {
+            Stage::Fetch => tracing::debug_span!("fetch", path = %path, bytes = Empty, duration_us = Empty),
+            Stage::Hash => tracing::debug_span!("hash", path = %path, bytes = Empty, duration_us = Empty),
+            Stage::Decompress => tracing::debug_span!("decompress", path = %path, bytes = Empty, duration_us = Empty),
+            Stage::Cache => tracing::debug_span!("cache", path = %path, bytes = Empty, duration_us = Empty),
+        };
+        Step {
+            span: span.entered(),
+            started: Instant::now(),
+        }
+    }
+    #[cfg(not(feature = "tracing"))]
+    Step {}
+}
+
+impl Step {
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn bytes(&self, bytes: usize) {
+        #[cfg(feature = "tracing")]
+        self.span.record("bytes", bytes as u64);
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for Step {
+    fn drop(&mut self) {
+        self.span.record("duration_us", self.started.elapsed().as_micros() as u64);
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::{
+        tests::test_root,
+        lock,
+        GemFileSystem,
+        ReadFile,
+    };
+    use std::{
+        fmt,
+        fs,
+        sync::{
+            Arc,
+            Mutex,
+        },
+    };
+    use tracing::{
+        field::{
+            Field,
+            Visit,
+        },
+        span,
+        Event,
+        Metadata,
+        Subscriber,
+    };
+    
+    /// the name of a span and the bytes recorded on it
+    type Traced = (&'static str, Option<u64>);
+    
+    /// every span opened
+    #[derive(Clone, Default)]
+    struct Spans(Arc<Mutex<Vec<Traced>>>);
+    
+    struct Bytes<'a>(&'a mut Option<u64>);
+    
+    impl Visit for Bytes<'_> {
+        fn record_debug(&mut self, _: &Field, _: &dyn fmt::Debug) {}
+    
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            if field.name() == "bytes" {
+                *self.0 = Some(value);
+            }
+        }
+    }
+    
+    impl Subscriber for Spans {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+    
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            let mut spans = lock(&self.0);
+            spans.push((span.metadata().name(), None));
+            span::Id::from_u64(spans.len() as u64)
+        }
+    
+        fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+            let mut spans = lock(&self.0);
+            values.record(&mut Bytes(&mut spans[span.into_u64() as usize - 1].1));
+        }
+    
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+    
+        fn event(&self, _: &Event<'_>) {}
+    
+        fn enter(&self, _: &span::Id) {}
+    
+        fn exit(&self, _: &span::Id) {}
+    }
+    
+    #[test]
+    fn loads_are_traced() {
+        let root = test_root("trace");
+        fs::write(root.join("map.bin"), b"tiles").unwrap();
+        let gfs = GemFileSystem::new(&root);
+        let spans = Spans::default();
+        tracing::subscriber::with_default(spans.clone(), || {
+            gfs.read_file("map.bin").unwrap();
+            gfs.read_file("map.bin").unwrap();
+        });
+        // served from the cache the second time, nothing to trace
+        assert_eq!(*lock(&spans.0), [("fetch", Some(5)), ("hash", Some(5)), ("cache", Some(5))]);
+    }
+}
@@ -0,0 +1,134 @@
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use crate::normalize;
+
+/// what a locale like "de-DE" falls back to, most specific first: "de-DE", then "de"
+pub(crate) fn locale_chain(locale: &str) -> Vec<&str> {
+    let mut chain = vec![locale];
+    let mut rest = locale;
+    while let Some(at) = rest.rfind(['-', '_']) {
+        rest = &rest[..at];
+        chain.push(rest);
+    }
+    chain
+}
+
+/// `pattern` filled in for `key` and the variant `value` stands for as `{name}`, e.g.
+/// "{dir}/{stem}.{locale}{ext}" for "ui/strings.json" and "de" makes "ui/strings.de.json".
+/// `{dir}` is the directory of `key`, `{file}` its file name, `{stem}` the file name
+/// without extension and `{ext}` the extension with its dot, if any. None if the result
+/// would leave the root or `key` isn't UTF-8
+pub(crate) fn render(pattern: &str, key: &Path, name: &str, value: &str) -> Option<PathBuf> {
+    let file = key.file_name()?.to_str()?;
+    let dir: Vec<_> = match key.parent() {
+        Some(dir) => dir.components().map(|component| component.as_os_str().to_str()).collect::<Option<_>>()?,
+        None => Vec::new(),
+    };
+    let (stem, ext) = match file.rfind('.') {
+        Some(at) if at > 0 => file.split_at(at),
+        _ => (file, ""),
+    };
+    let rendered = pattern.replace("{dir}", &dir.join("/"))
+        .replace("{file}", file)
+        .replace("{stem}", stem)
+        .replace("{ext}", ext)
+        .replace(&format!("{{{}}}", name), value);
+    // an empty `{dir}` leaves a leading separator behind
+    normalize(Path::new(rendered.trim_start_matches('/')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        GemFileSystem,
+        PathMapper,
+        ReadFile,
+    };
+    use std::fs;
+    
+    #[test]
+    fn locales_fall_back_to_the_base_file() {
+        assert_eq!(locale_chain("zh-Hant-TW"), ["zh-Hant-TW", "zh-Hant", "zh"]);
+        assert_eq!(render("{dir}/{stem}.{locale}{ext}", Path::new("strings"), "locale", "de"),
+                   Some(PathBuf::from("strings.de")));
+    
+        let root = test_root("locale");
+        fs::create_dir_all(root.join("ui/de")).unwrap();
+        fs::write(root.join("ui/strings.json"), b"base").unwrap();
+        fs::write(root.join("ui/strings.de.json"), b"de").unwrap();
+        fs::write(root.join("ui/menu.json"), b"menu").unwrap();
+        fs::write(root.join("ui/de/menu.json"), b"menu de").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        assert_eq!(&gfs.read_file("ui/strings.json").unwrap()[..], b"base");
+    
+        gfs.set_locale(Some("de-DE"));
+        assert_eq!(&gfs.read_file("ui/strings.json").unwrap()[..], b"de");
+        assert_eq!(&*gfs.map("ui/strings.json"), root.join("ui/strings.de.json"));
+        assert_eq!(&gfs.read_file("ui/menu.json").unwrap()[..], b"menu");
+        fs::write(root.join("ui/strings.de-DE.json"), b"de-DE").unwrap();
+        // resolutions are remembered until the files change
+        assert_eq!(&gfs.read_file("ui/strings.json").unwrap()[..], b"de");
+        gfs.apply_change_feed(["ui/strings.de-DE.json"]);
+        assert_eq!(&gfs.read_file("ui/strings.json").unwrap()[..], b"de-DE");
+    
+        gfs.locale_pattern("{dir}/{locale}/{file}");
+        assert_eq!(&gfs.read_file("ui/menu.json").unwrap()[..], b"menu de");
+        gfs.set_locale(Some("fr"));
+        assert_eq!(&gfs.read_file("ui/menu.json").unwrap()[..], b"menu");
+    }
+    
+    #[test]
+    fn extensionless_paths_pick_the_first_variant() {
+        let root = test_root("variant");
+        fs::create_dir_all(root.join("textures")).unwrap();
+        fs::write(root.join("textures/wood.ktx2"), b"ktx2").unwrap();
+        fs::write(root.join("textures/wood.dds"), b"dds").unwrap();
+        fs::write(root.join("textures/stone.dds"), b"stone dds").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.variant_extensions(&["ktx2", "dds"]);
+        assert_eq!(&gfs.read_file("textures/wood").unwrap()[..], b"ktx2");
+        assert_eq!(&*gfs.map("textures/wood"), root.join("textures/wood.ktx2"));
+        assert_eq!(&gfs.read_file("textures/stone").unwrap()[..], b"stone dds");
+        assert!(gfs.read_file("textures/glass").is_err());
+        
+        gfs.variant_extensions(&[".dds"]);
+        assert_eq!(&*gfs.map("textures/wood"), root.join("textures/wood.dds"));
+        fs::write(root.join("textures/wood.de.dds"), b"dds de").unwrap();
+        gfs.set_locale(Some("de"));
+        assert_eq!(&gfs.read_file("textures/wood").unwrap()[..], b"dds de");
+    }
+    
+    #[test]
+    fn quality_tiers_fall_back_to_lower_ones() {
+        let root = test_root("quality");
+        for tier in ["low", "high"] {
+            fs::create_dir_all(root.join(tier).join("textures")).unwrap();
+        }
+        fs::create_dir_all(root.join("textures")).unwrap();
+        fs::write(root.join("low/textures/rock.png"), b"rock low").unwrap();
+        fs::write(root.join("high/textures/rock.png"), b"rock high").unwrap();
+        fs::write(root.join("low/textures/moss.png"), b"moss low").unwrap();
+        fs::write(root.join("textures/sand.png"), b"sand").unwrap();
+        let mut gfs = GemFileSystem::new(&root);
+        gfs.quality_tiers(&["low", "medium", "high"], "{tier}/{dir}/{file}")
+            .set_quality(Some("high"));
+        assert_eq!(&gfs.read_file("textures/rock.png").unwrap()[..], b"rock high");
+        assert_eq!(&gfs.read_file("textures/moss.png").unwrap()[..], b"moss low");
+        assert_eq!(&gfs.read_file("textures/sand.png").unwrap()[..], b"sand");
+        
+        gfs.set_quality(Some("medium"));
+        assert_eq!(&gfs.read_file("textures/rock.png").unwrap()[..], b"rock low");
+        gfs.set_quality(None);
+        assert!(gfs.read_file("textures/rock.png").is_err());
+        
+        fs::write(root.join("textures/sand@high.png"), b"sand high").unwrap();
+        gfs.quality_tiers(&["low", "high"], "{dir}/{stem}@{tier}{ext}")
+            .set_quality(Some("high"));
+        assert_eq!(&gfs.read_file("textures/sand.png").unwrap()[..], b"sand high");
+    }
+}
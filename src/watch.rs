@@ -0,0 +1,214 @@
+use std::{
+    io,
+    path::PathBuf,
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering,
+        },
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use notify::{
+    EventKind,
+    RecursiveMode,
+    Watcher,
+};
+#[cfg(feature = "async")]
+use tokio::sync::mpsc;
+
+use crate::{
+    GemFileSystem,
+    GfsError,
+};
+
+/// how often the watcher runs the `on_change` callbacks of files that settled
+const DISPATCH_INTERVAL: Duration = Duration::from_millis(20);
+
+/// keeps `GemFileSystem::watch` running, stops watching when dropped
+pub struct GfsWatcher {
+    _watcher: notify::RecommendedWatcher,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for GfsWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl GemFileSystem {
+    /// watch `root`, every mount and every fallback root, invalidating the cache entry of a file as soon as
+    /// it changes on disk, e.g. an artist overwriting a texture while the game runs.
+    /// with `reload_on_change` set the file is read back right away, see `apply_change_feed`.
+    /// the watcher only holds a weak reference, so it never keeps the filesystem alive.
+    /// it also runs the `on_change` callbacks, on a thread of its own
+    pub fn watch(self: &Arc<GemFileSystem>) -> Result<GfsWatcher, GfsError> {
+        self.watch_with(|_| {})
+    }
+    
+    /// `watch`, also handing every batch of changes to an async task as a `FileChangeEvent`,
+    /// e.g. to upload the textures that changed to the GPU in one go. a batch is complete
+    /// once nothing changed for `debounce`
+    #[cfg(feature = "async")]
+    pub fn watch_stream(self: &Arc<GemFileSystem>, debounce: Duration) -> Result<ChangeStream, GfsError> {
+        let (sender, changes) = mpsc::unbounded_channel();
+        let watcher = self.watch_with(move |changed| {
+            // the stream may be gone already, its watcher is only just stopping
+            let _ = sender.send(changed);
+        })?;
+        Ok(ChangeStream {
+            _watcher: watcher,
+            changes,
+            debounce,
+        })
+    }
+    
+    /// `watch`, handing `changed` the cache keys of the files every event reports
+    fn watch_with<F>(self: &Arc<GemFileSystem>, changed: F) -> Result<GfsWatcher, GfsError>
+        where F: Fn(Vec<PathBuf>) + Send + 'static {
+        let gfs = Arc::downgrade(self);
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let gfs = match gfs.upgrade() {
+                Some(gfs) => gfs,
+                None => return,
+            };
+            match event {
+                Ok(event) => {
+                    let paths = on_event(&gfs, event);
+                    if !paths.is_empty() {
+                        changed(paths);
+                    }
+                }
+                Err(err) => warn!("file watcher error: {}", err),
+            }
+        }).map_err(watch_error)?;
+        for (root, _) in self.search_path() {
+            watcher.watch(root, RecursiveMode::Recursive).map_err(watch_error)?;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let gfs = Arc::downgrade(self);
+        thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                thread::sleep(DISPATCH_INTERVAL);
+                match gfs.upgrade() {
+                    Some(gfs) => gfs.dispatch_changes(),
+                    None => return,
+                };
+            }
+        });
+        Ok(GfsWatcher {
+            _watcher: watcher,
+            stop,
+        })
+    }
+}
+
+/// files that changed on disk within one debounce window, see `GemFileSystem::watch_stream`
+#[cfg(feature = "async")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileChangeEvent {
+    /// the cache keys of the changed files, sorted, each once
+    pub paths: Vec<PathBuf>,
+}
+
+/// the changes `GemFileSystem::watch_stream` sees, for an async task to go through with
+/// `while let Some(event) = stream.next().await`. stops watching when dropped
+#[cfg(feature = "async")]
+pub struct ChangeStream {
+    _watcher: GfsWatcher,
+    changes: mpsc::UnboundedReceiver<Vec<PathBuf>>,
+    debounce: Duration,
+}
+
+#[cfg(feature = "async")]
+impl ChangeStream {
+    /// the next batch of changes, once nothing changed for the debounce window. None if the
+    /// watcher stopped
+    pub async fn next(&mut self) -> Option<FileChangeEvent> {
+        let mut paths = self.changes.recv().await?;
+        // every change within the window joins the batch and starts the window over
+        while let Ok(Some(more)) = tokio::time::timeout(self.debounce, self.changes.recv()).await {
+            paths.extend(more);
+        }
+        paths.sort();
+        paths.dedup();
+        Some(FileChangeEvent { paths })
+    }
+}
+
+/// invalidate the files `event` is about, returning their cache keys
+fn on_event(gfs: &GemFileSystem, event: notify::Event) -> Vec<PathBuf> {
+    if let EventKind::Access(_) = event.kind {
+        return Vec::new();
+    }
+    // cache keys are relative to whichever root the file was found under
+    let changed: Vec<PathBuf> = event.paths.iter()
+        .filter_map(|absolute_path| {
+            gfs.search_path().into_iter()
+                .find_map(|(root, _)| absolute_path.strip_prefix(root).ok())
+                .map(|file_path| file_path.to_path_buf())
+        })
+        .collect();
+    let invalidated = gfs.apply_change_feed(&changed);
+    if invalidated > 0 {
+        debug!("file watcher invalidated {} cache entries", invalidated);
+    }
+    changed
+}
+
+fn watch_error(err: notify::Error) -> GfsError {
+    GfsError::Io(io::Error::other(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::test_root,
+        ReadFile,
+    };
+    use notify::event::{
+        AccessKind,
+        ModifyKind,
+    };
+    use std::{
+        fs,
+        path::Path,
+    };
+    
+    #[test]
+    fn events_invalidate_changed_files() {
+        let root = test_root("watch");
+        fs::create_dir_all(root.join("textures")).unwrap();
+        fs::write(root.join("textures/wood.png"), b"v1").unwrap();
+        let gfs = Arc::new(GemFileSystem::new(&root));
+        let _watcher = gfs.watch().unwrap();
+        gfs.read_file("textures/wood.png").unwrap();
+    
+        let wood = root.join("textures/wood.png");
+        on_event(&gfs, notify::Event::new(EventKind::Access(AccessKind::Any)).add_path(wood.clone()));
+        assert!(gfs.cache().content_map.contains_key(Path::new("textures/wood.png")));
+        on_event(&gfs, notify::Event::new(EventKind::Modify(ModifyKind::Any)).add_path(wood));
+        assert!(!gfs.cache().content_map.contains_key(Path::new("textures/wood.png")));
+    }
+    
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn changes_stream_in_debounced_batches() {
+        let root = test_root("watch_stream");
+        fs::write(root.join("wood.png"), b"v1").unwrap();
+        let gfs = Arc::new(GemFileSystem::new(&root));
+        let mut changes = gfs.watch_stream(Duration::from_millis(200)).unwrap();
+        fs::write(root.join("wood.png"), b"v2").unwrap();
+        fs::write(root.join("wood.png"), b"v3").unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(5), changes.next()).await.unwrap();
+        assert_eq!(event.unwrap().paths, vec![PathBuf::from("wood.png")]);
+        // both writes came in that one event
+        assert!(tokio::time::timeout(Duration::from_millis(500), changes.next()).await.is_err());
+    }
+}